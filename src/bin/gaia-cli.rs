@@ -0,0 +1,430 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use worlddatagen::data::datapack::{Datapack, DatapackError, Diagnostic, SerializableDatapack, Severity};
+use worlddatagen::data::project::{self, Project};
+use worlddatagen::data::reference::ResourceKey;
+use worlddatagen::data::rename::ResourceRename;
+use worlddatagen::data::vanilla::VanillaData;
+
+// Exit codes, following diff and most linters
+const EXIT_SUCCESS: i32 = 0;
+// The check ran but failed, such as a pack with errors or two packs which differ
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+// A pack could not be read or written
+const EXIT_ERROR: i32 = 3;
+
+// Options which take a value, written either as --option=value or --option value
+const VALUE_OPTIONS: [&str; 3] = ["--vanilla", "--to", "--output"];
+
+const USAGE: &str = "Usage:
+  gaia-cli [--vanilla <zip>] <command>
+
+Commands:
+  gaia-cli validate <pack> [--deny-warnings]
+  gaia-cli convert <input> <output> [--to zip|folder|project]
+  gaia-cli build <pack> <directory>
+  gaia-cli diff <old pack> <new pack>
+  gaia-cli rename <pack> <registry> <from> <to> [--output <pack>]
+  gaia-cli move-namespace <pack> <from> <to> [--output <pack>]
+
+Options taking a value can also be written as --option=value.
+
+Packs can be zips, datapack folders or Gaia project folders.
+Projects storing vanilla overrides as patches are applied to the vanilla data given with --vanilla,
+and any patched field which no longer applies cleanly is reported.
+Renames and moves list every change they make, and only write the result when given an output.
+
+Exit codes:
+  0  success
+  1  the pack has errors, or the packs differ
+  2  invalid arguments
+  3  a pack could not be read or written";
+
+///////////////////////////////////
+//------ Command Line Tool ------//
+///////////////////////////////////
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::process::exit(run(&args));
+}
+
+/// Runs a command, returning the exit code
+fn run(args: &[String]) -> i32 {
+    let (args, flags) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(message) => return usage_error(&message)
+    };
+
+    // Vanilla data can be given to any command which loads packs
+    let (vanilla_flags, flags): (Vec<Flag>, Vec<Flag>) = flags.into_iter().partition(|(name, _)| *name == "--vanilla");
+    let loader = match get_flag_value(&vanilla_flags, "--vanilla") {
+        Ok(vanilla) => match PackLoader::new(vanilla.as_deref()) {
+            Ok(loader) => loader,
+            Err(error) => {
+                eprintln!("error: Could not load vanilla data: {}", error);
+                return EXIT_ERROR
+            }
+        },
+        Err(message) => return usage_error(&message)
+    };
+
+    let result = match (args.as_slice(), flags.as_slice()) {
+        (["validate", pack], []) => validate(&loader, pack, false),
+        (["validate", pack], [("--deny-warnings", None)]) => validate(&loader, pack, true),
+        (["convert", input, output], _) => match get_flag_value(&flags, "--to") {
+            Ok(target) => convert(&loader, input, output, target.as_deref()),
+            Err(message) => return usage_error(&message)
+        },
+        (["build", pack, directory], []) => build(&loader, pack, directory),
+        (["diff", old, new], []) => diff(&loader, old, new),
+        (["rename", pack, registry, from, to], _) => match get_flag_value(&flags, "--output") {
+            Ok(output) => rename(&loader, pack, registry, from, to, output.as_deref()),
+            Err(message) => return usage_error(&message)
+        },
+        (["move-namespace", pack, from, to], _) => match get_flag_value(&flags, "--output") {
+            Ok(output) => move_namespace(&loader, pack, from, to, output.as_deref()),
+            Err(message) => return usage_error(&message)
+        },
+        (["help"], []) => {
+            println!("{}", USAGE);
+            return EXIT_SUCCESS
+        }
+        (_, [(flag, _), ..]) => return usage_error(&format!("Unknown option {}", flag)),
+        _ => return usage_error("Unknown command")
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            EXIT_ERROR
+        }
+    }
+}
+
+fn usage_error(message: &str) -> i32 {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    EXIT_USAGE
+}
+
+// An option and its value, if it was given one
+type Flag<'a> = (&'a str, Option<&'a str>);
+
+/// Splits arguments into positional arguments and options. Options listed in [`VALUE_OPTIONS`] take the
+/// argument after them as their value unless it was given with '='
+fn parse_args(args: &[String]) -> Result<(Vec<&str>, Vec<Flag<'_>>), String> {
+    let mut positional = Vec::new();
+    let mut flags = Vec::new();
+    let mut args = args.iter().map(String::as_str);
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            positional.push(arg);
+            continue
+        }
+
+        match arg.split_once('=') {
+            Some((name, value)) => flags.push((name, Some(value))),
+            None if VALUE_OPTIONS.contains(&arg) => match args.next() {
+                Some(value) => flags.push((arg, Some(value))),
+                None => return Err(format!("Missing value for {}", arg))
+            },
+            None => flags.push((arg, None))
+        }
+    }
+
+    Ok((positional, flags))
+}
+
+/// Reads the value of the given option, failing on any other option
+fn get_flag_value(flags: &[Flag], name: &str) -> Result<Option<String>, String> {
+    let mut value = None;
+
+    for (flag_name, flag_value) in flags {
+        match flag_value {
+            Some(flag_value) if *flag_name == name => value = Some(String::from(*flag_value)),
+            _ => return Err(format!("Unknown option {}", flag_name))
+        }
+    }
+
+    Ok(value)
+}
+
+//------------//
+
+/// Loads packs, applying projects which store patches to the vanilla data if it was given
+struct PackLoader {
+    vanilla: Option<Arc<VanillaData>>
+}
+
+impl PackLoader {
+    fn new(vanilla_path: Option<&str>) -> Result<Self, DatapackError> {
+        let vanilla = match vanilla_path {
+            Some(path) => Some(Arc::new(VanillaData::from_zips(&[path])?)),
+            None => None
+        };
+
+        Ok(Self { vanilla })
+    }
+
+    fn load(&self, path: &str) -> Result<(SerializableDatapack, Vec<Diagnostic>), DatapackError> {
+        let import = project::load_datapack(path, self.vanilla.clone())?;
+        Ok((import.datapack, import.diagnostics))
+    }
+}
+
+fn print_diagnostics(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+}
+
+fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+//------------//
+
+fn validate(loader: &PackLoader, path: &str, deny_warnings: bool) -> Result<i32, DatapackError> {
+    let (_, diagnostics) = loader.load(path)?;
+    print_diagnostics(&diagnostics);
+
+    let errors = diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error).count();
+    let warnings = diagnostics.len() - errors;
+    println!("{}: {} error(s), {} warning(s)", path, errors, warnings);
+
+    Ok(if errors > 0 || (deny_warnings && warnings > 0) { EXIT_FAILURE } else { EXIT_SUCCESS })
+}
+
+fn convert(loader: &PackLoader, input: &str, output: &str, target: Option<&str>) -> Result<i32, DatapackError> {
+    let (datapack, diagnostics) = loader.load(input)?;
+    print_diagnostics(&diagnostics);
+
+    if has_errors(&diagnostics) {
+        eprintln!("Conversion cancelled, {} has errors", input);
+        return Ok(EXIT_FAILURE)
+    }
+
+    let code = save_pack(datapack, output, target)?;
+    if code == EXIT_SUCCESS {
+        println!("Converted {} to {}", input, output);
+    }
+
+    Ok(code)
+}
+
+/// Writes a pack as a zip, folder or project, picking a zip or folder from the output path if no target is given
+fn save_pack(datapack: SerializableDatapack, output: &str, target: Option<&str>) -> Result<i32, DatapackError> {
+    let target = target.unwrap_or(if output.ends_with(".zip") { "zip" } else { "folder" });
+
+    match target {
+        "zip" => {
+            if let Some(parent) = Path::new(output).parent() {
+                fs::create_dir_all(parent)?;
+            }
+            datapack.to_zip(output)?
+        }
+        "folder" => datapack.to_directory(output)?,
+        "project" => Project::new(Datapack::try_from(datapack)?).save(output)?,
+        _ => return Ok(usage_error(&format!("Unknown conversion target {}", target)))
+    }
+
+    Ok(EXIT_SUCCESS)
+}
+
+fn build(loader: &PackLoader, path: &str, directory: &str) -> Result<i32, DatapackError> {
+    let (datapack, diagnostics) = loader.load(path)?;
+    print_diagnostics(&diagnostics);
+
+    if has_errors(&diagnostics) {
+        eprintln!("Build cancelled, {} has errors", path);
+        return Ok(EXIT_FAILURE)
+    }
+
+    let report = datapack.build(directory)?;
+    println!("{}", report);
+
+    Ok(if report.has_errors() { EXIT_FAILURE } else { EXIT_SUCCESS })
+}
+
+/// Compares two packs resource by resource, listing the fields which changed in each
+fn diff(loader: &PackLoader, old_path: &str, new_path: &str) -> Result<i32, DatapackError> {
+    let (old, _) = loader.load(old_path)?;
+    let (new, _) = loader.load(new_path)?;
+
+    let diff = old.diff(&new)?;
+    if diff.is_empty() {
+        println!("No differences");
+        return Ok(EXIT_SUCCESS)
+    }
+
+    println!("{}", diff);
+    Ok(EXIT_FAILURE)
+}
+
+/// Renames one resource of a pack, such as worldgen/biome mypack:dunes, along with every reference to it
+fn rename(loader: &PackLoader, path: &str, registry: &str, from: &str, to: &str, output: Option<&str>) -> Result<i32, DatapackError> {
+    let (datapack, _) = loader.load(path)?;
+
+    let (Some(from), Some(to)) = (ResourceKey::parse(registry, from), ResourceKey::parse(registry, to)) else {
+        return Ok(usage_error("Resources are written as namespace:path, or #namespace:path for tags"))
+    };
+    if from.is_tag() != to.is_tag() {
+        return Ok(usage_error("A tag can only be renamed to another tag"))
+    }
+
+    apply_rename(ResourceRename::rename(&datapack, &from, to.location)?, output)
+}
+
+/// Moves every file of a pack from one namespace to another, along with every reference to the moved resources
+fn move_namespace(loader: &PackLoader, path: &str, from: &str, to: &str, output: Option<&str>) -> Result<i32, DatapackError> {
+    let (datapack, _) = loader.load(path)?;
+    apply_rename(ResourceRename::move_namespace(&datapack, from, to)?, output)
+}
+
+/// Lists every change of a rename, then writes the renamed pack if there is an output
+fn apply_rename(rename: ResourceRename, output: Option<&str>) -> Result<i32, DatapackError> {
+    for change in rename.changes() {
+        println!("{}", change);
+    }
+
+    let Some(output) = output else {
+        println!("{} change(s), nothing written without --output", rename.changes().len());
+        return Ok(EXIT_SUCCESS)
+    };
+
+    let import = rename.finish()?;
+    print_diagnostics(&import.diagnostics);
+
+    let code = save_pack(import.datapack, output, None)?;
+    if code == EXIT_SUCCESS {
+        println!("Wrote {} change(s) to {}", rename.changes().len(), output);
+    }
+
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::*;
+
+    const PACK_INFO: &str = r#"{"pack": {"pack_format": 26, "description": "Test"}}"#;
+    const BIOME: &str = r#"{
+        "carvers": {}, "downfall": 0.5, "features": [], "has_precipitation": false,
+        "effects": {"fog_color": 12638463, "sky_color": 8103167, "water_color": 4159204, "water_fog_color": 329011},
+        "spawn_costs": {}, "spawners": {}, "temperature": 0.5
+    }"#;
+
+    /// A datapack folder holding the given files, removed again when dropped
+    struct TestPack(PathBuf);
+
+    impl TestPack {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let directory = std::env::temp_dir().join(format!("gaia_cli_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&directory);
+
+            for (path, contents) in files {
+                let path = directory.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+
+            Self(directory)
+        }
+
+        fn path(&self, path: &str) -> String {
+            self.0.join(path).to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for TestPack {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn run_args(args: &[&str]) -> i32 {
+        run(&args.iter().map(|arg| String::from(*arg)).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn options_take_values_with_or_without_equals() {
+        let args: Vec<String> = ["convert", "in", "--to", "project", "out", "--output=x", "--deny-warnings"].map(String::from).to_vec();
+        let (positional, flags) = parse_args(&args).unwrap();
+
+        assert_eq!(positional, ["convert", "in", "out"]);
+        assert_eq!(flags, [("--to", Some("project")), ("--output", Some("x")), ("--deny-warnings", None)]);
+        assert!(parse_args(&[String::from("--to")]).is_err());
+    }
+
+    #[test]
+    fn invalid_arguments_exit_with_usage_code() {
+        assert_eq!(run_args(&["help"]), EXIT_SUCCESS);
+        assert_eq!(run_args(&[]), EXIT_USAGE);
+        assert_eq!(run_args(&["frobnicate"]), EXIT_USAGE);
+        assert_eq!(run_args(&["validate", "pack", "--unknown"]), EXIT_USAGE);
+        assert_eq!(run_args(&["convert", "in", "out", "--to"]), EXIT_USAGE);
+        assert_eq!(run_args(&["convert", "in", "out", "--output", "x"]), EXIT_USAGE);
+    }
+
+    #[test]
+    fn validate_exits_with_failure_on_errors() {
+        let valid = TestPack::new("validate_valid", &[("pack.mcmeta", PACK_INFO), ("data/test/worldgen/biome/x.json", BIOME)]);
+        let invalid = TestPack::new("validate_invalid", &[("pack.mcmeta", PACK_INFO), ("data/test/worldgen/biome/x.json", "{")]);
+
+        assert_eq!(run_args(&["validate", &valid.path("")]), EXIT_SUCCESS);
+        assert_eq!(run_args(&["validate", &invalid.path("")]), EXIT_FAILURE);
+        assert_eq!(run_args(&["validate", &valid.path("missing")]), EXIT_ERROR);
+    }
+
+    #[test]
+    fn convert_accepts_the_documented_target_option() {
+        let pack = TestPack::new("convert", &[("pack.mcmeta", PACK_INFO), ("data/test/worldgen/biome/x.json", BIOME)]);
+
+        assert_eq!(run_args(&["convert", &pack.path(""), &pack.path("out/pack.zip")]), EXIT_SUCCESS);
+        assert!(Path::new(&pack.path("out/pack.zip")).is_file());
+
+        assert_eq!(run_args(&["convert", &pack.path(""), &pack.path("out/project"), "--to", "project"]), EXIT_SUCCESS);
+        assert!(Project::exists(&pack.path("out/project")));
+
+        assert_eq!(run_args(&["convert", &pack.path(""), &pack.path("out/folder"), "--to=folder"]), EXIT_SUCCESS);
+        assert!(Path::new(&pack.path("out/folder/pack.mcmeta")).is_file());
+
+        assert_eq!(run_args(&["convert", &pack.path(""), &pack.path("out/other"), "--to", "rar"]), EXIT_USAGE);
+    }
+
+    #[test]
+    fn convert_exits_with_failure_on_errors() {
+        let pack = TestPack::new("convert_invalid", &[("pack.mcmeta", PACK_INFO), ("data/test/worldgen/biome/x.json", "{")]);
+
+        assert_eq!(run_args(&["convert", &pack.path(""), &pack.path("out.zip")]), EXIT_FAILURE);
+        assert!(!Path::new(&pack.path("out.zip")).exists());
+    }
+
+    #[test]
+    fn diff_exits_with_failure_when_packs_differ() {
+        let old = TestPack::new("diff_old", &[("pack.mcmeta", PACK_INFO), ("data/test/worldgen/biome/x.json", BIOME)]);
+        let new = TestPack::new("diff_new", &[("pack.mcmeta", PACK_INFO), ("data/test/worldgen/biome/x.json", &BIOME.replace(r#""temperature": 0.5"#, r#""temperature": 0.8"#))]);
+
+        assert_eq!(run_args(&["diff", &old.path(""), &old.path("")]), EXIT_SUCCESS);
+        assert_eq!(run_args(&["diff", &old.path(""), &new.path("")]), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn rename_only_writes_with_an_output() {
+        let pack = TestPack::new("rename", &[("pack.mcmeta", PACK_INFO), ("data/test/worldgen/biome/x.json", BIOME)]);
+        let output = pack.path("renamed.zip");
+
+        assert_eq!(run_args(&["rename", &pack.path(""), "worldgen/biome", "test:x", "test:y"]), EXIT_SUCCESS);
+        assert!(!Path::new(&output).exists());
+
+        assert_eq!(run_args(&["rename", &pack.path(""), "worldgen/biome", "test:x", "test:y", "--output", &output]), EXIT_SUCCESS);
+        assert!(Path::new(&output).is_file());
+
+        assert_eq!(run_args(&["rename", &pack.path(""), "worldgen/biome", "#test:x", "test:y"]), EXIT_USAGE);
+    }
+}
@@ -3,7 +3,9 @@ use serde::{Deserialize, Serialize};
 use strum_macros::EnumString;
 use std::default::Default;
 use regex::Regex;
-use crate::data::datapack::{SerializableDataElement, DataHandler, SerializableDataHolder, FileElement, DatapackFormat};
+use serde_json::{Map, Value};
+use serde_with::skip_serializing_none;
+use crate::data::datapack::{SerializableDataElement, DataHandler, SerializableDataHolder, FileElement, SyncState};
 use crate::data::carver::CarverData;
 use crate::data::util::{BlockState, ItemStack, ResourceLocation};
 
@@ -24,13 +26,28 @@ impl SerializableBiomeData {
 }
 
 impl SerializableDataElement for SerializableBiomeData {
-    fn serialize(&self) -> String {
-        todo!()
+    fn serialize(&self) -> serde_json::Result<String> {
+        // Shared and format data are stored separately but live in the same json object.
+        // Modeled fields are written first, followed by unknown fields in the order they were read
+        let mut json = serde_json::to_value(&self.shared_data)?;
+        if let (Value::Object(shared), Value::Object(format)) = (&mut json, serde_json::to_value(&self.format_data)?) {
+            shared.extend(format);
+        }
+
+        serde_json::to_string_pretty(&json)
     }
 
     fn deserialize(json: String) -> serde_json::Result<Box<Self>> {
-        let shared_data: BiomeSharedData = serde_json::from_str(json.as_str())?;
+        let mut shared_data: BiomeSharedData = serde_json::from_str(json.as_str())?;
         let format_data: BiomeFormatData = serde_json::from_str(json.as_str())?;
+
+        // Format specific fields are read from the same object, so they also show up as unknown shared fields
+        if let Value::Object(format_fields) = serde_json::to_value(&format_data)? {
+            for key in format_fields.keys() {
+                shared_data.unknown_fields.shift_remove(key);
+            }
+        }
+
         Ok(Box::new(SerializableBiomeData::new(shared_data, format_data)))
     }
 }
@@ -39,29 +56,50 @@ impl FileElement for SerializableBiomeData {
     fn get_file_regex() -> &'static Regex {
         &BIOME_REG
     }
+
+    fn get_file_path(resource_location: &ResourceLocation) -> String {
+        format!("data/{}/worldgen/biome/{}.json", resource_location.namespace(), resource_location.id())
+    }
 }
 
 ////////////////////////////////////
 //------ Biome Data Storage ------//
 ////////////////////////////////////
 
+#[derive(Debug, Clone)]
 pub struct BiomeData {
-
+    data: SerializableDataHolder<SerializableBiomeData>,
+    sync_state: SyncState
 }
 
 impl BiomeData {
+    pub fn new(data: SerializableDataHolder<SerializableBiomeData>, sync_state: SyncState) -> Self {
+        Self { data, sync_state }
+    }
+
+    pub fn sync_state(&self) -> SyncState { self.sync_state }
+    pub fn set_sync_state(&mut self, sync_state: SyncState) { self.sync_state = sync_state }
+
+    pub fn data(&self) -> &SerializableDataHolder<SerializableBiomeData> {
+        &self.data
+    }
 
+    pub(crate) fn rename_overlay(&mut self, previous: &str, name: &str) {
+        self.data.rename_overlay(previous, name);
+    }
 }
 
 impl From<SerializableDataHolder<SerializableBiomeData>> for BiomeData {
     fn from(value: SerializableDataHolder<SerializableBiomeData>) -> Self {
-        todo!()
+        // Biomes which already differ between formats have to be edited per format
+        let sync_state = if value.get_overlay_data().is_empty() { SyncState::Synced } else { SyncState::Manual };
+        Self { data: value, sync_state }
     }
 }
 
-impl Into<SerializableDataHolder<SerializableBiomeData>> for BiomeData {
-    fn into(self) -> SerializableDataHolder<SerializableBiomeData> {
-        todo!()
+impl From<BiomeData> for SerializableDataHolder<SerializableBiomeData> {
+    fn from(value: BiomeData) -> Self {
+        value.data
     }
 }
 
@@ -73,20 +111,24 @@ impl DataHandler<SerializableBiomeData> for BiomeData {}
 //------ Biome Data Serialization ------//
 //////////////////////////////////////////
 
+#[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BiomeSharedData {
-    temperature: f32,
-    #[serde(default)]
+    temperature: f64,
+    #[serde(default, skip_serializing_if = "TemperatureModifier::is_default")]
     temperature_modifier: TemperatureModifier,
-    downfall: f32,
+    downfall: f64,
     effects: Effect,
     // TODO: Fix these categories
     //carvers: CarverList,
     //features: FeatureList,
     #[serde(default)]
-    creature_spawn_probability: Option<f32>
+    creature_spawn_probability: Option<f64>,
     // TODO: spawners entry and data
     // TODO: spawn costs
+    // Fields which are not modeled yet are kept so they are not lost on export
+    #[serde(flatten)]
+    unknown_fields: Map<String, Value>
 }
 
 //------------//
@@ -139,52 +181,61 @@ pub struct BiomeDataFormat6 {
 //------ Biome Data Serialization Helper Types ------//
 ///////////////////////////////////////////////////////
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, EnumString)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize, EnumString)]
 pub enum TemperatureModifier {
+    #[default]
     #[strum(to_string = "translate.biome.temperature_modifier.none")]
-    #[serde(alias = "none")]
+    #[serde(rename = "none")]
     None,
     #[strum(to_string = "translate.biome.temperature_modifier.frozen")]
-    #[serde(alias = "frozen")]
+    #[serde(rename = "frozen")]
     Frozen
 }
-impl Default for TemperatureModifier { fn default() -> Self { TemperatureModifier::None } }
+
+impl TemperatureModifier {
+    fn is_default(&self) -> bool { *self == Self::default() }
+}
 
 //------------//
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, EnumString)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize, EnumString)]
 pub enum GrassColorModifier {
+    #[default]
     #[strum(to_string = "translate.biome.grass_color_modifier.none")]
-    #[serde(alias = "none")]
+    #[serde(rename = "none")]
     None,
     #[strum(to_string = "translate.biome.grass_color_modifier.dark_forest")]
-    #[serde(alias = "dark_forest")]
+    #[serde(rename = "dark_forest")]
     DarkForest,
     #[strum(to_string = "translate.biome.grass_color_modifier.swamp")]
-    #[serde(alias = "swamp")]
+    #[serde(rename = "swamp")]
     Swamp
 }
-impl Default for GrassColorModifier { fn default() -> Self { GrassColorModifier::None } }
+
+impl GrassColorModifier {
+    fn is_default(&self) -> bool { *self == Self::default() }
+}
 
 //------------//
 
 // Used until Format 10
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, EnumString)]
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, EnumString)]
 pub enum LegacyPrecipitationCategory {
-    #[strum(to_string = "translate.biome.temperature_modifier.none")]
-    #[serde(alias = "none")]
+    #[strum(to_string = "translate.biome.precipitation.none")]
+    #[serde(rename = "none")]
     None,
-    #[strum(to_string = "translate.biome.temperature_modifier.frozen")]
-    #[serde(alias = "rain")]
+    #[default]
+    #[strum(to_string = "translate.biome.precipitation.rain")]
+    #[serde(rename = "rain")]
     Rain,
-    #[strum(to_string = "translate.biome.temperature_modifier.frozen")]
-    #[serde(alias = "snow")]
+    #[strum(to_string = "translate.biome.precipitation.snow")]
+    #[serde(rename = "snow")]
     Snow
 }
-impl Default for LegacyPrecipitationCategory { fn default() -> Self { LegacyPrecipitationCategory::Rain } }
 
 //------------//
 
+#[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Effect {
     fog_color: i32,
@@ -195,15 +246,19 @@ struct Effect {
     foliage_color: Option<i32>,
     #[serde(default)]
     grass_color: Option<i32>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "GrassColorModifier::is_default")]
     grass_color_modifier: GrassColorModifier,
     //#[serde(default)]
     //particle: Option<Particle>
     // TODO: Rest of spec
+    #[serde(flatten)]
+    unknown_fields: Map<String, Value>
 }
 
 //------------//
 
+// Not read yet, see the particle field of Effect
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "value")]
 enum Particle {
@@ -244,6 +299,7 @@ enum Particle {
 
 //------------//
 
+#[allow(dead_code)]
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 enum VibrationPositionSource {
@@ -260,6 +316,8 @@ enum VibrationPositionSource {
 
 //------------//
 
+// Not read yet, see the carvers field of BiomeSharedData
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct CarverList {
     air: Carver,
@@ -268,20 +326,42 @@ struct CarverList {
 
 //------------//
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 enum Carver {
-    ID(ResourceLocation),
-    CARVER(CarverData),
-    LIST(Vec<CarverData>)
+    Id(ResourceLocation),
+    Single(CarverData),
+    List(Vec<CarverData>)
 }
 
 //------------//
 
+// Not read yet, see the features field of BiomeSharedData
+#[allow(dead_code)]
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 struct FeatureList {
     // TODO: Implementation of the 11 steps - needs custom serialization
     // Feature placement has 11 steps (more info on wiki) in order
     // Feature placement must also be in the same order across all biomes
     //      per step so that'll be difficult to ensure
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::data::test_util;
+    use super::*;
+
+    #[test]
+    fn unknown_fields_are_kept_in_source_order() {
+        let mut json: Value = serde_json::from_str(&test_util::biome(0.8)).unwrap();
+        json["zzz_mod_field"] = Value::from(1);
+        json["aaa_mod_field"] = Value::from(2);
+
+        let biome = SerializableBiomeData::deserialize(json.to_string()).unwrap();
+        let exported: Value = serde_json::from_str(&biome.serialize().unwrap()).unwrap();
+
+        assert_eq!(exported, json);
+        let keys: Vec<&String> = exported.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["temperature", "downfall", "effects", "carvers", "features", "spawn_costs", "spawners", "zzz_mod_field", "aaa_mod_field", "has_precipitation"]);
+    }
+}
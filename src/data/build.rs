@@ -0,0 +1,230 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use crate::data::datapack::{DatapackError, DatapackFormat, Diagnostic, SerializableDatapack, Severity};
+use crate::data::overlay::DatapackFiles;
+use crate::data::util;
+
+////////////////////////////////
+//------ Datapack Builds ------//
+////////////////////////////////
+
+/// Every file created by a build, along with any problems found while building
+#[derive(Debug, Clone, Default)]
+pub struct BuildReport {
+    pub outputs: Vec<BuildOutput>,
+    pub diagnostics: Vec<Diagnostic>
+}
+
+impl BuildReport {
+    /// Checks for errors in the built datapack or in any of the files built from it
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter()
+            .chain(self.outputs.iter().flat_map(|output| &output.diagnostics))
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+impl Display for BuildReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Built {} file(s)", self.outputs.len())?;
+
+        for output in &self.outputs {
+            write!(f, "\n{}", output)?;
+        }
+
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n{}", diagnostic)?;
+        }
+
+        Ok(())
+    }
+}
+
+//------------//
+
+#[derive(Debug, Clone)]
+pub struct BuildOutput {
+    pub filepath: String,
+    pub min_format: DatapackFormat,
+    pub max_format: DatapackFormat,
+    // Problems found by validating the built file, other than those already found in the datapack it was built from
+    pub diagnostics: Vec<Diagnostic>
+}
+
+impl Display for BuildOutput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.min_format == self.max_format {
+            write!(f, "{} ({})", self.filepath, self.min_format)?;
+        }
+        else {
+            write!(f, "{} ({} to {})", self.filepath, self.min_format, self.max_format)?;
+        }
+
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n  {}", diagnostic)?;
+        }
+
+        Ok(())
+    }
+}
+
+//------------//
+
+impl SerializableDatapack {
+    /// Builds the datapack into zip files in the given directory. Formats from before overlays were added
+    /// cannot share a zip with differing data, so they get one zip for each group of formats with the same data,
+    /// and every newer format shares a single zip using overlays. Overlays declared for older formats hold the data
+    /// meant for them, which is applied to their zips directly. Formats from before overlays only read pack_format,
+    /// so their zips declare the newest format of their group and no supported_formats. Every zip is validated once built
+    pub fn build(&self, directory: &str) -> Result<BuildReport, DatapackError> {
+        fs::create_dir_all(directory)?;
+
+        let formats = self.get_supported_formats();
+        let (legacy_formats, overlay_formats): (Vec<DatapackFormat>, Vec<DatapackFormat>) = formats.iter()
+            .partition(|datapack_format| !datapack_format.supports_overlays());
+
+        let mut report = BuildReport {
+            diagnostics: self.validate(),
+            ..BuildReport::default()
+        };
+
+        if legacy_formats.is_empty() {
+            let filepath = format!("{}/{}.zip", directory, util::to_file_name(self.name()));
+            self.build_zip(&filepath, &formats, &mut report)?;
+            return Ok(report)
+        }
+
+        for group in self.group_legacy_formats(&legacy_formats)? {
+            let (first, last) = (group[0], group[group.len() - 1]);
+            let filepath = format!("{}/{}", directory, Self::get_build_filename(self.name(), first, last));
+
+            self.for_build_format(last).build_zip(&filepath, &group, &mut report)?;
+        }
+
+        if let (Some(first), Some(last)) = (overlay_formats.first(), overlay_formats.last()) {
+            let filepath = format!("{}/{}", directory, Self::get_build_filename(self.name(), *first, *last));
+
+            self.with_formats(*first, *last).build_zip(&filepath, &overlay_formats, &mut report)?;
+        }
+
+        Ok(report)
+    }
+
+    fn build_zip(&self, filepath: &str, formats: &[DatapackFormat], report: &mut BuildReport) -> Result<(), DatapackError> {
+        let plan = self.plan_overlays()?;
+        report.diagnostics.extend(plan.diagnostics.iter().cloned());
+
+        Self::write_zip(filepath, self.export_planned_files(plan)?)?;
+
+        let built = SerializableDatapack::from_zip(filepath)?;
+        let diagnostics = built.diagnostics.into_iter()
+            .filter(|diagnostic| !report.diagnostics.iter().any(|reported| reported.to_string() == diagnostic.to_string()))
+            .collect();
+
+        report.outputs.push(BuildOutput {
+            filepath: String::from(filepath),
+            min_format: formats[0],
+            max_format: formats[formats.len() - 1],
+            diagnostics
+        });
+
+        Ok(())
+    }
+
+    /// Groups consecutive formats which load exactly the same data
+    fn group_legacy_formats(&self, formats: &[DatapackFormat]) -> Result<Vec<Vec<DatapackFormat>>, DatapackError> {
+        let mut groups: Vec<(Vec<DatapackFormat>, DatapackFiles)> = Vec::new();
+
+        for datapack_format in formats {
+            let files = self.for_build_format(*datapack_format).export_stored_files()?;
+
+            match groups.last_mut() {
+                Some((group, group_files)) if *group_files == files => group.push(*datapack_format),
+                _ => groups.push((vec![*datapack_format], files))
+            }
+        }
+
+        Ok(groups.into_iter().map(|(group, _)| group).collect())
+    }
+
+    fn get_build_filename(name: &str, min_format: DatapackFormat, max_format: DatapackFormat) -> String {
+        let name = util::to_file_name(name);
+        if min_format == max_format {
+            format!("{}_{}.zip", name, min_format.format())
+        }
+        else {
+            format!("{}_{}-{}.zip", name, min_format.format(), max_format.format())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use serde_json::Value;
+    use crate::data::datapack::{DatapackFormat, SerializableDatapack};
+    use crate::data::test_util;
+
+    #[test]
+    fn legacy_overlay_data_gets_its_own_zip() {
+        let pack_info = test_util::pack_info(26, Some((7, 26)), &[("legacy", 7, 7)]);
+        let (root_biome, legacy_biome) = (test_util::biome(0.8), test_util::biome(0.1));
+
+        let import = test_util::import(&[
+            ("pack.mcmeta", &pack_info),
+            ("data/test/worldgen/biome/x.json", &root_biome),
+            ("legacy/data/test/worldgen/biome/x.json", &legacy_biome)
+        ]);
+
+        let directory = std::env::temp_dir().join(format!("gaia_build_test_{}", std::process::id()));
+        let report = import.datapack.build(directory.to_str().unwrap()).unwrap();
+
+        let ranges: Vec<(u32, u32)> = report.outputs.iter()
+            .map(|output| (output.min_format.format(), output.max_format.format()))
+            .collect();
+        assert_eq!(ranges, vec![(7, 7), (8, 15), (18, 26)]);
+
+        let temperature = |filename: &str| {
+            let import = SerializableDatapack::from_zip(directory.join(filename).to_str().unwrap()).unwrap();
+            let files = import.datapack.for_format(DatapackFormat::new(26)).export_stored_files().unwrap();
+            serde_json::from_slice::<Value>(&files["data/test/worldgen/biome/x.json"]).unwrap()["temperature"].as_f64()
+        };
+
+        assert_eq!(temperature("test_7.zip"), Some(0.1));
+        assert_eq!(temperature("test_8-15.zip"), Some(0.8));
+        assert_eq!(temperature("test_18-26.zip"), Some(0.8));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn legacy_zips_only_declare_pack_format() {
+        let pack_info = test_util::pack_info(26, Some((7, 26)), &[("legacy", 7, 7)]);
+        let import = test_util::import(&[
+            ("pack.mcmeta", &pack_info),
+            ("data/test/worldgen/biome/x.json", &test_util::biome(0.8)),
+            ("legacy/data/test/worldgen/biome/x.json", &test_util::biome(0.1))
+        ]);
+
+        let directory = std::env::temp_dir().join(format!("gaia_legacy_build_test_{}", std::process::id()));
+        let report = import.datapack.build(directory.to_str().unwrap()).unwrap();
+
+        let pack_info = |filename: &str| {
+            let file = fs::File::open(directory.join(filename)).unwrap();
+            let mut zip = zip::ZipArchive::new(file).unwrap();
+            serde_json::from_reader::<_, Value>(zip.by_name("pack.mcmeta").unwrap()).unwrap()
+        };
+
+        for (filename, pack_format) in [("test_7.zip", 7), ("test_8-15.zip", 15)] {
+            let pack_info = pack_info(filename);
+            assert_eq!(pack_info["pack"]["pack_format"], pack_format);
+            assert!(pack_info["pack"].get("supported_formats").is_none());
+        }
+        assert!(pack_info("test_18-26.zip")["pack"].get("supported_formats").is_some());
+
+        // Each zip is validated by itself, and none of them declare formats their versions cannot read
+        assert!(report.outputs.iter().all(|output| output.diagnostics.is_empty()), "{}", report);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use regex::Regex;
-use crate::data::datapack::{SerializableDataElement, DataHandler, FileElement};
+use crate::data::datapack::{SerializableDataElement, FileElement};
+use crate::data::util::ResourceLocation;
 
 #[derive(Debug)]
 pub struct CarverElement {
@@ -12,11 +13,11 @@ impl CarverElement {
 }
 
 impl SerializableDataElement for CarverElement {
-    fn serialize(&self) -> String {
+    fn serialize(&self) -> serde_json::Result<String> {
         todo!()
     }
 
-    fn deserialize(json: String) -> serde_json::Result<Box<Self>> {
+    fn deserialize(_json: String) -> serde_json::Result<Box<Self>> {
         todo!()
     }
 }
@@ -25,6 +26,10 @@ impl FileElement for CarverElement {
     fn get_file_regex() -> &'static Regex {
         todo!()
     }
+
+    fn get_file_path(resource_location: &ResourceLocation) -> String {
+        format!("data/{}/worldgen/configured_carver/{}.json", resource_location.namespace(), resource_location.id())
+    }
 }
 
 /////////////////////////////////////
@@ -1,116 +1,208 @@
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::error::Error;
-use std::fmt::{Debug, Display, Formatter, write};
+use std::fmt::{Debug, Display, Formatter};
+use std::fs;
 use std::fs::File;
 use std::io;
-use std::io::Read;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use lazy_static::lazy_static;
-use serde::{Deserialize, Deserializer, Serialize};
-use serde_repr::{Deserialize_repr, Serialize_repr};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
 use regex::Regex;
-use strum_macros::{Display, FromRepr};
-use zip::read::ZipFile;
+use strum_macros::Display;
 use zip::result::ZipError;
-use zip::ZipArchive;
-use crate::data::datapack::DatapackFormat::*;
-use crate::data::biome::SerializableBiomeData;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use crate::data::biome::{BiomeData, SerializableBiomeData};
+use crate::data::tag::TagData;
 use crate::data::util;
+use crate::data::overlay::{self, DatapackFiles, OverlayPlan};
 use crate::data::util::{ColorParseError, ResourceLocation, SerializableText};
+use crate::data::validate::{DeclaredRange, FormatDeclarations};
 
 //////////////////////////////////
 //------ Datapack Formats ------//
 //////////////////////////////////
 
-#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd, Serialize_repr, Deserialize_repr, FromRepr)]
-#[repr(u8)]
-pub enum DatapackFormat {
-    Format6 = 6,
-    Format7 = 7,
-    Format8 = 8,
-    Format9 = 9,
-    Format10 = 10,
-    Format12 = 12,
-    Format15 = 15,
-    Format18 = 18,
-    Format26 = 26,
-    #[default]
-    Format41 = 41
-}
+/// A datapack format number. Formats missing from the bundled registry keep their number, so datapacks
+/// made for newer or snapshot versions can still be opened
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DatapackFormat(u32);
 
 impl DatapackFormat {
-    pub fn get_version_range(&self) -> [(i32, i32); 2] {
-        use DatapackFormat::*;
-        match *self {
-            Format6 => [(16, 2), (16, 5)],
-            Format7 => [(17, 0), (17, 1)],
-            Format8 => [(18, 0), (18, 1)],
-            Format9 => [(18, 2), (18, 2)],
-            Format10 => [(19, 0), (19, 3)],
-            Format12 => [(19, 4), (19, 4)],
-            Format15 => [(20, 0), (20, 1)],
-            Format18 => [(20, 2), (20, 2)],
-            Format26 => [(20, 3), (20, 4)],
-            Format41 => [(20, 5), (20, 5)],
-        }
+    pub const fn new(format: u32) -> Self {
+        Self(format)
+    }
+
+    /// Reads a format number from pack.mcmeta, which is stored as a signed integer
+    pub fn from_int(format: i32) -> Result<Self, DatapackError> {
+        u32::try_from(format)
+            .map(Self)
+            .map_err(|_| DatapackError::Format(format!("Invalid datapack format {format}").into()))
+    }
+
+    pub fn format(&self) -> u32 { self.0 }
+
+    pub fn get_info(&self) -> Option<&'static FormatInfo> {
+        FORMAT_REGISTRY.iter().find(|info| info.format == self.0)
+    }
+
+    pub fn is_known(&self) -> bool {
+        self.get_info().is_some()
+    }
+
+    /// Gets the first and last game versions using this format, if the format is known
+    pub fn get_version_range(&self) -> Option<(&'static str, &'static str)> {
+        self.get_info().map(|info| (info.min_version.as_str(), info.max_version.as_str()))
     }
 
     pub fn supports_overlays(&self) -> bool {
-        *self >= Self::get_minimum_overlay_version()
+        match self.get_info() {
+            Some(info) => info.supports_overlays,
+            None => *self >= Self::get_minimum_overlay_version()
+        }
     }
 
     pub fn get_minimum_overlay_version() -> Self {
-        Format18
+        FORMAT_REGISTRY.iter()
+            .find(|info| info.supports_overlays)
+            .map(|info| Self(info.format))
+            .expect("Pack format registry should contain a format supporting overlays")
+    }
+
+    /// Unknown formats use the layout of the closest known format below them
+    pub fn get_directory_layout(&self) -> DirectoryLayout {
+        FORMAT_REGISTRY.iter()
+            .rev()
+            .find(|info| info.format <= self.0)
+            .or(FORMAT_REGISTRY.first())
+            .map(|info| info.directory_layout)
+            .unwrap_or_default()
+    }
+
+    pub fn get_latest() -> Self {
+        FORMAT_REGISTRY.last()
+            .map(|info| Self(info.format))
+            .expect("Pack format registry should not be empty")
+    }
+}
+
+impl Default for DatapackFormat {
+    fn default() -> Self {
+        Self::get_latest()
     }
 }
 
 impl Display for DatapackFormat {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let version_range = self.get_version_range();
-        let min = version_range[0];
-        let max = version_range[1];
-
-        if min == max {
-            write!(f, "1.{}.{}", min.0, min.1)
-        }
-        else {
-            write!(f, "1.{}.{}-1.{}.{}", min.0, min.1, max.0, max.1)
+        match self.get_version_range() {
+            Some((min, max)) if min == max => write!(f, "{}", min),
+            Some((min, max)) => write!(f, "{}-{}", min, max),
+            None => write!(f, "Unknown format {}", self.0)
         }
     }
 }
 
-pub const DATAPACK_FORMATS: [DatapackFormat; 10] = [
-    Format6,
-    Format7,
-    Format8,
-    Format9,
-    Format10,
-    Format12,
-    Format15,
-    Format18,
-    Format26,
-    Format41,
+//------------//
+
+/// An entry in the bundled pack format registry
+#[derive(Debug, Clone, Deserialize)]
+pub struct FormatInfo {
+    pub format: u32,
+    pub min_version: String,
+    pub max_version: String,
+    pub supports_overlays: bool,
+    pub directory_layout: DirectoryLayout
+}
+
+/// Whether registry folders use plural names such as `functions`, or the singular names used from 1.21
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirectoryLayout {
+    #[default]
+    Plural,
+    Singular
+}
+
+// Registry directories which were renamed to their singular names, along with the tags of those registries
+const RENAMED_DIRECTORIES: &[(&str, &str)] = &[
+    ("advancements", "advancement"),
+    ("functions", "function"),
+    ("item_modifiers", "item_modifier"),
+    ("loot_tables", "loot_table"),
+    ("predicates", "predicate"),
+    ("recipes", "recipe"),
+    ("structures", "structure"),
+    ("tags/blocks", "tags/block"),
+    ("tags/entity_types", "tags/entity_type"),
+    ("tags/fluids", "tags/fluid"),
+    ("tags/functions", "tags/function"),
+    ("tags/game_events", "tags/game_event"),
+    ("tags/items", "tags/item")
 ];
 
+impl DirectoryLayout {
+    /// Gets the layout a data file is stored for, along with its path in the other layout. Files in registries which
+    /// were never renamed, such as everything in worldgen, are read by both layouts and give None
+    pub fn of_path(path: &str) -> Option<(DirectoryLayout, String)> {
+        let (namespace, path) = path.strip_prefix("data/")?.split_once('/')?;
+
+        RENAMED_DIRECTORIES.iter().find_map(|(plural, singular)| {
+            if let Some(id) = path.strip_prefix(plural).and_then(|id| id.strip_prefix('/')) {
+                Some((DirectoryLayout::Plural, format!("data/{}/{}/{}", namespace, singular, id)))
+            }
+            else {
+                let id = path.strip_prefix(singular)?.strip_prefix('/')?;
+                Some((DirectoryLayout::Singular, format!("data/{}/{}/{}", namespace, plural, id)))
+            }
+        })
+    }
+}
+
+impl Display for DirectoryLayout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DirectoryLayout::Plural => write!(f, "plural"),
+            DirectoryLayout::Singular => write!(f, "singular")
+        }
+    }
+}
+
+lazy_static! {
+    static ref FORMAT_REGISTRY: Vec<FormatInfo> = serde_json::from_str(include_str!("../../resources/data/pack_formats.json"))
+        .expect("Bundled pack format registry should be valid");
+
+    /// Every known datapack format, oldest first
+    pub static ref DATAPACK_FORMATS: Vec<DatapackFormat> = FORMAT_REGISTRY.iter()
+        .map(|info| DatapackFormat(info.format))
+        .collect();
+}
+
 ///////////////////////////////////
 //------ Datapack Handling ------//
 ///////////////////////////////////
 
 lazy_static! {
     static ref DATAPACK_NAME_REG: Regex = Regex::new(r"^(?:.+/)+(.+).zip").unwrap();
-    static ref NAMESPACE_REG: Regex = Regex::new(r"data/([a-z0-9_.-]+)").unwrap();
-    static ref DATA_REG: Regex = Regex::new(r"data/.+").unwrap();
+    static ref NAMESPACE_REG: Regex = Regex::new(r"^(?:[a-z0-9_-]+/)?data/([a-z0-9_.-]+)/").unwrap();
+    static ref DATA_REG: Regex = Regex::new(r"^(?:[a-z0-9_-]+/)?data/.+").unwrap();
     static ref OVERLAY_REG: Regex = Regex::new(r"^([a-z0-9_-]+)/data/(.+)").unwrap();
+    static ref OVERLAY_DIRECTORY_REG: Regex = Regex::new(r"^[a-z0-9_-]+$").unwrap();
 }
 
 //------------//
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SerializableDatapack {
     name: String,
     pub pack_info: SerializablePackInfo,
 
-    biomes: HashMap<ResourceLocation, Box<SerializableDataHolder<SerializableBiomeData>>>
+    biomes: HashMap<ResourceLocation, Box<SerializableDataHolder<SerializableBiomeData>>>,
+    // Files which are not modeled, keyed by path relative to the root or overlay directory
+    passthrough: HashMap<String, Box<SerializableDataHolder<PassthroughFile>>>
 }
 
 impl SerializableDatapack {
@@ -119,20 +211,19 @@ impl SerializableDatapack {
             name,
             pack_info,
 
-            biomes: HashMap::new()
+            biomes: HashMap::new(),
+            passthrough: HashMap::new()
         }
     }
 
     //------ File Handling ------//
 
-    pub fn from_zip(filepath: &str) -> Result<SerializableDatapack, DatapackError> {
-        let zip_file = File::open(&filepath)?;
+    pub fn from_zip(filepath: &str) -> Result<DatapackImport, DatapackError> {
+        let zip_file = File::open(filepath)?;
         let mut archive = ZipArchive::new(zip_file)?;
 
         // Load pack info
-        let mut pack_info_str = String::new();
-        archive.by_name("pack.mcmeta")?.read_to_string(&mut pack_info_str)?;
-        let pack_info: SerializablePackInfo = serde_json::from_str(&*pack_info_str)?;
+        let pack_info = Self::read_pack_info(archive.by_name("pack.mcmeta")?)?;
 
         let name = if let Some(filename_cap) = DATAPACK_NAME_REG.captures(filepath) {
             filename_cap.get(1).unwrap().as_str()
@@ -143,92 +234,644 @@ impl SerializableDatapack {
 
 
         let mut datapack = SerializableDatapack::empty(String::from(name), pack_info);
+        let mut diagnostics = datapack.check_pack_info();
 
         // Iterate through files in the archive
         // Must be done by index instead of iterator to allow mutable access to contents
         for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
+            let mut file = match archive.by_index(i) {
+                Ok(file) => file,
+                Err(error) => {
+                    diagnostics.push(Diagnostic::error(DatapackError::from(error)));
+                    continue
+                }
+            };
+            let name = file.name().to_owned();
 
-            let data_source = Self::get_data_source(&mut file, &datapack)?;
-            Self::import_data(&mut file, &mut datapack, data_source)?;
+            Self::import_file(&name, &mut file, &mut datapack, &mut diagnostics);
         }
 
-        Ok(datapack)
+        diagnostics.extend(datapack.validate());
+
+        Ok(DatapackImport { datapack, diagnostics })
     }
 
-    fn get_data_source(file: &mut ZipFile, datapack: &SerializableDatapack) -> Result<SerializableDataSource, DatapackError> {
-        let name = file.name().to_owned();
+    pub fn from_directory(directory: &str) -> Result<DatapackImport, DatapackError> {
+        let root = Path::new(directory);
+
+        // Load pack info
+        let pack_info = Self::read_pack_info(File::open(root.join("pack.mcmeta"))?)
+            .map_err(|error| error.with_path("pack.mcmeta"))?;
 
-        let mut data_source = SerializableDataSource::Root;
-        let mut overlay_directory: Option<&str> = None;
+        let name = root.file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("Untitled");
 
-        // Is the current file part of the base data or the overlay?
-        if let Some(overlay_cap) = OVERLAY_REG.captures(&*name) {
-            // If it is in an overlay, either get the existing overlay or make a new one with the
-            // overlay folder name obtained from the file path and then set that as the target data holder
-            overlay_directory = Some(overlay_cap.get(1).unwrap().as_str());
+        let mut datapack = SerializableDatapack::empty(String::from(name), pack_info);
+        let mut diagnostics = datapack.check_pack_info();
+
+        let mut filepaths = Vec::new();
+        Self::collect_files(root, &mut filepaths)?;
+        filepaths.sort();
+
+        for filepath in filepaths {
+            // Paths are matched the same way as zip entries, relative to the pack root and separated by '/'
+            let name = filepath.strip_prefix(root)
+                .expect("Collected files should be inside the datapack directory")
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            let mut file = match File::open(&filepath) {
+                Ok(file) => file,
+                Err(error) => {
+                    diagnostics.push(Diagnostic::error(DatapackError::from(error).with_path(&name)));
+                    continue
+                }
+            };
+
+            Self::import_file(&name, &mut file, &mut datapack, &mut diagnostics);
+        }
+
+        diagnostics.extend(datapack.validate());
+
+        Ok(DatapackImport { datapack, diagnostics })
+    }
+
+    /// Loads a datapack from the contents of its files, keyed by path relative to the pack root
+    pub fn from_files(name: &str, files: &DatapackFiles) -> Result<DatapackImport, DatapackError> {
+        let pack_info = files.get("pack.mcmeta")
+            .ok_or_else(|| DatapackError::File(String::from("Missing pack.mcmeta").into()))?;
+        let pack_info = Self::read_pack_info(pack_info.as_slice())?;
+
+        let mut datapack = SerializableDatapack::empty(String::from(name), pack_info);
+        let mut diagnostics = datapack.check_pack_info();
+
+        for (name, contents) in files {
+            Self::import_file(name, &mut contents.as_slice(), &mut datapack, &mut diagnostics);
+        }
+
+        diagnostics.extend(datapack.validate());
+
+        Ok(DatapackImport { datapack, diagnostics })
+    }
+
+    fn read_pack_info(mut file: impl Read) -> Result<SerializablePackInfo, DatapackError> {
+        let mut pack_info_str = String::new();
+        file.read_to_string(&mut pack_info_str)?;
+        serde_json::from_str(&pack_info_str)
+            .map_err(|error| DatapackError::from(error).with_path("pack.mcmeta"))
+    }
+
+    /// Checks pack info which is read as is, but must be valid when converted into a datapack
+    fn check_pack_info(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        if let Err(error) = util::Text::try_from(self.pack_info.pack.description.clone()) {
+            diagnostics.push(Diagnostic::error(error.with_path("pack.mcmeta")));
+        }
+
+        for filter in Filter::from_pack_filter(&self.pack_info.filter) {
+            if let Err(error) = filter.validate() {
+                diagnostics.push(Diagnostic::error(error.with_path("pack.mcmeta")));
+            }
+        }
+
+        // Unknown formats are kept as they are, as they are most likely from a newer or snapshot version
+        let mut formats = BTreeSet::from([self.pack_info.pack.pack_format.format() as i32]);
+
+        if let Some(format_range) = &self.pack_info.pack.supported_formats {
+            let (min, max) = format_range.bounds();
+            formats.extend([min, max]);
+        }
+
+        if let Some(overlays) = &self.pack_info.overlays {
+            for overlay in &overlays.entries {
+                let (min, max) = overlay.formats.bounds();
+                formats.extend([min, max]);
+            }
+        }
+
+        for format in formats {
+            match DatapackFormat::from_int(format) {
+                Ok(datapack_format) if !datapack_format.is_known() => {
+                    diagnostics.push(Diagnostic::warning(DatapackError::Format(
+                        format!("Unknown datapack format {}, data for it may not be read correctly", format).into()
+                    ).with_path("pack.mcmeta")));
+                }
+                Err(error) => diagnostics.push(Diagnostic::error(error.with_path("pack.mcmeta"))),
+                _ => {}
+            }
+        }
+
+        diagnostics
+    }
 
-            if let Some(overlay) = datapack.pack_info.get_overlay(overlay_directory.unwrap()) {
-                data_source = SerializableDataSource::Overlay(overlay);
+    /// Collects every file below a directory. Hidden files and directories, such as a `.git` folder the pack
+    /// is tracked in, are not part of the pack and are skipped
+    pub(crate) fn collect_files(directory: &Path, filepaths: &mut Vec<PathBuf>) -> Result<(), DatapackError> {
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue
+            }
+
+            let path = entry.path();
+
+            if path.is_dir() {
+                Self::collect_files(&path, filepaths)?;
             }
             else {
-                return Err(DatapackError::Overlay(format!("Overlay directory {} found, but not declared in pack info!", overlay_directory.unwrap())))
+                filepaths.push(path);
             }
         }
 
-        Ok(data_source)
+        Ok(())
     }
 
-    fn import_data(file: &mut ZipFile, datapack: &mut SerializableDatapack, data_source: SerializableDataSource) -> Result<(), DatapackError> {
-        let name = file.name();
+    /// Imports a single file, recording any problems instead of stopping the import
+    fn import_file(name: &str, file: &mut impl Read, datapack: &mut SerializableDatapack, diagnostics: &mut Vec<Diagnostic>) {
+        let data_source = match Self::get_data_source(name, datapack) {
+            Ok(data_source) => data_source,
+            Err(error) => {
+                // Undeclared overlays are ignored by the game, but their files are kept as they are under their full path
+                // so that they are not lost. They are never modeled, as they would replace the root data they shadow
+                diagnostics.push(Diagnostic::warning(error.with_path(name)));
+                if let Err(error) = Self::import_passthrough(name, file, datapack, SerializableDataSource::Root) {
+                    diagnostics.push(Diagnostic::error(error.with_path(name)));
+                }
+                return
+            }
+        };
+
+        if let Err(mut error) = Self::import_data(name, file, datapack, data_source.clone()) {
+            error = error.with_path(name);
+            if let SerializableDataSource::Overlay(overlay) = &data_source {
+                error = error.with_overlay(&overlay.directory);
+            }
+
+            diagnostics.push(Diagnostic::error(error));
+        }
+    }
+
+    fn get_data_source(name: &str, datapack: &SerializableDatapack) -> Result<SerializableDataSource, DatapackError> {
+        // Is the current file part of the base data or the overlay?
+        if let Some(overlay_cap) = OVERLAY_REG.captures(name) {
+            // If it is in an overlay, get the overlay declared for the folder name obtained
+            // from the file path and set that as the target data holder
+            let overlay_directory = overlay_cap.get(1).unwrap().as_str();
+
+            return match datapack.pack_info.get_overlay(overlay_directory) {
+                Some(overlay) => Ok(SerializableDataSource::Overlay(overlay)),
+                None => Err(DatapackError::Overlay(format!("Overlay directory {} found, but not declared in pack info!", overlay_directory).into()))
+            }
+        }
+
+        Ok(SerializableDataSource::Root)
+    }
+
+    /// Stores a file in the datapack. Files which cannot be modeled are still kept as passthrough
+    /// data, and the reason they could not be modeled is returned as an error
+    fn import_data(name: &str, file: &mut impl Read, datapack: &mut SerializableDatapack, data_source: SerializableDataSource) -> Result<(), DatapackError> {
+        // Directories hold no data themselves, and pack info is loaded separately
+        if name.ends_with('/') || name == "pack.mcmeta" {
+            return Ok(())
+        }
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+
+        let mut result = Ok(());
 
         // Makes sure all files in data folder are in a valid namespace
         if let Some(cap) = NAMESPACE_REG.captures(name) {
-            let namespace = cap.get(1).unwrap().clone().as_str();
+            let namespace = cap.get(1).unwrap().as_str();
 
-            if let Some(cap) = SerializableBiomeData::get_file_regex().captures(file.name()) {
-                let id = cap.get(1).unwrap().clone().as_str();
+            if let Some(cap) = SerializableBiomeData::get_file_regex().captures(name) {
+                let id = cap.get(1).unwrap().as_str();
                 let resource_location = ResourceLocation::new(String::from(namespace), String::from(id));
 
-                //println!("{}", name);
-                //println!("{}", resource_location);
-
-                let mut biome_data = String::new();
-                file.read_to_string(&mut biome_data)?;
-                //println!("{}", biome_data);
-                let biome = *SerializableBiomeData::deserialize(biome_data.clone())?;
+                let biome_data = String::from_utf8_lossy(&contents).into_owned();
+
+                match SerializableBiomeData::deserialize(biome_data) {
+                    Ok(biome) => {
+                        match datapack.biomes.entry(resource_location.clone()) {
+                            Entry::Occupied(mut entry) => {
+                                entry.get_mut().add(data_source, *biome);
+                            }
+                            Entry::Vacant(entry) => {
+                                entry.insert(SerializableDataHolder::named(resource_location, data_source, *biome));
+                            }
+                        }
 
-                match datapack.biomes.entry(resource_location.clone()) {
-                    Entry::Occupied(mut entry) => {
-                        entry.get_mut().add(data_source, Box::new(biome));
-                    }
-                    Entry::Vacant(entry) => {
-                        entry.insert(SerializableDataHolder::named(resource_location, data_source, biome));
+                        return Ok(())
                     }
+                    Err(error) => result = Err(DatapackError::from(error))
+                }
+            }
+            // Tags are kept as they are, but are read to report any problems with them
+            else if TagData::is_worldgen_tag(name) {
+                if let Err(error) = serde_json::from_slice::<TagData>(&contents) {
+                    result = Err(DatapackError::from(error))
                 }
             }
         }
         // Only error on data reg match to ignore files outside the data folder (and the data folder itself)
-        else if let Some(_) = DATA_REG.find(name) {
-            return Err(DatapackError::Namespace(format!("Invalid namespace in \"{}\"! Only a-z, 0-9, '_', '-', '.' are allowed!", name)))
+        else if DATA_REG.find(name).is_some() {
+            result = Err(DatapackError::Namespace(format!("Invalid namespace in \"{}\"! Only a-z, 0-9, '_', '-', '.' are allowed!", name).into()))
+        }
+
+        // Anything which is not modeled is kept as is so that it can be written back out unchanged
+        let path = String::from(data_source.get_relative_path(name));
+        datapack.add_passthrough(path, data_source, contents);
+
+        result
+    }
+
+    /// Stores a file as is, without trying to model it
+    fn import_passthrough(name: &str, file: &mut impl Read, datapack: &mut SerializableDatapack, data_source: SerializableDataSource) -> Result<(), DatapackError> {
+        if name.ends_with('/') {
+            return Ok(())
+        }
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+
+        let path = String::from(data_source.get_relative_path(name));
+        datapack.add_passthrough(path, data_source, contents);
+
+        Ok(())
+    }
+
+    fn add_passthrough(&mut self, path: String, data_source: SerializableDataSource, contents: Vec<u8>) {
+        match self.passthrough.entry(path) {
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().add(data_source, PassthroughFile::new(contents));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(SerializableDataHolder::anonymous(data_source, PassthroughFile::new(contents)));
+            }
+        }
+    }
+
+    /// Writes the datapack out as a zip, as long as validation finds no errors
+    pub fn to_zip(&self, filepath: &str) -> Result<(), DatapackError> {
+        self.check_export()?;
+        Self::write_zip(filepath, self.export_files()?)
+    }
+
+    pub(crate) fn write_zip(filepath: &str, files: DatapackFiles) -> Result<(), DatapackError> {
+        let zip_file = File::create(filepath)?;
+        let mut archive = ZipWriter::new(zip_file);
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        for (path, contents) in files {
+            archive.start_file(path, options)?;
+            archive.write_all(&contents)?;
+        }
+
+        archive.finish()?;
+
+        Ok(())
+    }
+
+    /// Writes the datapack out as an unpacked folder. The data directory and every overlay directory, of this
+    /// datapack or of the one already in the folder, are cleared first, as the game would still load files left
+    /// in them by an earlier export. Anything else in the folder is left untouched. Nothing is written if validation finds errors
+    pub fn to_directory(&self, directory: &str) -> Result<(), DatapackError> {
+        self.check_export()?;
+        let root = Path::new(directory);
+        let files = self.export_files()?;
+
+        for managed_directory in Self::get_managed_directories(root, &files) {
+            match fs::remove_dir_all(root.join(managed_directory)) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+                _ => {}
+            }
+        }
+
+        for (path, contents) in files {
+            let filepath = root.join(path);
+
+            if let Some(parent) = filepath.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            fs::write(filepath, contents)?;
         }
 
         Ok(())
     }
 
-    // TODO: Writing back into a zip file
+    /// Gets the directories of a folder which hold datapack data: the data directory, the overlays being exported and
+    /// the overlays declared by the pack.mcmeta already there. Only valid overlay names are used, so that a pack.mcmeta
+    /// cannot point outside of the folder
+    fn get_managed_directories(root: &Path, files: &DatapackFiles) -> BTreeSet<String> {
+        let existing_overlays = fs::read(root.join("pack.mcmeta")).ok()
+            .and_then(|contents| serde_json::from_slice::<SerializablePackInfo>(&contents).ok())
+            .and_then(|pack_info| pack_info.overlays)
+            .map(|overlays| overlays.entries)
+            .unwrap_or_default();
+
+        let mut directories = BTreeSet::from([String::from("data")]);
+        directories.extend(files.keys().filter_map(|path| OVERLAY_REG.captures(path)).map(|captures| String::from(&captures[1])));
+        directories.extend(existing_overlays.into_iter()
+            .map(|overlay| overlay.directory)
+            .filter(|directory| OVERLAY_DIRECTORY_REG.is_match(directory)));
+
+        directories
+    }
+
+    /// Gets the contents of every file in the exported datapack, keyed by path inside the datapack. Overlays are generated from the data each supported format loads,
+    /// replacing the overlays the datapack was stored with
+    pub fn export_files(&self) -> Result<DatapackFiles, DatapackError> {
+        self.export_planned_files(self.plan_overlays()?)
+    }
+
+    /// Gets every file to export using overlays which have already been planned
+    pub(crate) fn export_planned_files(&self, plan: OverlayPlan) -> Result<DatapackFiles, DatapackError> {
+        let mut pack_info = self.pack_info.clone();
+        pack_info.overlays = if plan.overlays.is_empty() { None } else {
+            Some(SerializablePackOverlays {
+                entries: plan.overlays.iter().map(|(overlay, _)| SerializableOverlayEntry::from(overlay)).collect()
+            })
+        };
+
+        let mut files = plan.root;
+        files.insert(String::from("pack.mcmeta"), serde_json::to_string_pretty(&pack_info)?.into_bytes());
+
+        for (overlay, overlay_files) in plan.overlays {
+            for (path, contents) in overlay_files {
+                files.insert(format!("{}/{}", overlay.name, path), contents);
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Works out the overlays needed to reproduce the data of every supported format
+    pub fn plan_overlays(&self) -> Result<OverlayPlan, DatapackError> {
+        let format_files = self.get_supported_formats().into_iter()
+            .map(|datapack_format| Ok((datapack_format, self.for_format(datapack_format).export_stored_files()?)))
+            .collect::<Result<BTreeMap<_, _>, DatapackError>>()?;
+
+        Ok(overlay::plan_overlays(&format_files))
+    }
+
+    /// Gets a copy of the datapack which declares support for a different range of formats
+    pub fn with_formats(&self, min_format: DatapackFormat, max_format: DatapackFormat) -> SerializableDatapack {
+        let mut datapack = self.clone();
+        let pack = &mut datapack.pack_info.pack;
+
+        if pack.pack_format < min_format || pack.pack_format > max_format {
+            pack.pack_format = max_format;
+        }
+
+        pack.supported_formats = if min_format == max_format { None }
+            else { Some(FormatRange::from_formats(min_format, max_format)) };
+
+        datapack
+    }
+
+    /// Gets every known format between the minimum and maximum supported formats, along with
+    /// the root format and range bounds even when they are unknown
+    pub fn get_supported_formats(&self) -> Vec<DatapackFormat> {
+        let pack = &self.pack_info.pack;
+
+        let mut formats: BTreeSet<DatapackFormat> = DATAPACK_FORMATS.iter()
+            .copied()
+            .filter(|datapack_format| match &pack.supported_formats {
+                Some(format_range) => format_range.contains(*datapack_format),
+                None => *datapack_format == pack.pack_format
+            })
+            .collect();
+
+        formats.insert(pack.pack_format);
+
+        if let Some(format_range) = &pack.supported_formats {
+            let (min, max) = format_range.bounds();
+            formats.extend([min, max].into_iter().filter_map(|format| DatapackFormat::from_int(format).ok()));
+        }
+
+        formats.into_iter().collect()
+    }
+
+    /// Gets every data file as it is stored, without pack.mcmeta
+    pub fn export_stored_files(&self) -> Result<DatapackFiles, DatapackError> {
+        let mut files = BTreeMap::new();
+
+        Self::export_data(&self.biomes, &mut files)?;
+
+        for (path, data_holder) in &self.passthrough {
+            if let Some(root_data) = &data_holder.root_data {
+                files.insert(path.clone(), root_data.contents.clone());
+            }
+
+            for (overlay, overlay_data) in &data_holder.overlay_data {
+                files.insert(format!("{}/{}", overlay.directory, path), overlay_data.contents.clone());
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn export_data<T: FileElement>(
+        data: &HashMap<ResourceLocation, Box<SerializableDataHolder<T>>>,
+        files: &mut BTreeMap<String, Vec<u8>>
+    ) -> Result<(), DatapackError> {
+        for (resource_location, data_holder) in data {
+            let path = T::get_file_path(resource_location);
+
+            if let Some(root_data) = &data_holder.root_data {
+                files.insert(path.clone(), root_data.serialize()?.into_bytes());
+            }
+
+            for (overlay, overlay_data) in &data_holder.overlay_data {
+                files.insert(format!("{}/{}", overlay.directory, path), overlay_data.serialize()?.into_bytes());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SerializableDatapack {
+    pub fn name(&self) -> &str { &self.name }
+    pub fn pack_format(&self) -> DatapackFormat { self.pack_info.pack.pack_format }
+
+    pub fn filters(&self) -> Vec<Filter> {
+        Filter::from_pack_filter(&self.pack_info.filter)
+    }
+
+    pub fn biome(&self, resource_location: &ResourceLocation) -> Option<&SerializableDataHolder<SerializableBiomeData>> {
+        self.biomes.get(resource_location).map(|data_holder| data_holder.as_ref())
+    }
+
+    pub fn passthrough_file(&self, path: &str) -> Option<&SerializableDataHolder<PassthroughFile>> {
+        self.passthrough.get(path).map(|data_holder| data_holder.as_ref())
+    }
+
+    pub fn biomes(&self) -> impl Iterator<Item = (&ResourceLocation, &SerializableDataHolder<SerializableBiomeData>)> {
+        self.biomes.iter().map(|(resource_location, data_holder)| (resource_location, data_holder.as_ref()))
+    }
+
+    pub fn passthrough_files(&self) -> impl Iterator<Item = (&String, &SerializableDataHolder<PassthroughFile>)> {
+        self.passthrough.iter().map(|(path, data_holder)| (path, data_holder.as_ref()))
+    }
+
+    /// Gets the datapack as the given format loads it, with every overlay for that format applied to the root data
+    pub fn for_format(&self, datapack_format: DatapackFormat) -> SerializableDatapack {
+        self.with_overlays_applied(datapack_format, &self.pack_info.get_active_overlays(datapack_format))
+    }
+
+    /// Gets the data meant for the given format, applying every overlay declared for it even when the format is from
+    /// before overlays were added. Builds give those formats data of their own this way, as each gets a separate zip
+    pub(crate) fn for_build_format(&self, datapack_format: DatapackFormat) -> SerializableDatapack {
+        self.with_overlays_applied(datapack_format, &self.pack_info.get_declared_overlays(datapack_format))
+    }
+
+    fn with_overlays_applied(&self, datapack_format: DatapackFormat, overlays: &[SerializableOverlayEntry]) -> SerializableDatapack {
+        let pack_info = SerializablePackInfo {
+            pack: SerializablePackData {
+                pack_format: datapack_format,
+                supported_formats: None,
+                description: self.pack_info.pack.description.clone()
+            },
+            overlays: None,
+            filter: self.pack_info.filter.clone()
+        };
+
+        let mut datapack = SerializableDatapack::empty(self.name.clone(), pack_info);
+        datapack.biomes = Self::resolve_data(&self.biomes, overlays);
+        datapack.passthrough = Self::resolve_data(&self.passthrough, overlays);
+        datapack
+    }
+
+    fn resolve_data<K, T>(data: &HashMap<K, Box<SerializableDataHolder<T>>>, overlays: &[SerializableOverlayEntry]) -> HashMap<K, Box<SerializableDataHolder<T>>>
+    where
+        K: Clone + Eq + std::hash::Hash,
+        T: Clone
+    {
+        data.iter()
+            .filter_map(|(key, data_holder)| {
+                let data = data_holder.resolve(overlays)?.clone();
+
+                let mut resolved = SerializableDataHolder::anonymous(SerializableDataSource::Root, data);
+                resolved.resource_location = data_holder.resource_location.clone();
+
+                Some((key.clone(), resolved))
+            })
+            .collect()
+    }
+
+    /// Gets the formats declared in pack.mcmeta
+    pub fn get_format_declarations(&self) -> Result<FormatDeclarations, DatapackError> {
+        let pack = &self.pack_info.pack;
+
+        let supported_formats = match &pack.supported_formats {
+            Some(format_range) => Some(DeclaredRange::try_from(format_range)?),
+            None => None
+        };
+
+        let overlays = match &self.pack_info.overlays {
+            Some(overlays) => overlays.entries.iter()
+                .map(|overlay| Ok((overlay.directory.clone(), DeclaredRange::try_from(&overlay.formats)?)))
+                .collect::<Result<Vec<_>, DatapackError>>()?,
+            None => Vec::new()
+        };
+
+        Ok(FormatDeclarations {
+            root_format: pack.pack_format,
+            supported_formats,
+            overlays
+        })
+    }
+
+    /// Gets the paths of the resources stored in each overlay
+    pub fn get_overlay_paths(&self) -> BTreeMap<String, BTreeSet<String>> {
+        let mut overlay_paths: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+        for (resource_location, data_holder) in &self.biomes {
+            for overlay in data_holder.overlay_data.keys() {
+                overlay_paths.entry(overlay.directory.clone()).or_default().insert(SerializableBiomeData::get_file_path(resource_location));
+            }
+        }
+
+        for (path, data_holder) in &self.passthrough {
+            for overlay in data_holder.overlay_data.keys() {
+                overlay_paths.entry(overlay.directory.clone()).or_default().insert(path.clone());
+            }
+        }
+
+        overlay_paths
+    }
+
+    /// Gets the path of every resource in the datapack relative to the pack root, whether it is stored in the root or an overlay
+    pub fn resource_paths(&self) -> BTreeSet<String> {
+        let biome_paths = self.biomes.keys()
+            .map(SerializableBiomeData::get_file_path);
+        let passthrough_paths = self.passthrough.keys()
+            .filter(|path| path.starts_with("data/"))
+            .cloned();
+
+        biome_paths.chain(passthrough_paths).collect()
+    }
+
+    /// Gets the path of every data file in the datapack, including the overlay directory for overlay data
+    pub fn data_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+
+        Self::collect_data_paths(&self.biomes, SerializableBiomeData::get_file_path, &mut paths);
+        Self::collect_data_paths(&self.passthrough, |path| path.clone(), &mut paths);
+
+        paths.retain(|path| NAMESPACE_REG.is_match(path));
+        paths.sort();
+        paths
+    }
+
+    fn collect_data_paths<K, T>(data: &HashMap<K, Box<SerializableDataHolder<T>>>, get_path: impl Fn(&K) -> String, paths: &mut Vec<String>) {
+        for (key, data_holder) in data {
+            let path = get_path(key);
+
+            for overlay in data_holder.overlay_data.keys() {
+                paths.push(format!("{}/{}", overlay.directory, path));
+            }
+            if data_holder.root_data.is_some() {
+                paths.push(path);
+            }
+        }
+    }
+
+    /// Gets the data files in this datapack which are hidden by the filters of a datapack loaded above it
+    pub fn get_filtered_paths(&self, filters: &[Filter]) -> Vec<String> {
+        self.data_paths().into_iter()
+            .filter(|path| filters.iter().any(|filter| filter.matches_path(path)))
+            .collect()
+    }
+}
+
+//------------//
+
+/// A datapack loaded from disk, along with any problems found while loading it
+#[derive(Debug)]
+pub struct DatapackImport {
+    pub datapack: SerializableDatapack,
+    pub diagnostics: Vec<Diagnostic>
 }
 
 //////////////////////////////////////////
 //------ Serialized Datapack Info ------//
 //////////////////////////////////////////
 
+#[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SerializablePackInfo {
     pack: SerializablePackData,
     #[serde(default)]
-    overlays: Option<SerializablePackOverlays>
-    // TODO: add filters (see wiki for more info)
+    overlays: Option<SerializablePackOverlays>,
+    #[serde(default)]
+    filter: Option<SerializablePackFilter>
 }
 
 impl SerializablePackInfo {
@@ -239,10 +882,31 @@ impl SerializablePackInfo {
 
         None
     }
+
+    /// Gets the overlays which the given format loads, in the order they are applied
+    fn get_active_overlays(&self, datapack_format: DatapackFormat) -> Vec<SerializableOverlayEntry> {
+        // Versions from before overlays were added ignore them entirely
+        if !datapack_format.supports_overlays() {
+            return Vec::new()
+        }
+
+        self.get_declared_overlays(datapack_format)
+    }
+
+    /// Gets the overlays declared for the given format, in the order they are applied
+    fn get_declared_overlays(&self, datapack_format: DatapackFormat) -> Vec<SerializableOverlayEntry> {
+        let Some(overlays) = &self.overlays else { return Vec::new() };
+
+        overlays.entries.iter()
+            .filter(|overlay| overlay.formats.contains(datapack_format))
+            .cloned()
+            .collect()
+    }
 }
 
 //------------//
 
+#[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct SerializablePackData {
     pack_format: DatapackFormat,
@@ -253,6 +917,24 @@ struct SerializablePackData {
 
 //------------//
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SerializablePackFilter {
+    block: Vec<SerializableFilterPattern>
+}
+
+//------------//
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SerializableFilterPattern {
+    #[serde(default)]
+    namespace: Option<String>,
+    #[serde(default)]
+    path: Option<String>
+}
+
+//------------//
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct SerializablePackOverlays {
     entries: Vec<SerializableOverlayEntry>
@@ -278,6 +960,15 @@ struct SerializableOverlayEntry {
     directory: String
 }
 
+impl From<&Overlay> for SerializableOverlayEntry {
+    fn from(overlay: &Overlay) -> Self {
+        Self {
+            formats: FormatRange::from_formats(overlay.min_format, overlay.max_format),
+            directory: overlay.name.clone()
+        }
+    }
+}
+
 //------------//
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -291,6 +982,49 @@ enum FormatRange {
     }
 }
 
+impl TryFrom<&FormatRange> for DeclaredRange {
+    type Error = DatapackError;
+
+    fn try_from(format_range: &FormatRange) -> Result<Self, Self::Error> {
+        let (min, max) = format_range.bounds();
+
+        Ok(DeclaredRange {
+            min_format: DatapackFormat::from_int(min)?,
+            max_format: DatapackFormat::from_int(max)?,
+            is_range: !matches!(format_range, FormatRange::Exact(_))
+        })
+    }
+}
+
+impl FormatRange {
+    fn from_formats(min_format: DatapackFormat, max_format: DatapackFormat) -> Self {
+        if min_format == max_format {
+            FormatRange::Exact(min_format.format() as i32)
+        }
+        else {
+            FormatRange::Object {
+                min_inclusive: min_format.format() as i32,
+                max_inclusive: max_format.format() as i32
+            }
+        }
+    }
+
+    fn bounds(&self) -> (i32, i32) {
+        match *self {
+            FormatRange::Exact(exact) => (exact, exact),
+            FormatRange::Range(range) => range,
+            FormatRange::Object { min_inclusive, max_inclusive } => (min_inclusive, max_inclusive)
+        }
+    }
+
+    fn contains(&self, datapack_format: DatapackFormat) -> bool {
+        let (min, max) = self.bounds();
+        let format = datapack_format.format() as i64;
+
+        min as i64 <= format && format <= max as i64
+    }
+}
+
 /////////////////////////////////////////
 //------ Serialized Data Storage ------//
 /////////////////////////////////////////
@@ -301,16 +1035,30 @@ enum SerializableDataSource {
     Overlay(SerializableOverlayEntry)
 }
 
+impl SerializableDataSource {
+    /// Strips the overlay directory from a path in the datapack, if there is one
+    fn get_relative_path<'a>(&self, name: &'a str) -> &'a str {
+        match self {
+            SerializableDataSource::Root => name,
+            SerializableDataSource::Overlay(overlay) => {
+                name.strip_prefix(&overlay.directory)
+                    .and_then(|path| path.strip_prefix('/'))
+                    .unwrap_or(name)
+            }
+        }
+    }
+}
+
 //------------//
 
-#[derive(Debug)]
-pub struct SerializableDataHolder<T: SerializableDataElement> {
+#[derive(Debug, Clone)]
+pub struct SerializableDataHolder<T> {
     root_data: Option<T>,
     overlay_data: HashMap<SerializableOverlayEntry, T>,
     resource_location: Option<ResourceLocation>
 }
 
-impl<T: SerializableDataElement> SerializableDataHolder<T> {
+impl<T> SerializableDataHolder<T> {
     fn anonymous(data_source: SerializableDataSource, data: T) -> Box<Self> {
         match data_source {
             SerializableDataSource::Root => {
@@ -329,7 +1077,7 @@ impl<T: SerializableDataElement> SerializableDataHolder<T> {
 
                 data_holder.overlay_data.insert(overlay, data);
 
-                return data_holder
+                data_holder
             }
         }
     }
@@ -340,44 +1088,124 @@ impl<T: SerializableDataElement> SerializableDataHolder<T> {
         data_holder
     }
 
-    fn add(&mut self, data_source: SerializableDataSource, data_element: Box<T>) {
+    fn add(&mut self, data_source: SerializableDataSource, data_element: T) {
         match data_source {
             SerializableDataSource::Root => {
-                self.root_data = Some(*data_element);
+                self.root_data = Some(data_element);
             }
             SerializableDataSource::Overlay(overlay) => {
-                self.overlay_data.insert(overlay, *data_element);
+                self.overlay_data.insert(overlay, data_element);
             }
         }
     }
 
-    fn get(&self, data_source: SerializableDataSource) -> Option<&T> {
-        match data_source {
-            SerializableDataSource::Root => {
-                Option::from(&self.root_data)
-            }
-            SerializableDataSource::Overlay(overlay) => {
-                self.overlay_data.get(&overlay)
+    /// Moves the data stored in one overlay directory to another. The new directory must not already hold data,
+    /// which [`Datapack::rename_overlay`] makes sure of by rejecting names used by another overlay
+    pub(crate) fn rename_overlay(&mut self, previous: &str, name: &str) {
+        if previous == name {
+            return
+        }
+        debug_assert!(!self.has_overlay(name), "Overlay data should not be renamed onto existing data");
+
+        let renamed: Vec<SerializableOverlayEntry> = self.overlay_data.keys()
+            .filter(|overlay| overlay.directory == previous)
+            .cloned()
+            .collect();
+
+        for overlay in renamed {
+            if let Some(data) = self.overlay_data.remove(&overlay) {
+                let entry = SerializableOverlayEntry { directory: String::from(name), .. overlay };
+                self.overlay_data.insert(entry, data);
             }
         }
     }
 
-    fn remove(&mut self, data_source: SerializableDataSource) {
-        match data_source {
-            SerializableDataSource::Root => {
-                self.root_data = None;
+    /// Re-keys overlay data to match the given overlay entries by directory, discarding data
+    /// for any overlay which is no longer declared
+    fn with_overlays(mut self, overlays: &[SerializableOverlayEntry]) -> Self {
+        let mut overlay_data = HashMap::new();
+
+        for (overlay, data) in self.overlay_data.drain() {
+            if let Some(entry) = overlays.iter().find(|entry| entry.directory == overlay.directory) {
+                overlay_data.insert(entry.clone(), data);
             }
-            SerializableDataSource::Overlay(overlay) => {
-                self.overlay_data.remove(&overlay);
+        }
+
+        self.overlay_data = overlay_data;
+        self
+    }
+
+    pub fn from_parts(root_data: Option<T>, overlay_data: Vec<(&Overlay, T)>, resource_location: Option<ResourceLocation>) -> Self {
+        Self {
+            root_data,
+            overlay_data: overlay_data.into_iter()
+                .map(|(overlay, data)| (SerializableOverlayEntry::from(overlay), data))
+                .collect(),
+            resource_location
+        }
+    }
+
+    pub fn root_data(&self) -> Option<&T> {
+        self.root_data.as_ref()
+    }
+
+    /// Gets the data stored in each overlay, keyed by overlay directory
+    pub fn get_overlay_data(&self) -> BTreeMap<&str, &T> {
+        self.overlay_data.iter()
+            .map(|(overlay, data)| (overlay.directory.as_str(), data))
+            .collect()
+    }
+
+    pub fn has_overlay(&self, name: &str) -> bool {
+        self.overlay_data.keys().any(|overlay| overlay.directory == name)
+    }
+
+    /// Changes the data stored in the root and in every overlay the same way
+    pub(crate) fn try_map<E>(&self, mut f: impl FnMut(&T) -> Result<T, E>) -> Result<Self, E> {
+        Ok(Self {
+            root_data: self.root_data.as_ref().map(&mut f).transpose()?,
+            overlay_data: self.overlay_data.iter()
+                .map(|(overlay, data)| Ok((overlay.clone(), f(data)?)))
+                .collect::<Result<_, E>>()?,
+            resource_location: self.resource_location.clone()
+        })
+    }
+
+    /// Changes only the copy of the data a format loads, which is the copy in the last overlay for the format holding one,
+    /// or otherwise the root data
+    pub(crate) fn try_map_loaded<E>(&self, overlays: &[Overlay], datapack_format: DatapackFormat, f: impl FnOnce(&T) -> Result<T, E>) -> Result<Self, E>
+    where T: Clone {
+        let loaded = overlays.iter().rev()
+            .filter(|overlay| datapack_format.supports_overlays() && overlay.min_format <= datapack_format && datapack_format <= overlay.max_format)
+            .find_map(|overlay| self.overlay_data.keys().find(|entry| entry.directory == overlay.name))
+            .cloned();
+
+        let mut data_holder = self.clone();
+        match loaded {
+            Some(entry) => {
+                let data = f(&self.overlay_data[&entry])?;
+                data_holder.overlay_data.insert(entry, data);
             }
+            None => data_holder.root_data = self.root_data.as_ref().map(f).transpose()?
         }
+
+        Ok(data_holder)
+    }
+
+    /// Gets the data seen after applying the given overlays in order, where later overlays replace earlier ones
+    fn resolve(&self, overlays: &[SerializableOverlayEntry]) -> Option<&T> {
+        overlays.iter().rev()
+            .find_map(|overlay| self.overlay_data.iter()
+                .find(|(entry, _)| entry.directory == overlay.directory)
+                .map(|(_, data)| data))
+            .or(self.root_data.as_ref())
     }
 }
 
 //------------//
 
 pub trait SerializableDataElement {
-    fn serialize(&self) -> String;
+    fn serialize(&self) -> serde_json::Result<String>;
     fn deserialize(json: String) -> serde_json::Result<Box<Self>> where Self: Sized;
 }
 
@@ -385,13 +1213,47 @@ pub trait SerializableDataElement {
 
 pub trait FileElement : SerializableDataElement {
     fn get_file_regex() -> &'static Regex;
+    fn get_file_path(resource_location: &ResourceLocation) -> String;
+}
+
+//------------//
+
+/// Whether a resource keeps the same data for every supported format, or is edited separately per format
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncState {
+    #[default]
+    Synced,
+    Manual
+}
+
+//------------//
+
+/// Raw contents of a file which is not modeled by the application
+#[derive(Clone, PartialEq, Eq)]
+pub struct PassthroughFile {
+    contents: Vec<u8>
+}
+
+impl PassthroughFile {
+    pub fn new(contents: Vec<u8>) -> Self {
+        Self { contents }
+    }
+
+    pub fn contents(&self) -> &[u8] { &self.contents }
+}
+
+impl Debug for PassthroughFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PassthroughFile({} bytes)", self.contents.len())
+    }
 }
 
 ///////////////////////////////////////
 //------ Internal Data storage ------//
 ///////////////////////////////////////
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Datapack {
     name: String,
     description: Vec<util::Text>,
@@ -403,12 +1265,14 @@ pub struct Datapack {
     root_format: DatapackFormat,
 
     overlays: Vec<Overlay>,
+    filters: Vec<Filter>,
 
-    //biomes: HashMap<Overlay, BiomeData>
+    biomes: HashMap<ResourceLocation, BiomeData>,
+    passthrough: HashMap<String, SerializableDataHolder<PassthroughFile>>
 }
 
 impl Datapack {
-    pub fn name(&self) -> &str { &*self.name }
+    pub fn name(&self) -> &str { &self.name }
     pub fn set_name(&mut self, name: &str) { self.name = String::from(name) }
 
     pub fn description(&self) -> &Vec<util::Text> { &self.description }
@@ -417,6 +1281,111 @@ impl Datapack {
     pub fn overlays(&self) -> &Vec<Overlay> { &self.overlays }
     pub fn overlays_mut(&mut self) -> &mut Vec<Overlay> { &mut self.overlays }
 
+    pub fn filters(&self) -> &Vec<Filter> { &self.filters }
+    pub fn filters_mut(&mut self) -> &mut Vec<Filter> { &mut self.filters }
+
+    /// Renames an overlay directory, keeping any data already stored in it.
+    /// Names used by another overlay are rejected, as the data of both overlays would be mixed together
+    pub fn rename_overlay(&mut self, index: usize, name: String) -> Result<(), DatapackError> {
+        if self.overlays.iter().enumerate().any(|(other, overlay)| other != index && overlay.name == name) {
+            return Err(DatapackError::Overlay(format!("Another overlay is already named \"{}\"", name).into()))
+        }
+
+        let Some(overlay) = self.overlays.get_mut(index) else { return Ok(()) };
+        let previous = std::mem::replace(&mut overlay.name, name.clone());
+
+        for biome in self.biomes.values_mut() {
+            biome.rename_overlay(&previous, &name);
+        }
+
+        for file in self.passthrough.values_mut() {
+            file.rename_overlay(&previous, &name);
+        }
+
+        Ok(())
+    }
+
+    /// Gets the datapack as the given format loads it. See [`SerializableDatapack::for_format`]
+    pub fn for_format(&self, datapack_format: DatapackFormat) -> Result<Datapack, DatapackError> {
+        Datapack::try_from(SerializableDatapack::from(self.clone()).for_format(datapack_format))
+    }
+
+    /// Creates a datapack without any data
+    pub fn new(
+        name: String,
+        description: Vec<util::Text>,
+        root_format: DatapackFormat,
+        min_format: DatapackFormat,
+        max_format: DatapackFormat,
+        overlays: Vec<Overlay>,
+        filters: Vec<Filter>
+    ) -> Self {
+        Self {
+            name,
+            description,
+            min_format,
+            max_format,
+            root_format,
+            overlays,
+            filters,
+            biomes: HashMap::new(),
+            passthrough: HashMap::new()
+        }
+    }
+
+    pub fn biomes(&self) -> &HashMap<ResourceLocation, BiomeData> { &self.biomes }
+    pub fn biomes_mut(&mut self) -> &mut HashMap<ResourceLocation, BiomeData> { &mut self.biomes }
+
+    pub fn passthrough(&self) -> &HashMap<String, SerializableDataHolder<PassthroughFile>> { &self.passthrough }
+    pub fn passthrough_mut(&mut self) -> &mut HashMap<String, SerializableDataHolder<PassthroughFile>> { &mut self.passthrough }
+
+    /// Gets the formats which would be declared in pack.mcmeta
+    pub fn get_format_declarations(&self) -> FormatDeclarations {
+        let supported_formats = if self.min_format == self.max_format { None }
+            else { Some(DeclaredRange { min_format: self.min_format, max_format: self.max_format, is_range: true }) };
+
+        let overlays = self.overlays.iter()
+            .map(|overlay| (overlay.name.clone(), DeclaredRange {
+                min_format: overlay.min_format,
+                max_format: overlay.max_format,
+                is_range: overlay.min_format != overlay.max_format
+            }))
+            .collect();
+
+        FormatDeclarations {
+            root_format: self.root_format,
+            supported_formats,
+            overlays
+        }
+    }
+
+    /// Gets the path of every resource in the datapack relative to the pack root, whether it is stored in the root or an overlay
+    pub fn resource_paths(&self) -> BTreeSet<String> {
+        let biome_paths = self.biomes.keys()
+            .map(SerializableBiomeData::get_file_path);
+        let passthrough_paths = self.passthrough.keys()
+            .filter(|path| path.starts_with("data/"))
+            .cloned();
+
+        biome_paths.chain(passthrough_paths).collect()
+    }
+
+    /// Gets the paths of the resources stored in each overlay
+    pub fn get_overlay_paths(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.overlays.iter()
+            .map(|overlay| {
+                let biome_paths = self.biomes.iter()
+                    .filter(|(_, biome)| biome.data().has_overlay(&overlay.name))
+                    .map(|(resource_location, _)| SerializableBiomeData::get_file_path(resource_location));
+                let passthrough_paths = self.passthrough.iter()
+                    .filter(|(_, data_holder)| data_holder.has_overlay(&overlay.name))
+                    .map(|(path, _)| path.clone());
+
+                (overlay.name.clone(), biome_paths.chain(passthrough_paths).collect())
+            })
+            .collect()
+    }
+
     pub fn root_format(&self) -> DatapackFormat { self.root_format }
     pub fn min_format(&self) -> DatapackFormat { self.min_format }
     pub fn max_format(&self) -> DatapackFormat { self.max_format }
@@ -432,33 +1401,28 @@ impl TryFrom<SerializableDatapack> for Datapack {
         let name = serializable_datapack.name;
         let pack_info = serializable_datapack.pack_info;
 
-        let description = util::Text::try_from(pack_info.pack.description)?.into();
+        // Invalid formatting is reported when importing, so the formatting is dropped here instead of failing entirely
+        let description = util::Text::try_from(pack_info.pack.description.clone())
+            .or_else(|_| util::Text::from_serializable_discard_formatting(pack_info.pack.description))?
+            .into();
 
         let root_format = pack_info.pack.pack_format;
 
         let (min_format, max_format) = if let Some(format_range) = pack_info.pack.supported_formats {
-            let (min_format_int, max_format_int) = match format_range {
-                FormatRange::Exact(format_int) => (format_int, format_int),
-                FormatRange::Range(format_range) => format_range,
-                FormatRange::Object { min_inclusive, max_inclusive } => (min_inclusive, max_inclusive)
-            };
-
-            let min = DatapackFormat::from_repr(min_format_int as u8)
-                .ok_or(DatapackError::Format(format!("Invalid datapack format {min_format_int}")))?;
-            let max = DatapackFormat::from_repr(max_format_int as u8)
-                .ok_or(DatapackError::Format(format!("Invalid datapack format {max_format_int}")))?;
-
-            (min, max)
+            let (min_format_int, max_format_int) = format_range.bounds();
+            (DatapackFormat::from_int(min_format_int)?, DatapackFormat::from_int(max_format_int)?)
         }
         else {
             (root_format, root_format)
         };
 
+        let filters = Filter::from_pack_filter(&pack_info.filter);
+
         let mut overlay_error: Option<Self::Error> = None;
 
         let overlays = if let Some(serializable_overlays) = pack_info.overlays {
             serializable_overlays.entries.into_iter()
-                .map(|overlay| Overlay::try_from(overlay))
+                .map(Overlay::try_from)
                 .filter_map(|result| {
                     match result {
                         Ok(overlay) => Some(overlay),
@@ -478,20 +1442,67 @@ impl TryFrom<SerializableDatapack> for Datapack {
             return Err(error);
         }
 
+        let biomes = serializable_datapack.biomes.into_iter()
+            .map(|(resource_location, data_holder)| (resource_location, BiomeData::from(*data_holder)))
+            .collect();
+
+        let passthrough = serializable_datapack.passthrough.into_iter()
+            .map(|(path, data_holder)| (path, *data_holder))
+            .collect();
+
         Ok(Self {
             name,
             description,
             min_format,
             max_format,
             root_format,
-            overlays
+            overlays,
+            filters,
+            biomes,
+            passthrough
         })
     }
 }
 
-impl Into<SerializableDatapack> for Datapack {
-    fn into(self) -> SerializableDatapack {
-        todo!()
+impl From<Datapack> for SerializableDatapack {
+    fn from(datapack: Datapack) -> Self {
+        let supported_formats = if datapack.min_format == datapack.max_format { None }
+        else { Some(FormatRange::from_formats(datapack.min_format, datapack.max_format)) };
+
+        let overlay_entries: Vec<SerializableOverlayEntry> = datapack.overlays.iter()
+            .map(SerializableOverlayEntry::from)
+            .collect();
+
+        let overlays = if overlay_entries.is_empty() { None }
+        else { Some(SerializablePackOverlays { entries: overlay_entries.clone() }) };
+
+        let filter = if datapack.filters.is_empty() { None }
+        else { Some(SerializablePackFilter { block: datapack.filters.iter().map(SerializableFilterPattern::from).collect() }) };
+
+        let pack_info = SerializablePackInfo {
+            pack: SerializablePackData {
+                pack_format: datapack.root_format,
+                supported_formats,
+                description: SerializableText::from(datapack.description)
+            },
+            overlays,
+            filter
+        };
+
+        let mut serializable_datapack = SerializableDatapack::empty(datapack.name, pack_info);
+
+        serializable_datapack.biomes = datapack.biomes.into_iter()
+            .map(|(resource_location, biome)| {
+                let data_holder = SerializableDataHolder::from(biome).with_overlays(&overlay_entries);
+                (resource_location, Box::new(data_holder))
+            })
+            .collect();
+
+        serializable_datapack.passthrough = datapack.passthrough.into_iter()
+            .map(|(path, data_holder)| (path, Box::new(data_holder.with_overlays(&overlay_entries))))
+            .collect();
+
+        serializable_datapack
     }
 }
 
@@ -507,7 +1518,7 @@ pub enum DataValue {
 
 //------------//
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Overlay {
     pub name: String,
     pub min_format: DatapackFormat,
@@ -515,33 +1526,9 @@ pub struct Overlay {
 }
 
 impl Overlay {
-    fn from_single_format(name: String, format: DatapackFormat) -> Result<Self, DatapackError> {
-        Self::from_formats(name, format, format)
-    }
-
-    fn from_formats(name: String, min_format: DatapackFormat, max_format: DatapackFormat) -> Result<Self, DatapackError> {
-        if min_format > max_format {
-            return Err(DatapackError::Overlay(format!("Minimum format {} cannot be greater than maximum format {}", min_format as u8, max_format as u8)))
-        }
-
-        if !min_format.supports_overlays() && min_format != max_format {
-            return Err(DatapackError::Overlay(format!("Format ranges are not supported below format {}", DatapackFormat::get_minimum_overlay_version() as u8)))
-        }
-
-        Ok(Overlay {
-            name,
-            min_format,
-            max_format
-        })
-    }
-
-    fn from_single_int_format(name: String, format_int: i32) -> Result<Self, DatapackError> {
-        Self::from_int_formats(name, format_int, format_int)
-    }
-
     fn from_int_formats(name: String, min_format_int: i32, max_format_int: i32) -> Result<Self, DatapackError> {
-        let min_format = DatapackFormat::from_repr(min_format_int as u8).ok_or(DatapackError::Format(format!("Invalid datapack format {min_format_int}")))?;
-        let max_format = DatapackFormat::from_repr(max_format_int as u8).ok_or(DatapackError::Format(format!("Invalid datapack format {max_format_int}")))?;
+        let min_format = DatapackFormat::from_int(min_format_int)?;
+        let max_format = DatapackFormat::from_int(max_format_int)?;
 
         Ok(Overlay {
             name,
@@ -555,17 +1542,121 @@ impl TryFrom<SerializableOverlayEntry> for Overlay {
     type Error = DatapackError;
 
     fn try_from(value: SerializableOverlayEntry) -> Result<Self, Self::Error> {
-        match value.formats {
-            FormatRange::Exact(version) => {
-                Overlay::from_int_formats(value.directory, version, version)
-            }
-            FormatRange::Range((min_version, max_version)) => {
-                Overlay::from_int_formats(value.directory, min_version, max_version)
-            }
-            FormatRange::Object {min_inclusive, max_inclusive} => {
-                Overlay::from_int_formats(value.directory, min_inclusive, max_inclusive)
+        let (min_version, max_version) = value.formats.bounds();
+        Overlay::from_int_formats(value.directory, min_version, max_version)
+    }
+}
+
+//------------//
+
+/// Blocks files from datapacks loaded below this one. Empty patterns match everything.
+/// Patterns are compiled with Rust's regex syntax rather than Java's, which the game uses. The two agree on the
+/// common constructs, but Java only ones such as lookarounds and backreferences count as invalid and never match
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Filter {
+    namespace: FilterPattern,
+    path: FilterPattern
+}
+
+lazy_static! {
+    static ref FILTER_PATH_REG: Regex = Regex::new(r"^(?:[a-z0-9_-]+/)?data/([^/]+)/(.+)$").unwrap();
+}
+
+impl Filter {
+    pub fn new(namespace: &str, path: &str) -> Self {
+        Self { namespace: FilterPattern::from(String::from(namespace)), path: FilterPattern::from(String::from(path)) }
+    }
+
+    fn from_pack_filter(filter: &Option<SerializablePackFilter>) -> Vec<Filter> {
+        let Some(filter) = filter else { return Vec::new() };
+
+        filter.block.iter()
+            .map(|pattern| Filter::new(
+                pattern.namespace.as_deref().unwrap_or_default(),
+                pattern.path.as_deref().unwrap_or_default()
+            ))
+            .collect()
+    }
+
+    pub fn namespace(&self) -> &str { &self.namespace.source }
+    pub fn path(&self) -> &str { &self.path.source }
+
+    pub fn set_namespace(&mut self, namespace: String) { self.namespace = FilterPattern::from(namespace) }
+    pub fn set_path(&mut self, path: String) { self.path = FilterPattern::from(path) }
+
+    /// Checks that both patterns are valid regular expressions
+    pub fn validate(&self) -> Result<(), DatapackError> {
+        for (name, pattern) in [("namespace", &self.namespace), ("path", &self.path)] {
+            if let Err(error) = &pattern.regex {
+                return Err(DatapackError::Filter(format!("Invalid filter {} pattern \"{}\": {}", name, pattern.source, error).into()))
             }
         }
+
+        Ok(())
+    }
+
+    /// Matches the same way the game does, where a pattern only has to be found somewhere in the value.
+    /// Invalid patterns never match
+    pub fn matches(&self, namespace: &str, path: &str) -> bool {
+        self.namespace.matches(namespace) && self.path.matches(path)
+    }
+
+    /// Matches against a file path inside a datapack, which may be inside an overlay
+    pub fn matches_path(&self, path: &str) -> bool {
+        match FILTER_PATH_REG.captures(path) {
+            Some(cap) => self.matches(cap.get(1).unwrap().as_str(), cap.get(2).unwrap().as_str()),
+            None => false
+        }
+    }
+}
+
+/// A filter pattern along with its compiled regex, so that it is only compiled once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+struct FilterPattern {
+    source: String,
+    regex: Result<Regex, regex::Error>
+}
+
+impl FilterPattern {
+    fn matches(&self, value: &str) -> bool {
+        self.source.is_empty() || self.regex.as_ref().is_ok_and(|regex| regex.is_match(value))
+    }
+}
+
+impl Default for FilterPattern {
+    fn default() -> Self {
+        Self::from(String::new())
+    }
+}
+
+impl PartialEq for FilterPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl From<String> for FilterPattern {
+    fn from(source: String) -> Self {
+        let regex = Regex::new(&source);
+        Self { source, regex }
+    }
+}
+
+impl From<FilterPattern> for String {
+    fn from(pattern: FilterPattern) -> Self {
+        pattern.source
+    }
+}
+
+impl From<&Filter> for SerializableFilterPattern {
+    fn from(filter: &Filter) -> Self {
+        let pattern = |pattern: &str| if pattern.is_empty() { None } else { Some(String::from(pattern)) };
+
+        Self {
+            namespace: pattern(filter.namespace()),
+            path: pattern(filter.path())
+        }
     }
 }
 
@@ -573,32 +1664,156 @@ impl TryFrom<SerializableOverlayEntry> for Overlay {
 //------ Error Types ------//
 /////////////////////////////
 
-#[derive(Debug, Display)]
+#[derive(Debug, Clone)]
 pub enum DatapackError {
-    File(String),
-    Deserialize(String),
-    Namespace(String),
-    Overlay(String),
-    Format(String)
+    File(ErrorContext),
+    Deserialize(ErrorContext),
+    Namespace(ErrorContext),
+    Overlay(ErrorContext),
+    Format(ErrorContext),
+    Filter(ErrorContext)
+}
+
+impl DatapackError {
+    pub fn context(&self) -> &ErrorContext {
+        use DatapackError::*;
+        match self {
+            File(context) | Deserialize(context) | Namespace(context) | Overlay(context) | Format(context) | Filter(context) => context
+        }
+    }
+
+    fn context_mut(&mut self) -> &mut ErrorContext {
+        use DatapackError::*;
+        match self {
+            File(context) | Deserialize(context) | Namespace(context) | Overlay(context) | Format(context) | Filter(context) => context
+        }
+    }
+
+    /// Sets the path of the file the error occurred in, if it is not already known
+    pub fn with_path(mut self, path: &str) -> Self {
+        let context = self.context_mut();
+        context.path = context.path.take().or_else(|| Some(String::from(path)));
+        self
+    }
+
+    /// Sets the overlay the error occurred in, if it is not already known
+    pub fn with_overlay(mut self, overlay: &str) -> Self {
+        let context = self.context_mut();
+        context.overlay = context.overlay.take().or_else(|| Some(String::from(overlay)));
+        self
+    }
+}
+
+impl Display for DatapackError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.context())
+    }
 }
 
 impl Error for DatapackError {}
 
+//------------//
+
+#[derive(Debug, Clone, Default)]
+pub struct ErrorContext {
+    pub message: String,
+    // Path of the file inside the datapack, including the overlay directory
+    pub path: Option<String>,
+    pub overlay: Option<String>,
+    // Line and column reported by serde, both starting at 1
+    pub position: Option<(usize, usize)>
+}
+
+impl From<String> for ErrorContext {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            .. Self::default()
+        }
+    }
+}
+
+impl From<&str> for ErrorContext {
+    fn from(message: &str) -> Self {
+        Self::from(String::from(message))
+    }
+}
+
+impl Display for ErrorContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}", path)?;
+
+            if let Some((line, column)) = self.position {
+                write!(f, ":{}:{}", line, column)?;
+            }
+            if let Some(overlay) = &self.overlay {
+                write!(f, " (overlay {})", overlay)?;
+            }
+
+            write!(f, ": ")?;
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+//------------//
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Display)]
+pub enum Severity {
+    Warning,
+    Error
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub error: DatapackError
+}
+
+impl Diagnostic {
+    pub fn warning(error: DatapackError) -> Self {
+        Self { severity: Severity::Warning, error }
+    }
+
+    pub fn error(error: DatapackError) -> Self {
+        Self { severity: Severity::Error, error }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.severity, self.error)
+    }
+}
+
 impl From<ZipError> for DatapackError {
     fn from(value: ZipError) -> Self {
-        DatapackError::File(format!("Error reading zip file: {}", value.to_string()))
+        DatapackError::File(format!("Error accessing zip file: {}", value).into())
     }
 }
 
 impl From<io::Error> for DatapackError {
     fn from(value: io::Error) -> Self {
-        DatapackError::File(format!("Error reading file from zip: {}", value.to_string()))
+        DatapackError::File(format!("Error accessing file: {}", value).into())
     }
 }
 
 impl From<serde_json::Error> for DatapackError {
     fn from(value: serde_json::Error) -> Self {
-        DatapackError::Deserialize(format!("Error deserializing file: {}", value.to_string()))
+        // Serde appends the position to its messages, which is stored separately instead
+        let message = value.to_string();
+        let message = match message.rfind(" at line ") {
+            Some(index) if value.line() > 0 => &message[..index],
+            _ => &message
+        };
+
+        DatapackError::Deserialize(ErrorContext {
+            message: format!("Error (de)serializing file: {}", message),
+            position: if value.line() > 0 { Some((value.line(), value.column())) } else { None },
+            .. ErrorContext::default()
+        })
     }
 }
 
@@ -609,6 +1824,131 @@ impl From<ColorParseError> for DatapackError {
             ColorParseError::Name(m) => m,
         };
 
-        DatapackError::Deserialize(format!("Error parsing color value: {}", message))
+        DatapackError::Deserialize(format!("Error parsing color value: {}", message).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use crate::data::test_util;
+    use super::*;
+
+    #[test]
+    fn undeclared_overlay_does_not_replace_root_data() {
+        let pack_info = test_util::pack_info(26, None, &[]);
+        let (root_biome, overlay_biome) = (test_util::biome(0.8), test_util::biome(0.1));
+
+        let import = test_util::import(&[
+            ("pack.mcmeta", &pack_info),
+            ("data/test/worldgen/biome/x.json", &root_biome),
+            ("foo/data/test/worldgen/biome/x.json", &overlay_biome)
+        ]);
+
+        assert!(import.diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Warning));
+
+        let location = ResourceLocation::new(String::from("test"), String::from("x"));
+        let biome = import.datapack.biome(&location).expect("Root biome should be modeled");
+        assert!(biome.get_overlay_data().is_empty());
+
+        let files = import.datapack.export_files().unwrap();
+        let temperature = |path: &str| serde_json::from_slice::<Value>(&files[path]).unwrap()["temperature"].as_f64();
+
+        assert_eq!(temperature("data/test/worldgen/biome/x.json"), Some(0.8));
+        assert_eq!(temperature("foo/data/test/worldgen/biome/x.json"), Some(0.1));
+    }
+
+    #[test]
+    fn exporting_to_a_folder_clears_stale_data() {
+        let directory = std::env::temp_dir().join(format!("gaia_folder_test_{}", std::process::id()));
+        let root = directory.to_str().unwrap();
+        let write = |path: &str, contents: &str| {
+            let path = directory.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+
+        // An earlier export, with a biome and an overlay which have since been removed, next to the user's own notes
+        write("pack.mcmeta", &test_util::pack_info(26, Some((18, 26)), &[("old", 18, 18), ("../outside", 18, 18)]));
+        write("data/test/worldgen/biome/removed.json", &test_util::biome(0.8));
+        write("old/data/test/worldgen/biome/x.json", &test_util::biome(0.1));
+        write("notes.txt", "notes");
+
+        let pack_info = test_util::pack_info(26, None, &[]);
+        let biome = test_util::biome(0.5);
+        let import = test_util::import(&[("pack.mcmeta", &pack_info), ("data/test/worldgen/biome/x.json", &biome)]);
+        import.datapack.to_directory(root).unwrap();
+
+        assert!(directory.join("data/test/worldgen/biome/x.json").is_file());
+        assert!(!directory.join("data/test/worldgen/biome/removed.json").exists());
+        assert!(!directory.join("old").exists());
+        assert!(directory.join("notes.txt").is_file());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn importing_a_folder_skips_hidden_files() {
+        let directory = std::env::temp_dir().join(format!("gaia_hidden_test_{}", std::process::id()));
+        let write = |path: &str, contents: &str| {
+            let path = directory.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+
+        write("pack.mcmeta", &test_util::pack_info(26, None, &[]));
+        write("data/test/worldgen/biome/x.json", &test_util::biome(0.5));
+        write("README.md", "readme");
+        write(".git/HEAD", "ref: refs/heads/main");
+        write(".git/objects/ab/cdef", "object");
+        write("data/test/.DS_Store", "");
+
+        let import = SerializableDatapack::from_directory(directory.to_str().unwrap()).unwrap();
+        let paths: Vec<&String> = import.datapack.passthrough_files().map(|(path, _)| path).collect();
+
+        assert_eq!(paths, ["README.md"]);
+        assert_eq!(import.datapack.biomes().count(), 1);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn filters_match_anywhere_and_never_match_invalid_patterns() {
+        let filter = Filter::new("^test$", "worldgen/biome");
+        assert!(filter.validate().is_ok());
+        assert!(filter.matches_path("data/test/worldgen/biome/x.json"));
+        assert!(filter.matches_path("overlay/data/test/worldgen/biome/x.json"));
+        assert!(!filter.matches_path("data/other/worldgen/biome/x.json"));
+        assert!(Filter::default().matches("any", "path"));
+
+        // Lookarounds are Java regex syntax which Rust's regex does not support
+        let filter = Filter::new("", "(?=worldgen)");
+        assert!(filter.validate().is_err());
+        assert!(!filter.matches("test", "worldgen/biome/x.json"));
+
+        let json = serde_json::to_string(&filter).unwrap();
+        assert_eq!(json, r#"{"namespace":"","path":"(?=worldgen)"}"#);
+        assert_eq!(serde_json::from_str::<Filter>(&json).unwrap(), filter);
+    }
+
+    #[test]
+    fn overlay_rename_rejects_taken_names() {
+        let pack_info = test_util::pack_info(26, Some((18, 26)), &[("a", 18, 18), ("b", 26, 26)]);
+        let (a_biome, b_biome) = (test_util::biome(0.1), test_util::biome(0.2));
+
+        let import = test_util::import(&[
+            ("pack.mcmeta", &pack_info),
+            ("a/data/test/worldgen/biome/x.json", &a_biome),
+            ("b/data/test/worldgen/biome/x.json", &b_biome)
+        ]);
+        let mut datapack = Datapack::try_from(import.datapack).unwrap();
+
+        assert!(datapack.rename_overlay(0, String::from("b")).is_err());
+        assert_eq!(datapack.overlays()[0].name, "a");
+
+        datapack.rename_overlay(0, String::from("c")).unwrap();
+        let exported = SerializableDatapack::from(datapack).export_stored_files().unwrap();
+        let paths: Vec<&str> = exported.keys().map(String::as_str).collect();
+        assert_eq!(paths, vec!["b/data/test/worldgen/biome/x.json", "c/data/test/worldgen/biome/x.json"]);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,535 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use serde_json::{Map, Value};
+use crate::data::biome::SerializableBiomeData;
+use crate::data::datapack::{Datapack, DatapackError, FileElement, SerializableDataElement, SerializableDataHolder, SerializableDatapack};
+
+const PACK_INFO_PATH: &str = "pack.mcmeta";
+
+////////////////////////////////
+//------ Datapack Diffs ------//
+////////////////////////////////
+
+/// Every resource which differs between two datapacks. Resources are compared where they are stored,
+/// so the root data is compared with the root data and each overlay with the overlay of the same name
+#[derive(Debug, Clone, Default)]
+pub struct DatapackDiff {
+    pub resources: Vec<ResourceDiff>
+}
+
+impl DatapackDiff {
+    pub fn is_empty(&self) -> bool { self.resources.is_empty() }
+
+    /// Counts the added, removed and changed resources of each registry and overlay
+    pub fn summary(&self) -> BTreeMap<(String, Option<String>), ChangeCounts> {
+        let mut summary: BTreeMap<(String, Option<String>), ChangeCounts> = BTreeMap::new();
+
+        for resource in &self.resources {
+            let counts = summary.entry((resource.registry.clone(), resource.overlay.clone())).or_default();
+            match resource.change {
+                ChangeKind::Added => counts.added += 1,
+                ChangeKind::Removed => counts.removed += 1,
+                ChangeKind::Changed => counts.changed += 1
+            }
+        }
+
+        summary
+    }
+}
+
+impl Display for DatapackDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for ((registry, overlay), counts) in self.summary() {
+            let registry = if registry.is_empty() { "other files" } else { &registry };
+            match overlay {
+                Some(overlay) => writeln!(f, "{} in overlay {}: {}", registry, overlay, counts)?,
+                None => writeln!(f, "{}: {}", registry, counts)?
+            }
+        }
+
+        for resource in &self.resources {
+            write!(f, "\n{}", resource)?;
+        }
+
+        Ok(())
+    }
+}
+
+//------------//
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChangeCounts {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize
+}
+
+impl Display for ChangeCounts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} added, {} removed, {} changed", self.added, self.removed, self.changed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed
+}
+
+impl Display for ChangeKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangeKind::Added => write!(f, "added"),
+            ChangeKind::Removed => write!(f, "removed"),
+            ChangeKind::Changed => write!(f, "changed")
+        }
+    }
+}
+
+//------------//
+
+/// A single resource which differs, in the root data or in one overlay
+#[derive(Debug, Clone)]
+pub struct ResourceDiff {
+    /// Path relative to the root or overlay directory
+    pub path: String,
+    /// The registry the resource belongs to, such as worldgen/biome, or empty for files outside the data directory
+    pub registry: String,
+    pub overlay: Option<String>,
+    pub change: ChangeKind,
+    /// Every json value which differs, for resources which are json on both sides.
+    /// Added and removed resources list their whole contents as one field
+    pub fields: Vec<FieldDiff>
+}
+
+impl Display for ResourceDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.change, self.path)?;
+        if let Some(overlay) = &self.overlay {
+            write!(f, " (overlay {})", overlay)?;
+        }
+
+        if self.change == ChangeKind::Changed {
+            for field in &self.fields {
+                write!(f, "\n  {}", field)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+//------------//
+
+/// A json value which differs, addressed by the keys and indices leading to it such as effects.sky_color or features[2]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    pub field: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>
+}
+
+impl Display for FieldDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let field = if self.field.is_empty() { "(value)" } else { &self.field };
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{}: {} -> {}", field, old, new),
+            (None, Some(new)) => write!(f, "{}: added {}", field, new),
+            (Some(old), None) => write!(f, "{}: removed {}", field, old),
+            (None, None) => write!(f, "{}", field)
+        }
+    }
+}
+
+//------------//
+
+impl SerializableDatapack {
+    /// Compares this datapack, as the old version, with a newer version of it
+    pub fn diff(&self, new: &SerializableDatapack) -> Result<DatapackDiff, DatapackError> {
+        let mut resources = Vec::new();
+
+        let old_pack_info = serde_json::to_value(&self.pack_info)?;
+        let new_pack_info = serde_json::to_value(&new.pack_info)?;
+        if old_pack_info != new_pack_info {
+            resources.push(ResourceDiff {
+                path: String::from(PACK_INFO_PATH),
+                registry: String::new(),
+                overlay: None,
+                change: ChangeKind::Changed,
+                fields: diff_json(&old_pack_info, &new_pack_info)
+            });
+        }
+
+        let old_biomes: BTreeMap<String, &SerializableDataHolder<SerializableBiomeData>> = self.biomes()
+            .map(|(resource_location, data_holder)| (SerializableBiomeData::get_file_path(resource_location), data_holder))
+            .collect();
+        let new_biomes: BTreeMap<String, &SerializableDataHolder<SerializableBiomeData>> = new.biomes()
+            .map(|(resource_location, data_holder)| (SerializableBiomeData::get_file_path(resource_location), data_holder))
+            .collect();
+
+        diff_holders(&old_biomes, &new_biomes, |biome| Ok(serde_json::from_str(&biome.serialize()?)?), &mut resources)?;
+
+        let old_files: BTreeMap<String, _> = self.passthrough_files().map(|(path, data_holder)| (path.clone(), data_holder)).collect();
+        let new_files: BTreeMap<String, _> = new.passthrough_files().map(|(path, data_holder)| (path.clone(), data_holder)).collect();
+
+        diff_holders(&old_files, &new_files, |file| Ok(get_file_value(file.contents())), &mut resources)?;
+
+        resources.sort_by(|a, b| (&a.overlay, &a.registry, &a.path).cmp(&(&b.overlay, &b.registry, &b.path)));
+        Ok(DatapackDiff { resources })
+    }
+}
+
+impl Datapack {
+    /// Compares this datapack, as the old version, with a newer version of it. See [`SerializableDatapack::diff`]
+    pub fn diff(&self, new: &Datapack) -> Result<DatapackDiff, DatapackError> {
+        SerializableDatapack::from(self.clone()).diff(&SerializableDatapack::from(new.clone()))
+    }
+}
+
+//------------//
+
+/// Compares every copy of the resources in two sets of data holders, keyed by path
+fn diff_holders<T>(
+    old: &BTreeMap<String, &SerializableDataHolder<T>>,
+    new: &BTreeMap<String, &SerializableDataHolder<T>>,
+    to_value: impl Fn(&T) -> Result<Value, DatapackError>,
+    resources: &mut Vec<ResourceDiff>
+) -> Result<(), DatapackError> {
+    let paths: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    for path in paths {
+        let old_copies = get_copies(old.get(path).copied());
+        let new_copies = get_copies(new.get(path).copied());
+        let overlays: BTreeSet<&Option<String>> = old_copies.keys().chain(new_copies.keys()).collect();
+
+        for overlay in overlays {
+            let old_value = old_copies.get(overlay).map(|data| to_value(data)).transpose()?;
+            let new_value = new_copies.get(overlay).map(|data| to_value(data)).transpose()?;
+
+            let (change, fields) = match (&old_value, &new_value) {
+                (None, Some(value)) => (ChangeKind::Added, vec![FieldDiff { field: String::new(), old: None, new: Some(value.clone()) }]),
+                (Some(value), None) => (ChangeKind::Removed, vec![FieldDiff { field: String::new(), old: Some(value.clone()), new: None }]),
+                (Some(old_value), Some(new_value)) if old_value != new_value => (ChangeKind::Changed, diff_json(old_value, new_value)),
+                _ => continue
+            };
+
+            resources.push(ResourceDiff {
+                path: path.clone(),
+                registry: get_registry(path),
+                overlay: overlay.clone(),
+                change,
+                fields
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Gets each stored copy of a resource, keyed by overlay name or None for the root
+fn get_copies<T>(data_holder: Option<&SerializableDataHolder<T>>) -> BTreeMap<Option<String>, &T> {
+    let Some(data_holder) = data_holder else { return BTreeMap::new() };
+
+    data_holder.root_data().map(|data| (None, data)).into_iter()
+        .chain(data_holder.get_overlay_data().into_iter().map(|(overlay, data)| (Some(String::from(overlay)), data)))
+        .collect()
+}
+
+/// Reads a passthrough file for comparing. Json is compared field by field, other text as a whole,
+/// and binary files such as images only by their size and hash
+pub(crate) fn get_file_value(contents: &[u8]) -> Value {
+    if let Ok(value) = serde_json::from_slice(contents) {
+        return value
+    }
+
+    match std::str::from_utf8(contents) {
+        Ok(text) => Value::String(String::from(text)),
+        Err(_) => {
+            let mut hasher = DefaultHasher::new();
+            contents.hash(&mut hasher);
+            Value::String(format!("binary file, {} bytes, hash {:016x}", contents.len(), hasher.finish()))
+        }
+    }
+}
+
+/// Works out the registry of a data file from its path. Worldgen and tag registries are nested one level deeper than the rest
+pub(crate) fn get_registry(path: &str) -> String {
+    let components: Vec<&str> = path.split('/').collect();
+
+    // data/<namespace>/<registry...>/<id>, where the id may contain directories itself
+    if components.len() < 4 || components[0] != "data" {
+        return String::new()
+    }
+
+    let folders = &components[2..components.len() - 1];
+    let depth = match folders {
+        ["tags", "worldgen", ..] => 3,
+        ["tags", ..] | ["worldgen", ..] => 2,
+        _ => 1
+    };
+
+    folders[..depth.min(folders.len())].join("/")
+}
+
+/// Lists every value which differs between two json values. Objects are compared key by key and
+/// arrays index by index, and anything else which differs is listed as a whole. See [`apply_field_diffs`] to apply them
+pub fn diff_json(old: &Value, new: &Value) -> Vec<FieldDiff> {
+    let mut fields = Vec::new();
+    diff_json_value(&mut Vec::new(), Some(old), Some(new), &mut fields);
+    fields
+}
+
+fn diff_json_value(field: &mut Vec<JsonKey>, old: Option<&Value>, new: Option<&Value>, fields: &mut Vec<FieldDiff>) {
+    match (old, new) {
+        (Some(old), Some(new)) if old == new => {}
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                field.push(JsonKey::Key(key.clone()));
+                diff_json_value(field, old.get(key), new.get(key), fields);
+                field.pop();
+            }
+        }
+        (Some(Value::Array(old)), Some(Value::Array(new))) => {
+            for index in 0..old.len().max(new.len()) {
+                field.push(JsonKey::Index(index));
+                diff_json_value(field, old.get(index), new.get(index), fields);
+                field.pop();
+            }
+        }
+        _ => fields.push(FieldDiff { field: format_field(field), old: old.cloned(), new: new.cloned() })
+    }
+}
+
+/// Applies the fields of a diff to the old value, giving the new value. Removed array entries are taken out
+/// from the last one down, as removing one moves every later entry. Returns false if a field could not be applied
+pub fn apply_field_diffs(value: &mut Value, fields: &[FieldDiff]) -> bool {
+    let (removed, changed): (Vec<&FieldDiff>, Vec<&FieldDiff>) = fields.iter().partition(|field| field.new.is_none());
+
+    changed.into_iter().chain(removed.into_iter().rev()).all(|field| {
+        parse_field(&field.field).is_some_and(|keys| set_json_field(value, &keys, field.new.clone()))
+    })
+}
+
+//------------//
+
+/// One step into a json value, either a key of an object or an index of an array
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum JsonKey {
+    Key(String),
+    Index(usize)
+}
+
+/// Writes the keys and indices leading to a value the way diffs show them, such as effects.sky_color or features[2].
+/// Keys which could be mistaken for part of the field, such as ones containing '.' or '[', are written as quoted
+/// json strings in brackets, such as effects["a.b"]
+pub(crate) fn format_field(keys: &[JsonKey]) -> String {
+    let mut field = String::new();
+
+    for key in keys {
+        match key {
+            JsonKey::Key(key) if !is_plain_key(key) => {
+                field.push_str(&format!("[{}]", Value::String(key.clone())));
+            }
+            JsonKey::Key(key) if field.is_empty() => field.push_str(key),
+            JsonKey::Key(key) => field.push_str(&format!(".{}", key)),
+            JsonKey::Index(index) => field.push_str(&format!("[{}]", index))
+        }
+    }
+
+    field
+}
+
+fn is_plain_key(key: &str) -> bool {
+    !key.is_empty() && !key.contains(['.', '[', ']', '"', '\\'])
+}
+
+/// Reads a field written by [`format_field`] back into keys and indices, or None if it is malformed
+pub(crate) fn parse_field(field: &str) -> Option<Vec<JsonKey>> {
+    let mut keys = Vec::new();
+    let mut rest = field;
+
+    while !rest.is_empty() {
+        if let Some(bracketed) = rest.strip_prefix('[') {
+            if bracketed.starts_with('"') {
+                // Quoted keys are json strings, which end at the first quote not escaped by a backslash
+                let mut stream = serde_json::Deserializer::from_str(bracketed).into_iter::<String>();
+                let key = stream.next()?.ok()?;
+                rest = bracketed[stream.byte_offset()..].strip_prefix(']')?;
+                keys.push(JsonKey::Key(key));
+            }
+            else {
+                let (index, after) = bracketed.split_once(']')?;
+                keys.push(JsonKey::Index(index.parse().ok()?));
+                rest = after;
+            }
+        }
+        else {
+            // Only the first key of a field is written without a leading dot
+            let plain = match rest.strip_prefix('.') {
+                Some(plain) => plain,
+                None if keys.is_empty() => rest,
+                None => return None
+            };
+
+            let end = plain.find(['.', '[']).unwrap_or(plain.len());
+            if end == 0 {
+                return None
+            }
+
+            keys.push(JsonKey::Key(String::from(&plain[..end])));
+            rest = &plain[end..];
+        }
+    }
+
+    Some(keys)
+}
+
+pub(crate) fn get_json_field<'a>(value: &'a Value, field: &[JsonKey]) -> Option<&'a Value> {
+    field.iter().try_fold(value, |value, key| match (key, value) {
+        (JsonKey::Key(key), Value::Object(object)) => object.get(key),
+        (JsonKey::Index(index), Value::Array(array)) => array.get(*index),
+        _ => None
+    })
+}
+
+/// Replaces a nested json value, removing it if the new value is None. Missing objects along the way are created,
+/// and arrays grow by one entry when setting the index just past their end. Returns false if the value could not be
+/// put in place, such as when a value along the way has a different type or an index is further past the end
+#[must_use]
+pub(crate) fn set_json_field(value: &mut Value, field: &[JsonKey], new_value: Option<Value>) -> bool {
+    let Some((last, parents)) = field.split_last() else {
+        return match new_value {
+            Some(new_value) => {
+                *value = new_value;
+                true
+            }
+            None => false
+        }
+    };
+
+    let mut target = value;
+    for (depth, key) in parents.iter().enumerate() {
+        // Missing values along the way are only created when something is set, not when removing
+        let empty = match (&field[depth + 1], &new_value) {
+            (_, None) => None,
+            (JsonKey::Key(_), Some(_)) => Some(Value::Object(Map::new())),
+            (JsonKey::Index(_), Some(_)) => Some(Value::Array(Vec::new()))
+        };
+
+        let next = match (key, target) {
+            (JsonKey::Key(key), Value::Object(object)) => match (object.contains_key(key), empty) {
+                (true, _) => object.get_mut(key),
+                (false, Some(empty)) => Some(object.entry(key.clone()).or_insert(empty)),
+                (false, None) => return true
+            },
+            (JsonKey::Index(index), Value::Array(array)) => match (*index < array.len(), empty) {
+                (true, _) => array.get_mut(*index),
+                (false, Some(empty)) if *index == array.len() => {
+                    array.push(empty);
+                    array.last_mut()
+                }
+                (false, Some(_)) => return false,
+                (false, None) => return true
+            },
+            _ => return false
+        };
+
+        let Some(next) = next else { return false };
+        target = next;
+    }
+
+    match (last, target, new_value) {
+        (JsonKey::Key(key), Value::Object(object), Some(new_value)) => { object.insert(key.clone(), new_value); }
+        (JsonKey::Key(key), Value::Object(object), None) => { object.shift_remove(key); }
+        (JsonKey::Index(index), Value::Array(array), Some(new_value)) => {
+            if *index < array.len() {
+                array[*index] = new_value;
+            }
+            else if *index == array.len() {
+                array.push(new_value);
+            }
+            else {
+                return false
+            }
+        }
+        (JsonKey::Index(index), Value::Array(array), None) => if *index < array.len() {
+            array.remove(*index);
+        },
+        _ => return false
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn assert_round_trip(old: Value, new: Value) {
+        let fields = diff_json(&old, &new);
+
+        let mut value = old;
+        assert!(apply_field_diffs(&mut value, &fields), "{:?} should apply", fields);
+        assert_eq!(value, new);
+    }
+
+    #[test]
+    fn diffs_apply_back() {
+        assert_round_trip(json!({"a": 1, "b": {"c": 2}}), json!({"a": 3, "b": {"d": 4}}));
+        assert_round_trip(json!({"features": ["a", "b", "c"]}), json!({"features": ["a"]}));
+        assert_round_trip(json!({"features": ["a"]}), json!({"features": ["a", "b", "c"]}));
+        assert_round_trip(json!({"features": [["a", "b"], ["c"]]}), json!({"features": [["x"], ["c", "d"], [], ["e"]]}));
+        assert_round_trip(json!({"features": [["a", "b"], ["c"], ["d"]]}), json!({"features": [[], ["c"]]}));
+        assert_round_trip(json!({"a.b": 1, "c[0]": {"\"d\"": 2}, "": 3}), json!({"a.b": 2, "c[0]": {"\"d\"": 3}, "": 4}));
+        assert_round_trip(json!({"a": [1, 2]}), json!({"a": {"b": 1}}));
+        assert_round_trip(json!([1, 2, 3]), json!([4]));
+    }
+
+    #[test]
+    fn fields_escape_keys() {
+        let keys = vec![
+            JsonKey::Key(String::from("effects")),
+            JsonKey::Key(String::from("a.b")),
+            JsonKey::Index(2),
+            JsonKey::Key(String::from("c[0]")),
+            JsonKey::Key(String::from("\"quoted\\\"")),
+            JsonKey::Key(String::new()),
+            JsonKey::Key(String::from("sky_color"))
+        ];
+
+        let field = format_field(&keys);
+        assert_eq!(field, r#"effects["a.b"][2]["c[0]"]["\"quoted\\\""][""].sky_color"#);
+        assert_eq!(parse_field(&field), Some(keys));
+
+        assert_eq!(format_field(&[JsonKey::Key(String::from("features")), JsonKey::Index(3), JsonKey::Index(5)]), "features[3][5]");
+        assert_eq!(parse_field("features[3][5]"), Some(vec![JsonKey::Key(String::from("features")), JsonKey::Index(3), JsonKey::Index(5)]));
+        assert_eq!(parse_field("a[x]"), None);
+        assert_eq!(parse_field("a..b"), None);
+        assert_eq!(parse_field("a[0]b"), None);
+    }
+
+    #[test]
+    fn set_json_field_reports_values_it_cannot_place() {
+        let mut value = json!({"features": [["a"]]});
+
+        assert!(!set_json_field(&mut value, &parse_field("features[0][5]").unwrap(), Some(json!("b"))));
+        assert!(!set_json_field(&mut value, &parse_field("features.a").unwrap(), Some(json!("b"))));
+        assert!(set_json_field(&mut value, &parse_field("features[0][1]").unwrap(), Some(json!("b"))));
+        assert!(set_json_field(&mut value, &parse_field("missing.x").unwrap(), None));
+        assert_eq!(value, json!({"features": [["a", "b"]]}));
+    }
+
+    #[test]
+    fn removing_a_field_keeps_the_order_of_the_others() {
+        let mut value = json!({"a": 1, "b": 2, "c": 3, "d": 4});
+
+        assert!(set_json_field(&mut value, &parse_field("b").unwrap(), None));
+        let keys: Vec<&String> = value.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["a", "c", "d"]);
+    }
+}
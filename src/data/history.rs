@@ -0,0 +1,298 @@
+use std::time::{Duration, Instant};
+use crate::data::biome::BiomeData;
+use crate::data::datapack::{Datapack, DatapackFormat, Filter, Overlay, PassthroughFile, SerializableDataHolder};
+use crate::data::util::{ResourceLocation, Text};
+
+// Oldest edits are dropped past this point so that the history does not grow forever
+const MAX_HISTORY: usize = 500;
+// Edits to the same field after a pause this long are undone separately
+const GROUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+//////////////////////////////
+//------ Edit History ------//
+//////////////////////////////
+
+/// A reversible change to a datapack. Each edit holds the value from before and after the change,
+/// so it can be applied in either direction
+#[derive(Debug, Clone)]
+pub enum DatapackEdit {
+    Name { before: String, after: String },
+    Description { before: Vec<Text>, after: Vec<Text> },
+    Formats { before: FormatSelection, after: FormatSelection },
+    Overlays { before: Vec<Overlay>, after: Vec<Overlay> },
+    // Renaming an overlay also moves the data stored in it, so it is kept separate from other overlay changes
+    RenameOverlay { index: usize, before: String, after: String },
+    Filters { before: Vec<Filter>, after: Vec<Filter> },
+    Biome { resource_location: ResourceLocation, before: Option<Box<BiomeData>>, after: Option<Box<BiomeData>> },
+    // Files which are not modeled, such as tags, keyed by path
+    Passthrough { path: String, before: Option<Box<SerializableDataHolder<PassthroughFile>>>, after: Option<Box<SerializableDataHolder<PassthroughFile>>> },
+    // Replaces everything at once, such as when applying a merge
+    Datapack { before: Box<Datapack>, after: Box<Datapack> }
+}
+
+impl DatapackEdit {
+    fn apply(&self, datapack: &mut Datapack, is_undo: bool) {
+        use DatapackEdit::*;
+        match self {
+            Name { before, after } => datapack.set_name(if is_undo { before } else { after }),
+            Description { before, after } => *datapack.description_mut() = if is_undo { before } else { after }.clone(),
+            Formats { before, after } => if is_undo { before } else { after }.apply(datapack),
+            Overlays { before, after } => *datapack.overlays_mut() = if is_undo { before } else { after }.clone(),
+            RenameOverlay { index, before, after } => {
+                // Undoing and redoing returns the datapack to the state the rename was made in, where the name was free
+                let _ = datapack.rename_overlay(*index, if is_undo { before } else { after }.clone());
+            }
+            Filters { before, after } => *datapack.filters_mut() = if is_undo { before } else { after }.clone(),
+            Biome { resource_location, before, after } => {
+                match if is_undo { before } else { after } {
+                    Some(biome) => datapack.biomes_mut().insert(resource_location.clone(), (**biome).clone()),
+                    None => datapack.biomes_mut().remove(resource_location)
+                };
+            }
+            Passthrough { path, before, after } => {
+                match if is_undo { before } else { after } {
+                    Some(data_holder) => datapack.passthrough_mut().insert(path.clone(), (**data_holder).clone()),
+                    None => datapack.passthrough_mut().remove(path)
+                };
+            }
+            Datapack { before, after } => *datapack = (**if is_undo { before } else { after }).clone()
+        }
+    }
+
+    /// Folds a later edit of the same group into this one, keeping the value from before the first edit.
+    /// Only edits to the same value can be folded together, and false is returned for any other edit
+    fn merge(&mut self, later: &DatapackEdit) -> bool {
+        use DatapackEdit::*;
+        match (self, later) {
+            (Name { after, .. }, Name { after: later, .. }) => *after = later.clone(),
+            (Description { after, .. }, Description { after: later, .. }) => *after = later.clone(),
+            (Formats { after, .. }, Formats { after: later, .. }) => *after = *later,
+            (Overlays { after, .. }, Overlays { after: later, .. }) => *after = later.clone(),
+            (RenameOverlay { index, after, .. }, RenameOverlay { index: later_index, after: later, .. }) if index == later_index => {
+                *after = later.clone()
+            }
+            (Filters { after, .. }, Filters { after: later, .. }) => *after = later.clone(),
+            (Biome { resource_location, after, .. }, Biome { resource_location: later_location, after: later, .. }) if resource_location == later_location => {
+                *after = later.clone()
+            }
+            (Passthrough { path, after, .. }, Passthrough { path: later_path, after: later, .. }) if path == later_path => {
+                *after = later.clone()
+            }
+            (Datapack { after, .. }, Datapack { after: later, .. }) => *after = later.clone(),
+            _ => return false
+        }
+
+        true
+    }
+}
+
+//------------//
+
+/// The root, minimum and maximum formats of a datapack, which are always changed together
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FormatSelection {
+    pub root_format: DatapackFormat,
+    pub min_format: DatapackFormat,
+    pub max_format: DatapackFormat
+}
+
+impl FormatSelection {
+    pub fn from_datapack(datapack: &Datapack) -> Self {
+        Self {
+            root_format: datapack.root_format(),
+            min_format: datapack.min_format(),
+            max_format: datapack.max_format()
+        }
+    }
+
+    fn apply(&self, datapack: &mut Datapack) {
+        datapack.set_root_format(self.root_format);
+        datapack.set_min_format(self.min_format);
+        datapack.set_max_format(self.max_format);
+    }
+}
+
+//------------//
+
+/// Every edit made to a datapack, in the order they were made.
+/// Edits sharing a group with the edit before them, such as typing into the same text field, are undone together
+/// until the group is ended by [`EditHistory::end_group`] or a pause in editing
+#[derive(Debug, Clone, Default)]
+pub struct EditHistory {
+    undo_stack: Vec<(DatapackEdit, Option<String>)>,
+    redo_stack: Vec<DatapackEdit>,
+    // When the last edit was recorded, to end its group after a pause
+    last_edit: Option<Instant>
+}
+
+impl EditHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an edit which has already been applied to the datapack
+    pub fn record(&mut self, edit: DatapackEdit, group: Option<String>) {
+        self.record_at(edit, group, Instant::now())
+    }
+
+    fn record_at(&mut self, edit: DatapackEdit, group: Option<String>, now: Instant) {
+        self.redo_stack.clear();
+
+        let paused = self.last_edit.is_some_and(|last_edit| now.saturating_duration_since(last_edit) >= GROUP_TIMEOUT);
+        self.last_edit = Some(now);
+
+        if let Some((last, last_group)) = self.undo_stack.last_mut() {
+            if !paused && group.is_some() && *last_group == group && last.merge(&edit) {
+                return
+            }
+        }
+
+        self.undo_stack.push((edit, group));
+
+        if self.undo_stack.len() > MAX_HISTORY {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    /// Stops later edits from joining the group of the last edit, such as when the edited field loses focus
+    pub fn end_group(&mut self) {
+        if let Some((_, group)) = self.undo_stack.last_mut() {
+            *group = None;
+        }
+    }
+
+    pub fn can_undo(&self) -> bool { !self.undo_stack.is_empty() }
+    pub fn can_redo(&self) -> bool { !self.redo_stack.is_empty() }
+
+    /// Reverts the last edit, returning false if there was nothing to undo
+    pub fn undo(&mut self, datapack: &mut Datapack) -> bool {
+        let Some((edit, _)) = self.undo_stack.pop() else { return false };
+
+        edit.apply(datapack, true);
+        self.redo_stack.push(edit);
+        true
+    }
+
+    /// Applies the last undone edit again, returning false if there was nothing to redo
+    pub fn redo(&mut self, datapack: &mut Datapack) -> bool {
+        let Some(edit) = self.redo_stack.pop() else { return false };
+
+        edit.apply(datapack, false);
+        self.undo_stack.push((edit, None));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::test_util;
+    use super::*;
+
+    fn datapack() -> Datapack {
+        Datapack::try_from(test_util::import(&[("pack.mcmeta", &test_util::pack_info(48, None, &[]))]).datapack).unwrap()
+    }
+
+    /// Sets the name as if typed into the name field at the given time
+    fn type_name(history: &mut EditHistory, datapack: &mut Datapack, name: &str, at: Instant) {
+        let before = String::from(datapack.name());
+        datapack.set_name(name);
+        history.record_at(DatapackEdit::Name { before, after: String::from(name) }, Some(String::from("name")), at);
+    }
+
+    #[test]
+    fn typing_into_one_field_is_undone_at_once() {
+        let (mut history, mut datapack) = (EditHistory::new(), datapack());
+        let start = Instant::now();
+        type_name(&mut history, &mut datapack, "a", start);
+        type_name(&mut history, &mut datapack, "ab", start + Duration::from_millis(200));
+
+        assert!(history.undo(&mut datapack));
+        assert_eq!(datapack.name(), "test");
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn ending_a_group_starts_a_new_undo_step() {
+        let (mut history, mut datapack) = (EditHistory::new(), datapack());
+        let start = Instant::now();
+        type_name(&mut history, &mut datapack, "a", start);
+        history.end_group();
+        type_name(&mut history, &mut datapack, "ab", start + Duration::from_millis(200));
+
+        assert!(history.undo(&mut datapack));
+        assert_eq!(datapack.name(), "a");
+        assert!(history.undo(&mut datapack));
+        assert_eq!(datapack.name(), "test");
+    }
+
+    #[test]
+    fn pausing_starts_a_new_undo_step() {
+        let (mut history, mut datapack) = (EditHistory::new(), datapack());
+        let start = Instant::now();
+        type_name(&mut history, &mut datapack, "a", start);
+        type_name(&mut history, &mut datapack, "ab", start + GROUP_TIMEOUT);
+        type_name(&mut history, &mut datapack, "abc", start + GROUP_TIMEOUT + Duration::from_millis(200));
+
+        assert!(history.undo(&mut datapack));
+        assert_eq!(datapack.name(), "a");
+        assert!(history.undo(&mut datapack));
+        assert_eq!(datapack.name(), "test");
+    }
+
+    #[test]
+    fn different_edits_in_one_group_are_undone_separately() {
+        let (mut history, mut datapack) = (EditHistory::new(), datapack());
+        let start = Instant::now();
+        let group = Some(String::from("field"));
+
+        type_name(&mut history, &mut datapack, "a", start);
+        let before = datapack.filters().clone();
+        datapack.filters_mut().push(Filter::new("test", ""));
+        history.record_at(DatapackEdit::Filters { before, after: datapack.filters().clone() }, group.clone(), start);
+        let before = String::from(datapack.name());
+        datapack.set_name("ab");
+        history.record_at(DatapackEdit::Name { before, after: String::from("ab") }, group, start);
+
+        assert!(history.undo(&mut datapack));
+        assert_eq!(datapack.name(), "a");
+        assert_eq!(datapack.filters().len(), 1);
+        assert!(history.undo(&mut datapack));
+        assert!(datapack.filters().is_empty());
+        assert!(history.undo(&mut datapack));
+        assert_eq!(datapack.name(), "test");
+    }
+
+    #[test]
+    fn edits_to_different_resources_are_undone_separately() {
+        let pack_info = test_util::pack_info(48, None, &[]);
+        let mut datapack = Datapack::try_from(test_util::import(&[("pack.mcmeta", &pack_info), ("a.txt", "a"), ("b.txt", "b")]).datapack).unwrap();
+        let files = std::mem::take(datapack.passthrough_mut());
+        let mut history = EditHistory::new();
+
+        // Recreating both files in one group, such as from the same editor
+        for path in ["a.txt", "b.txt"] {
+            let file = files[path].clone();
+            datapack.passthrough_mut().insert(String::from(path), file.clone());
+            history.record(DatapackEdit::Passthrough { path: String::from(path), before: None, after: Some(Box::new(file)) }, Some(String::from("file")));
+        }
+
+        assert!(history.undo(&mut datapack));
+        assert!(datapack.passthrough().contains_key("a.txt"));
+        assert!(!datapack.passthrough().contains_key("b.txt"));
+        assert!(history.undo(&mut datapack));
+        assert!(datapack.passthrough().is_empty());
+    }
+
+    #[test]
+    fn redone_edits_do_not_join_later_typing() {
+        let (mut history, mut datapack) = (EditHistory::new(), datapack());
+        let start = Instant::now();
+        type_name(&mut history, &mut datapack, "a", start);
+        history.undo(&mut datapack);
+        history.redo(&mut datapack);
+        type_name(&mut history, &mut datapack, "ab", start + Duration::from_millis(200));
+
+        assert!(history.undo(&mut datapack));
+        assert_eq!(datapack.name(), "a");
+    }
+}
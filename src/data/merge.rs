@@ -0,0 +1,418 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use serde_json::{Map, Value};
+use crate::data::biome::SerializableBiomeData;
+use crate::data::datapack::{Datapack, DatapackError, DatapackImport, FileElement, SerializableDataElement, SerializableDatapack};
+use crate::data::diff::{self, JsonKey};
+use crate::data::overlay::DatapackFiles;
+
+const PACK_INFO_PATH: &str = "pack.mcmeta";
+
+//////////////////////////////////
+//------ Three-Way Merges ------//
+//////////////////////////////////
+
+/// Combines two datapacks edited from the same base. Each file is merged where it is stored, in the root data or in one
+/// overlay, and json files are merged field by field so that edits to different fields of the same file both apply.
+/// Arrays are merged as a whole. Anything changed differently on both sides is a conflict which has to be resolved
+#[derive(Debug, Clone)]
+pub struct DatapackMerge {
+    name: String,
+    files: BTreeMap<StoredPath, MergedFile>,
+    conflicts: Vec<MergeConflict>
+}
+
+/// A file path relative to the root or overlay directory, along with the overlay holding it
+pub(crate) type StoredPath = (Option<String>, String);
+
+#[derive(Debug, Clone)]
+enum MergedFile {
+    Json(Value),
+    Raw(Vec<u8>),
+    Removed
+}
+
+impl DatapackMerge {
+    pub fn new(base: &SerializableDatapack, ours: &SerializableDatapack, theirs: &SerializableDatapack) -> Result<Self, DatapackError> {
+        let base_files = get_stored_files(base)?;
+        let our_files = get_stored_files(ours)?;
+        let their_files = get_stored_files(theirs)?;
+
+        let paths: BTreeSet<&StoredPath> = base_files.keys().chain(our_files.keys()).chain(their_files.keys()).collect();
+        let mut files = BTreeMap::new();
+        let mut conflicts = Vec::new();
+
+        for path in paths {
+            let (base_file, our_file, their_file) = (base_files.get(path), our_files.get(path), their_files.get(path));
+
+            let merged = if our_file == their_file || their_file == base_file { to_merged_file(our_file) }
+                else if our_file == base_file { to_merged_file(their_file) }
+                else {
+                    let as_json = |file: Option<&Vec<u8>>| file.and_then(|contents| serde_json::from_slice::<Value>(contents).ok());
+
+                    match (as_json(our_file), as_json(their_file)) {
+                        // Both sides still have the file as json, so their changes are combined field by field
+                        (Some(our_json), Some(their_json)) => {
+                            let base_json = as_json(base_file);
+                            let mut field_conflicts = Vec::new();
+                            let merged = merge_json(&mut Vec::new(), base_json.as_ref(), Some(&our_json), Some(&their_json), &mut field_conflicts);
+
+                            conflicts.extend(field_conflicts.into_iter().map(|(field, base, ours, theirs)| MergeConflict {
+                                path: path.1.clone(),
+                                overlay: path.0.clone(),
+                                field: ConflictField::Json(field),
+                                base,
+                                ours,
+                                theirs,
+                                resolution: None
+                            }));
+
+                            merged.map(MergedFile::Json).unwrap_or(MergedFile::Removed)
+                        }
+                        _ => {
+                            let to_value = |file: Option<&Vec<u8>>| file.map(|contents| diff::get_file_value(contents));
+                            conflicts.push(MergeConflict {
+                                path: path.1.clone(),
+                                overlay: path.0.clone(),
+                                field: ConflictField::File { ours: our_file.cloned(), theirs: their_file.cloned(), base: base_file.cloned() },
+                                base: to_value(base_file),
+                                ours: to_value(our_file),
+                                theirs: to_value(their_file),
+                                resolution: None
+                            });
+
+                            to_merged_file(our_file)
+                        }
+                    }
+                };
+
+            files.insert(path.clone(), merged);
+        }
+
+        Ok(Self {
+            name: String::from(ours.name()),
+            files,
+            conflicts
+        })
+    }
+
+    pub fn conflicts(&self) -> &Vec<MergeConflict> { &self.conflicts }
+
+    pub fn is_resolved(&self) -> bool {
+        self.conflicts.iter().all(|conflict| conflict.resolution.is_some())
+    }
+
+    /// Picks which side's version to keep for a conflict
+    pub fn resolve(&mut self, index: usize, side: MergeSide) {
+        if let Some(conflict) = self.conflicts.get_mut(index) {
+            conflict.resolution = Some(side);
+        }
+    }
+
+    /// Builds the merged datapack. Fails if any conflict has not been resolved yet
+    pub fn finish(&self) -> Result<DatapackImport, DatapackError> {
+        let mut files = self.files.clone();
+
+        for conflict in &self.conflicts {
+            let Some(side) = conflict.resolution else {
+                return Err(DatapackError::File(format!("Merge conflict in {} has not been resolved", conflict).into()))
+            };
+
+            let stored_path = (conflict.overlay.clone(), conflict.path.clone());
+
+            match &conflict.field {
+                ConflictField::Json(field) => {
+                    if let Some(MergedFile::Json(value)) = files.get_mut(&stored_path) {
+                        if !diff::set_json_field(value, field, conflict.get(side).cloned()) {
+                            return Err(DatapackError::File(format!("Could not apply the resolution of merge conflict in {}", conflict).into()))
+                        }
+                    }
+                }
+                ConflictField::File { base, ours, theirs } => {
+                    let file = match side {
+                        MergeSide::Base => base,
+                        MergeSide::Ours => ours,
+                        MergeSide::Theirs => theirs
+                    };
+                    files.insert(stored_path, to_merged_file(file.as_ref()));
+                }
+            }
+        }
+
+        let mut datapack_files = DatapackFiles::new();
+
+        for ((overlay, path), file) in files {
+            let contents = match file {
+                MergedFile::Json(value) => serde_json::to_string_pretty(&value)?.into_bytes(),
+                MergedFile::Raw(contents) => contents,
+                MergedFile::Removed => continue
+            };
+
+            let path = match overlay {
+                Some(overlay) => format!("{}/{}", overlay, path),
+                None => path
+            };
+
+            datapack_files.insert(path, contents);
+        }
+
+        SerializableDatapack::from_files(&self.name, &datapack_files)
+    }
+}
+
+impl Datapack {
+    /// Merges the edits made in two datapacks edited from this one. See [`DatapackMerge`].
+    /// This has to be their common base, as changes made since it on either side would otherwise be seen as reverted
+    pub fn merge(&self, ours: &Datapack, theirs: &Datapack) -> Result<DatapackMerge, DatapackError> {
+        DatapackMerge::new(
+            &SerializableDatapack::from(self.clone()),
+            &SerializableDatapack::from(ours.clone()),
+            &SerializableDatapack::from(theirs.clone())
+        )
+    }
+}
+
+//------------//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeSide {
+    Base,
+    Ours,
+    Theirs
+}
+
+/// A value changed differently on both sides of a merge
+#[derive(Debug, Clone)]
+pub struct MergeConflict {
+    pub path: String,
+    pub overlay: Option<String>,
+    field: ConflictField,
+    /// Each side's value, or None where the value is missing. Files which are not json are shown as text or a hash
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+    pub resolution: Option<MergeSide>
+}
+
+#[derive(Debug, Clone)]
+enum ConflictField {
+    // Keys and indices leading to the conflicting value
+    Json(Vec<JsonKey>),
+    // The whole file conflicts, such as a file removed on one side and changed on the other
+    File { base: Option<Vec<u8>>, ours: Option<Vec<u8>>, theirs: Option<Vec<u8>> }
+}
+
+impl MergeConflict {
+    /// The conflicting field, written the same way as in diffs, or empty if the whole file conflicts
+    pub fn field(&self) -> String {
+        match &self.field {
+            ConflictField::Json(keys) => diff::format_field(keys),
+            ConflictField::File { .. } => String::new()
+        }
+    }
+
+    pub fn get(&self, side: MergeSide) -> Option<&Value> {
+        match side {
+            MergeSide::Base => self.base.as_ref(),
+            MergeSide::Ours => self.ours.as_ref(),
+            MergeSide::Theirs => self.theirs.as_ref()
+        }
+    }
+}
+
+impl Display for MergeConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some(overlay) = &self.overlay {
+            write!(f, " (overlay {})", overlay)?;
+        }
+
+        let field = self.field();
+        if !field.is_empty() {
+            write!(f, " {}", field)?;
+        }
+
+        Ok(())
+    }
+}
+
+//------------//
+
+/// Gets the contents of every stored file, including pack.mcmeta, keyed by overlay and path
+pub(crate) fn get_stored_files(datapack: &SerializableDatapack) -> Result<BTreeMap<StoredPath, Vec<u8>>, DatapackError> {
+    let mut files = BTreeMap::new();
+    files.insert((None, String::from(PACK_INFO_PATH)), serde_json::to_string_pretty(&datapack.pack_info)?.into_bytes());
+
+    for (resource_location, data_holder) in datapack.biomes() {
+        let path = SerializableBiomeData::get_file_path(resource_location);
+
+        if let Some(biome) = data_holder.root_data() {
+            files.insert((None, path.clone()), biome.serialize()?.into_bytes());
+        }
+
+        for (overlay, biome) in data_holder.get_overlay_data() {
+            files.insert((Some(String::from(overlay)), path.clone()), biome.serialize()?.into_bytes());
+        }
+    }
+
+    for (path, data_holder) in datapack.passthrough_files() {
+        if let Some(file) = data_holder.root_data() {
+            files.insert((None, path.clone()), file.contents().to_vec());
+        }
+
+        for (overlay, file) in data_holder.get_overlay_data() {
+            files.insert((Some(String::from(overlay)), path.clone()), file.contents().to_vec());
+        }
+    }
+
+    Ok(files)
+}
+
+fn to_merged_file(file: Option<&Vec<u8>>) -> MergedFile {
+    // Files taken whole from one side are kept as they are, so that their formatting does not change
+    match file {
+        Some(contents) => MergedFile::Raw(contents.clone()),
+        None => MergedFile::Removed
+    }
+}
+
+type JsonConflict = (Vec<JsonKey>, Option<Value>, Option<Value>, Option<Value>);
+
+/// Merges one json value, returning None if it was removed. Conflicting values keep our side until resolved
+fn merge_json(
+    field: &mut Vec<JsonKey>,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<JsonConflict>
+) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned()
+    }
+    if ours == base {
+        return theirs.cloned()
+    }
+
+    match (ours, theirs) {
+        (Some(Value::Object(our_object)), Some(Value::Object(their_object))) => {
+            // A key added on both sides is merged as if it had been empty in the base
+            let empty = Map::new();
+            let base_object = match base {
+                Some(Value::Object(base_object)) => base_object,
+                _ => &empty
+            };
+
+            let keys: BTreeSet<&String> = base_object.keys().chain(our_object.keys()).chain(their_object.keys()).collect();
+            let mut merged = Map::new();
+
+            for key in keys {
+                field.push(JsonKey::Key(key.clone()));
+                if let Some(value) = merge_json(field, base_object.get(key), our_object.get(key), their_object.get(key), conflicts) {
+                    merged.insert(key.clone(), value);
+                }
+                field.pop();
+            }
+
+            Some(Value::Object(merged))
+        }
+        _ => {
+            conflicts.push((field.clone(), base.cloned(), ours.cloned(), theirs.cloned()));
+            ours.cloned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::data::test_util;
+    use super::*;
+
+    fn merge(base: &Value, ours: &Value, theirs: &Value) -> (Option<Value>, Vec<JsonConflict>) {
+        let mut conflicts = Vec::new();
+        let merged = merge_json(&mut Vec::new(), Some(base), Some(ours), Some(theirs), &mut conflicts);
+        (merged, conflicts)
+    }
+
+    fn biome(temperature: f64, downfall: f64) -> String {
+        let mut biome: Value = serde_json::from_str(&test_util::biome(temperature)).unwrap();
+        biome["downfall"] = json!(downfall);
+        biome.to_string()
+    }
+
+    fn datapack(biome: &str) -> SerializableDatapack {
+        let pack_info = test_util::pack_info(26, None, &[]);
+        test_util::import(&[("pack.mcmeta", &pack_info), ("data/test/worldgen/biome/x.json", biome)]).datapack
+    }
+
+    #[test]
+    fn merges_edits_to_different_fields() {
+        let base = json!({"temperature": 0.8, "effects": {"sky_color": 1, "fog_color": 2}});
+        let ours = json!({"temperature": 0.5, "effects": {"sky_color": 1, "fog_color": 2}});
+        let theirs = json!({"temperature": 0.8, "effects": {"sky_color": 3, "fog_color": 2}, "downfall": 0.1});
+
+        let (merged, conflicts) = merge(&base, &ours, &theirs);
+        assert!(conflicts.is_empty());
+        assert_eq!(merged, Some(json!({"temperature": 0.5, "effects": {"sky_color": 3, "fog_color": 2}, "downfall": 0.1})));
+    }
+
+    #[test]
+    fn conflicting_edits_keep_our_value() {
+        let base = json!({"temperature": 0.8, "downfall": 0.5});
+        let ours = json!({"temperature": 0.5, "downfall": 0.5});
+        let theirs = json!({"temperature": 0.2, "downfall": 0.1});
+
+        let (merged, conflicts) = merge(&base, &ours, &theirs);
+        assert_eq!(merged, Some(json!({"temperature": 0.5, "downfall": 0.1})));
+        assert_eq!(conflicts, vec![(vec![JsonKey::Key(String::from("temperature"))], Some(json!(0.8)), Some(json!(0.5)), Some(json!(0.2)))]);
+    }
+
+    #[test]
+    fn keys_added_on_both_sides_merge_as_if_empty_in_the_base() {
+        let base = json!({});
+        let ours = json!({"effects": {"sky_color": 1}});
+        let theirs = json!({"effects": {"fog_color": 2}});
+
+        let (merged, conflicts) = merge(&base, &ours, &theirs);
+        assert!(conflicts.is_empty());
+        assert_eq!(merged, Some(json!({"effects": {"sky_color": 1, "fog_color": 2}})));
+
+        // The same key added with different values conflicts, with no base value
+        let (_, conflicts) = merge(&base, &json!({"downfall": 0.1}), &json!({"downfall": 0.2}));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].1, None);
+    }
+
+    #[test]
+    fn arrays_are_merged_whole() {
+        let base = json!({"features": [["a"], ["b"]]});
+        let ours = json!({"features": [["a", "c"], ["b"]]});
+        let theirs = json!({"features": [["a"], ["b", "d"]]});
+
+        let (merged, conflicts) = merge(&base, &ours, &theirs);
+        assert_eq!(merged, Some(ours));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(diff::format_field(&conflicts[0].0), "features");
+    }
+
+    #[test]
+    fn finish_applies_merged_fields_and_resolutions() {
+        let base = datapack(&biome(0.8, 0.5));
+        let ours = datapack(&biome(0.5, 0.5));
+        let theirs = datapack(&biome(0.2, 0.1));
+
+        let mut merge = DatapackMerge::new(&base, &ours, &theirs).unwrap();
+        assert_eq!(merge.conflicts().len(), 1);
+        assert_eq!(merge.conflicts()[0].field(), "temperature");
+        assert!(!merge.is_resolved());
+        assert!(merge.finish().is_err());
+
+        merge.resolve(0, MergeSide::Theirs);
+        assert!(merge.is_resolved());
+
+        let files = merge.finish().unwrap().datapack.export_files().unwrap();
+        let merged: Value = serde_json::from_slice(&files["data/test/worldgen/biome/x.json"]).unwrap();
+        assert_eq!(merged["temperature"].as_f64(), Some(0.2));
+        assert_eq!(merged["downfall"].as_f64(), Some(0.1));
+    }
+}
@@ -1,4 +1,20 @@
-pub(crate) mod datapack;
-mod carver;
-mod biome;
-pub mod util;
\ No newline at end of file
+pub mod datapack;
+pub mod diff;
+pub mod carver;
+pub mod biome;
+pub mod build;
+pub mod history;
+pub mod merge;
+pub mod overlay;
+pub mod patch;
+pub mod project;
+pub mod reference;
+pub mod rename;
+pub mod stack;
+pub mod tag;
+pub mod validate;
+pub mod util;
+pub mod vanilla;
+
+#[cfg(test)]
+mod test_util;
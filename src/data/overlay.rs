@@ -0,0 +1,234 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::data::datapack::{DatapackError, DatapackFormat, Diagnostic, Overlay};
+
+/// Contents of every file in a datapack, keyed by path relative to the pack root
+pub type DatapackFiles = BTreeMap<String, Vec<u8>>;
+
+////////////////////////////////////
+//------ Overlay Generation ------//
+////////////////////////////////////
+
+/// The root data and overlays which reproduce the data of each format a datapack supports
+#[derive(Debug, Clone, Default)]
+pub struct OverlayPlan {
+    pub root: DatapackFiles,
+    pub overlays: Vec<(Overlay, DatapackFiles)>,
+    pub diagnostics: Vec<Diagnostic>
+}
+
+/// Works out the smallest set of overlays which gives each format its own data.
+/// Files which are the same for every format stay in the root, and each run of formats sharing
+/// the same version of a file gets one overlay, shared with any other files which change over the same formats
+pub fn plan_overlays(format_files: &BTreeMap<DatapackFormat, DatapackFiles>) -> OverlayPlan {
+    let formats: Vec<(DatapackFormat, &DatapackFiles)> = format_files.iter()
+        .map(|(datapack_format, files)| (*datapack_format, files))
+        .collect();
+
+    let paths: BTreeSet<&String> = formats.iter()
+        .flat_map(|(_, files)| files.keys())
+        .collect();
+
+    let mut plan = OverlayPlan::default();
+    // Overlay contents keyed by the index of the first and last format they apply to
+    let mut overlay_files: BTreeMap<(usize, usize), DatapackFiles> = BTreeMap::new();
+
+    for path in paths {
+        let values: Vec<Option<&Vec<u8>>> = formats.iter()
+            .map(|(_, files)| files.get(path))
+            .collect();
+
+        let root = get_root_value(&formats, &values);
+
+        if let Some(root) = root {
+            plan.root.insert(path.clone(), root.clone());
+        }
+
+        for (index, (datapack_format, _)) in formats.iter().enumerate() {
+            if !datapack_format.supports_overlays() {
+                if values[index] != root {
+                    plan.diagnostics.push(Diagnostic::warning(DatapackError::Overlay(
+                        format!("File differs between formats from before overlays were added, so {} will load the root version", datapack_format).into()
+                    ).with_path(path)));
+                }
+            }
+            else if values[index].is_none() && root.is_some() {
+                plan.diagnostics.push(Diagnostic::warning(DatapackError::Overlay(
+                    format!("Overlays cannot remove files, so {} will still load this file", datapack_format).into()
+                ).with_path(path)));
+            }
+        }
+
+        // Formats from before overlays always load the root, so they are left out when covering formats with overlays
+        let overlay_values: Vec<Option<&Vec<u8>>> = formats.iter().zip(&values)
+            .map(|((datapack_format, _), value)| if datapack_format.supports_overlays() { *value } else { root })
+            .collect();
+
+        cover_formats(path, &overlay_values, 0, formats.len(), root, &mut overlay_files);
+    }
+
+    // Overlays later in the list replace earlier ones, so narrower overlays go last
+    let mut ranges: Vec<((usize, usize), DatapackFiles)> = overlay_files.into_iter().collect();
+    ranges.sort_by_key(|((start, end), _)| (std::cmp::Reverse(end - start), *start));
+
+    plan.overlays = ranges.into_iter()
+        .map(|((start, end), files)| {
+            let min_format = formats[start].0;
+            let max_format = formats[end].0;
+
+            let name = if start == end { format!("overlay_{}", min_format.format()) }
+                else { format!("overlay_{}_{}", min_format.format(), max_format.format()) };
+
+            (Overlay { name, min_format, max_format }, files)
+        })
+        .collect();
+
+    plan
+}
+
+/// Covers the formats from start up to end with overlays wherever they need a different version of the file than the
+/// one they would otherwise load. Each overlay spans from a format to the last one using the same version of the file,
+/// and any formats inside it which need something else get narrower overlays of their own
+fn cover_formats<'a>(
+    path: &str,
+    values: &[Option<&'a Vec<u8>>],
+    start: usize,
+    end: usize,
+    inherited: Option<&'a Vec<u8>>,
+    overlay_files: &mut BTreeMap<(usize, usize), DatapackFiles>
+) {
+    let mut index = start;
+
+    while index < end {
+        let Some(value) = values[index].filter(|value| Some(*value) != inherited) else {
+            index += 1;
+            continue
+        };
+
+        // An overlay cannot remove the file or bring back the version it replaced, so it has to stop before either
+        let limit = (index..end)
+            .find(|i| values[*i].is_none() || values[*i] == inherited)
+            .unwrap_or(end);
+        let last = (index..limit).rev()
+            .find(|i| values[*i] == Some(value))
+            .unwrap_or(index);
+
+        overlay_files.entry((index, last)).or_default().insert(String::from(path), value.clone());
+        cover_formats(path, values, index + 1, last, Some(value), overlay_files);
+
+        index = last + 1;
+    }
+}
+
+/// Picks the version of a file stored in the root. Formats from before overlays can only load the root,
+/// so the lowest format decides it when there is one. Otherwise the most common version is used,
+/// as long as every format has the file, since overlays cannot remove files
+fn get_root_value<'a>(formats: &[(DatapackFormat, &DatapackFiles)], values: &[Option<&'a Vec<u8>>]) -> Option<&'a Vec<u8>> {
+    if formats.first().is_some_and(|(datapack_format, _)| !datapack_format.supports_overlays()) {
+        return values[0]
+    }
+
+    if values.iter().any(|value| value.is_none()) {
+        return None
+    }
+
+    let mut counts: Vec<(&Vec<u8>, usize)> = Vec::new();
+
+    for value in values.iter().flatten() {
+        match counts.iter_mut().find(|(counted, _)| counted == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1))
+        }
+    }
+
+    // Ties go to the lowest format, as max_by_key returns the last maximum
+    counts.into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(value, _)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The files of each format, given as the version of each file the format has, if any
+    fn format_files(formats: &[u32], files: &[(&str, &[Option<&str>])]) -> BTreeMap<DatapackFormat, DatapackFiles> {
+        formats.iter().enumerate()
+            .map(|(index, format)| {
+                let format_files = files.iter()
+                    .filter_map(|(path, versions)| Some((String::from(*path), versions[index]?.as_bytes().to_vec())))
+                    .collect();
+                (DatapackFormat::new(*format), format_files)
+            })
+            .collect()
+    }
+
+    /// Each overlay as its name and the files it holds, along with each file's contents
+    fn overlays(plan: &OverlayPlan) -> Vec<(&str, Vec<(&str, &str)>)> {
+        plan.overlays.iter()
+            .map(|(overlay, files)| {
+                let files = files.iter().map(|(path, contents)| (path.as_str(), std::str::from_utf8(contents).unwrap())).collect();
+                (overlay.name.as_str(), files)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn root_holds_the_most_common_version() {
+        let plan = plan_overlays(&format_files(&[18, 26, 41], &[("x", &[Some("a"), Some("a"), Some("b")])]));
+        assert_eq!(plan.root["x"], b"a");
+        assert_eq!(overlays(&plan), [("overlay_41", vec![("x", "b")])]);
+
+        // Ties go to the lowest format
+        let plan = plan_overlays(&format_files(&[18, 26], &[("x", &[Some("b"), Some("a")])]));
+        assert_eq!(plan.root["x"], b"b");
+        assert_eq!(overlays(&plan), [("overlay_26", vec![("x", "a")])]);
+        assert!(plan.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn root_holds_the_version_of_formats_before_overlays() {
+        let plan = plan_overlays(&format_files(&[15, 18, 26], &[("x", &[Some("b"), Some("a"), Some("a")])]));
+        assert_eq!(plan.root["x"], b"b");
+        assert_eq!(overlays(&plan), [("overlay_18_26", vec![("x", "a")])]);
+        assert!(plan.diagnostics.is_empty());
+
+        // Formats before overlays which need something else, or newer formats without the file, cannot get it
+        let plan = plan_overlays(&format_files(&[12, 15, 18], &[("x", &[Some("a"), Some("b"), None])]));
+        assert_eq!(plan.root["x"], b"a");
+        assert!(plan.overlays.is_empty());
+        assert_eq!(plan.diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn files_missing_from_some_formats_stay_out_of_the_root() {
+        let plan = plan_overlays(&format_files(&[18, 26, 41], &[("x", &[None, Some("a"), Some("a")])]));
+        assert!(plan.root.is_empty());
+        assert_eq!(overlays(&plan), [("overlay_26_41", vec![("x", "a")])]);
+    }
+
+    #[test]
+    fn files_changing_over_the_same_formats_share_an_overlay() {
+        let plan = plan_overlays(&format_files(&[18, 26, 41, 48], &[
+            ("x", &[Some("a"), Some("a"), Some("b"), Some("b")]),
+            ("y", &[Some("c"), Some("c"), Some("d"), Some("d")]),
+            ("z", &[Some("e"), Some("e"), Some("e"), Some("e")])
+        ]));
+
+        assert_eq!(plan.root.len(), 3);
+        assert_eq!(overlays(&plan), [("overlay_41_48", vec![("x", "b"), ("y", "d")])]);
+    }
+
+    #[test]
+    fn narrower_overlays_come_after_the_ones_they_override() {
+        let plan = plan_overlays(&format_files(&[18, 26, 41, 48, 57], &[
+            ("x", &[Some("a"), Some("b"), Some("c"), Some("b"), Some("a")])
+        ]));
+
+        assert_eq!(plan.root["x"], b"a");
+        assert_eq!(overlays(&plan), [
+            ("overlay_26_48", vec![("x", "b")]),
+            ("overlay_41", vec![("x", "c")])
+        ]);
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::data::datapack::{DatapackError, DatapackFormat, Diagnostic};
+use crate::data::diff::{self, JsonKey};
+
+///////////////////////////////////
+//------ Vanilla Overrides ------//
+///////////////////////////////////
+
+/// The fields of a resource which differ from the vanilla resource it overrides. Each field keeps the vanilla value
+/// it was changed from, so the patch can be applied to newer vanilla data while noticing what changed upstream.
+/// Objects are patched key by key, and anything else, including arrays, as a whole
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourcePatch {
+    /// The format of the vanilla data the patch was made against
+    pub base_format: DatapackFormat,
+    #[serde(default)]
+    pub fields: Vec<FieldPatch>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldPatch {
+    pub field: String,
+    /// The vanilla value, or None if vanilla did not have the field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<Value>,
+    /// The overriding value, or None if the field was removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>
+}
+
+impl ResourcePatch {
+    /// Records how a resource differs from the vanilla resource it overrides
+    pub fn new(base_format: DatapackFormat, base: &Value, value: &Value) -> Self {
+        let mut fields = Vec::new();
+        diff_fields(&mut Vec::new(), Some(base), Some(value), &mut fields);
+
+        Self { base_format, fields }
+    }
+
+    pub fn is_empty(&self) -> bool { self.fields.is_empty() }
+
+    /// Applies the patch to a vanilla resource, which may be newer than the one the patch was made against.
+    /// Our value is kept for fields which also changed upstream, but they are reported so they can be looked at again
+    pub fn apply(&self, base: &Value) -> (Value, PatchReport) {
+        let mut value = base.clone();
+        let mut report = PatchReport::default();
+
+        for field_patch in &self.fields {
+            let Some(keys) = diff::parse_field(&field_patch.field) else {
+                report.conflicts.push(PatchIssue::new(field_patch, None, "the field could not be read"));
+                continue
+            };
+
+            let upstream = diff::get_json_field(base, &keys).cloned();
+
+            // Every object along the way has to still be there, or the value has nowhere to go
+            let parents = &keys[..keys.len().saturating_sub(1)];
+            if !matches!(diff::get_json_field(&value, parents), Some(Value::Object(_))) {
+                report.conflicts.push(PatchIssue::new(field_patch, upstream, "vanilla no longer has the object holding it"));
+                continue
+            }
+
+            if upstream != field_patch.base && upstream != field_patch.value {
+                let issue = match (&field_patch.base, &upstream) {
+                    (Some(_), None) => Some(PatchIssue::new(field_patch, upstream.clone(), "vanilla removed the field")),
+                    (None, Some(_)) => Some(PatchIssue::new(field_patch, upstream.clone(), "vanilla added the field as well")),
+                    _ => None
+                };
+
+                match issue {
+                    Some(issue) => report.conflicts.push(issue),
+                    None => report.upstream_changes.push(PatchIssue::new(field_patch, upstream.clone(), "vanilla changed the field"))
+                }
+            }
+
+            if !diff::set_json_field(&mut value, &keys, field_patch.value.clone()) {
+                report.conflicts.push(PatchIssue::new(field_patch, upstream, "the value could not be put in place"));
+            }
+        }
+
+        (value, report)
+    }
+}
+
+//------------//
+
+/// What needs another look after applying a patch to newer vanilla data
+#[derive(Debug, Clone, Default)]
+pub struct PatchReport {
+    /// Fields which could not be applied cleanly, such as ones vanilla removed
+    pub conflicts: Vec<PatchIssue>,
+    /// Fields vanilla changed which are overridden by the patch
+    pub upstream_changes: Vec<PatchIssue>
+}
+
+impl PatchReport {
+    pub fn is_empty(&self) -> bool { self.conflicts.is_empty() && self.upstream_changes.is_empty() }
+}
+
+#[derive(Debug, Clone)]
+pub struct PatchIssue {
+    pub field: String,
+    /// The vanilla value the patch was made against
+    pub base: Option<Value>,
+    /// The vanilla value the patch was applied to
+    pub upstream: Option<Value>,
+    pub ours: Option<Value>,
+    pub reason: &'static str
+}
+
+impl PatchIssue {
+    fn new(field_patch: &FieldPatch, upstream: Option<Value>, reason: &'static str) -> Self {
+        Self {
+            field: field_patch.field.clone(),
+            base: field_patch.base.clone(),
+            upstream,
+            ours: field_patch.value.clone(),
+            reason
+        }
+    }
+}
+
+impl Display for PatchIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let describe = |value: &Option<Value>| value.as_ref().map(|value| value.to_string()).unwrap_or_else(|| String::from("(missing)"));
+        write!(f, "{}: {}, was {}, now {}, ours {}", self.field, self.reason, describe(&self.base), describe(&self.upstream), describe(&self.ours))
+    }
+}
+
+//------------//
+
+/// Every resource loaded from a patch, and what came up when applying them
+#[derive(Debug, Clone, Default)]
+pub struct RebaseReport {
+    /// Keyed by resource path
+    pub resources: BTreeMap<String, PatchReport>
+}
+
+impl RebaseReport {
+    /// Checks whether any patch needs another look
+    pub fn has_issues(&self) -> bool {
+        self.resources.values().any(|report| !report.is_empty())
+    }
+
+    /// Describes every issue, one line each
+    pub fn lines(&self) -> Vec<String> {
+        self.resources.iter()
+            .flat_map(|(path, report)| {
+                let conflicts = report.conflicts.iter().map(move |issue| format!("Conflict in {} {}", path, issue));
+                let upstream_changes = report.upstream_changes.iter().map(move |issue| format!("Upstream change in {} {}", path, issue));
+                conflicts.chain(upstream_changes)
+            })
+            .collect()
+    }
+
+    /// Describes every issue as a diagnostic on the resource's path. Conflicts are errors, as the patch did not
+    /// apply cleanly, and upstream changes are warnings, as our value was kept over a newer vanilla value
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.resources.iter()
+            .flat_map(|(path, report)| {
+                let conflicts = report.conflicts.iter()
+                    .map(move |issue| Diagnostic::error(DatapackError::File(format!("Conflict in patched field {}", issue).into()).with_path(path)));
+                let upstream_changes = report.upstream_changes.iter()
+                    .map(move |issue| Diagnostic::warning(DatapackError::File(format!("Upstream change in patched field {}", issue).into()).with_path(path)));
+                conflicts.chain(upstream_changes)
+            })
+            .collect()
+    }
+}
+
+impl Display for RebaseReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for line in self.lines() {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+//------------//
+
+fn diff_fields(field: &mut Vec<JsonKey>, base: Option<&Value>, value: Option<&Value>, fields: &mut Vec<FieldPatch>) {
+    if base == value {
+        return
+    }
+
+    match (base, value) {
+        (Some(Value::Object(base_object)), Some(Value::Object(object))) => {
+            let keys: BTreeSet<&String> = base_object.keys().chain(object.keys()).collect();
+
+            for key in keys {
+                field.push(JsonKey::Key(key.clone()));
+                diff_fields(field, base_object.get(key), object.get(key), fields);
+                field.pop();
+            }
+        }
+        _ => fields.push(FieldPatch {
+            field: diff::format_field(field),
+            base: base.cloned(),
+            value: value.cloned()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn patch(base: Value, value: Value) -> ResourcePatch {
+        ResourcePatch::new(DatapackFormat::new(26), &base, &value)
+    }
+
+    #[test]
+    fn applies_to_unchanged_vanilla() {
+        let base = json!({"temperature": 0.8, "effects": {"sky_color": 1, "fog_color": 2}});
+        let ours = json!({"temperature": 0.5, "effects": {"sky_color": 3, "fog_color": 2}, "downfall": 0.1});
+
+        let (value, report) = patch(base.clone(), ours.clone()).apply(&base);
+        assert_eq!(value, ours);
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn reports_removed_parents() {
+        let base = json!({"effects": {"sky_color": 1}});
+        let ours = json!({"effects": {"sky_color": 3}});
+        let upstream = json!({"temperature": 0.8});
+
+        let (value, report) = patch(base, ours).apply(&upstream);
+        assert_eq!(value, upstream);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].field, "effects.sky_color");
+    }
+
+    #[test]
+    fn keeps_ours_over_upstream_changes() {
+        let base = json!({"temperature": 0.8, "downfall": 0.4});
+        let ours = json!({"temperature": 0.5, "downfall": 0.4});
+        let upstream = json!({"temperature": 0.9, "downfall": 0.6});
+
+        let (value, report) = patch(base, ours).apply(&upstream);
+        assert_eq!(value, json!({"temperature": 0.5, "downfall": 0.6}));
+        assert!(report.conflicts.is_empty());
+        assert_eq!(report.upstream_changes.len(), 1);
+        assert_eq!(report.upstream_changes[0].upstream, Some(json!(0.9)));
+    }
+
+    #[test]
+    fn reports_fields_added_on_both_sides() {
+        let base = json!({"temperature": 0.8});
+        let ours = json!({"temperature": 0.8, "downfall": 0.1});
+
+        let (value, report) = patch(base.clone(), ours).apply(&json!({"temperature": 0.8, "downfall": 0.2}));
+        assert_eq!(value["downfall"], json!(0.1));
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].reason, "vanilla added the field as well");
+
+        // Both sides adding the same value is not a conflict
+        let (_, report) = patch(base, json!({"temperature": 0.8, "downfall": 0.1})).apply(&json!({"temperature": 0.8, "downfall": 0.1}));
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn reports_removed_fields() {
+        let base = json!({"temperature": 0.8, "downfall": 0.4});
+        let ours = json!({"temperature": 0.8, "downfall": 0.1});
+
+        let (value, report) = patch(base, ours).apply(&json!({"temperature": 0.8}));
+        assert_eq!(value["downfall"], json!(0.1));
+        assert_eq!(report.conflicts[0].reason, "vanilla removed the field");
+    }
+}
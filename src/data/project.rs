@@ -0,0 +1,574 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use crate::data::biome::{BiomeData, SerializableBiomeData};
+use crate::data::datapack::{Datapack, DatapackError, DatapackFormat, DatapackImport, FileElement, Filter, Overlay, PassthroughFile,
+    SerializableDataElement, SerializableDataHolder, SerializableDatapack, SyncState};
+use crate::data::overlay::DatapackFiles;
+use crate::data::patch::{RebaseReport, ResourcePatch};
+use crate::data::util::{self, ResourceLocation, SerializableText};
+use crate::data::vanilla::VanillaData;
+
+pub const PROJECT_FILE: &str = "project.json";
+const PROJECT_FORMAT_VERSION: u32 = 1;
+
+// Modeled resources, one file per resource holding the data for every format
+const RESOURCE_DIRECTORY: &str = "resources";
+// Files which are not modeled, stored exactly as they are written to the datapack
+const FILE_DIRECTORY: &str = "files";
+// How the editor was left, such as which lists were collapsed. Kept apart from the datapack, so it never shows up as a change
+const EDITOR_FILE: &str = "editor.json";
+// A save is written here first and then moved into place, so that a failed save leaves the last one intact
+const SAVE_DIRECTORY: &str = ".saving";
+
+lazy_static! {
+    static ref PROJECT_BIOME_REG: Regex = Regex::new(r"^data/([a-z0-9_.-]+)/worldgen/biome/([a-z0-9/_.-]+)\.json$").unwrap();
+}
+
+/////////////////////////////////
+//------ Project Storage ------//
+/////////////////////////////////
+
+/// A datapack saved in Gaia's own format. Every resource is written to its own file, with stable formatting,
+/// so that projects can be kept in version control. Zips are built from a project rather than edited directly
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub datapack: Datapack,
+    /// The editor's own state, which is saved and loaded as it is
+    pub editor_state: Option<Value>,
+    // Biomes overriding this vanilla data are stored as patches against it
+    vanilla: Option<Arc<VanillaData>>
+}
+
+impl Project {
+    pub fn new(datapack: Datapack) -> Self {
+        Self { datapack, editor_state: None, vanilla: None }
+    }
+
+    pub fn with_editor_state(mut self, editor_state: Option<Value>) -> Self {
+        self.editor_state = editor_state;
+        self
+    }
+
+    /// Stores the root data of biomes which override vanilla as patches holding only the changed fields,
+    /// so that they can be applied again to newer vanilla data. See [`Project::load_rebased`]
+    pub fn with_vanilla(mut self, vanilla: Arc<VanillaData>) -> Self {
+        self.vanilla = Some(vanilla);
+        self
+    }
+
+    /// Checks whether biomes overriding vanilla are stored as patches
+    pub fn stores_patches(&self) -> bool { self.vanilla.is_some() }
+
+    /// Saves the project into a directory, replacing any resources left from an earlier save. Everything is written
+    /// to a temporary directory first and then moved into place, so a save which fails part way leaves the last one intact
+    pub fn save(&self, directory: &str) -> Result<(), DatapackError> {
+        let mut files = self.to_files()?;
+        if let Some(editor_state) = &self.editor_state {
+            files.insert(String::from(EDITOR_FILE), to_stable_json(editor_state)?.into_bytes());
+        }
+
+        let root = Path::new(directory);
+        let staging = root.join(SAVE_DIRECTORY);
+        remove_directory(&staging)?;
+
+        for (path, contents) in &files {
+            write_file(&staging.join(path), contents)?;
+        }
+
+        // The earlier save is moved aside rather than deleted, and moved back if the new one cannot be put in place
+        let previous = staging.join("previous");
+        fs::create_dir_all(&previous)?;
+
+        let moved = [RESOURCE_DIRECTORY, FILE_DIRECTORY].into_iter().try_for_each(|stored_directory| {
+            if root.join(stored_directory).exists() {
+                fs::rename(root.join(stored_directory), previous.join(stored_directory))?;
+            }
+            if staging.join(stored_directory).exists() {
+                fs::rename(staging.join(stored_directory), root.join(stored_directory))?;
+            }
+            Ok::<_, io::Error>(())
+        });
+
+        if let Err(error) = moved {
+            for stored_directory in [RESOURCE_DIRECTORY, FILE_DIRECTORY] {
+                if previous.join(stored_directory).exists() {
+                    let _ = remove_directory(&root.join(stored_directory));
+                    let _ = fs::rename(previous.join(stored_directory), root.join(stored_directory));
+                }
+            }
+            return Err(error.into())
+        }
+
+        // Single files are replaced in one step, with the project file last as it is what marks a directory as a project
+        if !files.contains_key(EDITOR_FILE) && root.join(EDITOR_FILE).exists() {
+            fs::remove_file(root.join(EDITOR_FILE))?;
+        }
+        for stored_file in [EDITOR_FILE, PROJECT_FILE] {
+            if files.contains_key(stored_file) {
+                fs::rename(staging.join(stored_file), root.join(stored_file))?;
+            }
+        }
+
+        remove_directory(&staging)
+    }
+
+    /// Loads a project saved with [`Project::save`]. Projects storing patches need the vanilla data, see [`Project::load_rebased`]
+    pub fn load(directory: &str) -> Result<Self, DatapackError> {
+        let project = Self::from_files(Self::read_project(directory)?, None, &mut RebaseReport::default())?;
+        Ok(project.with_editor_state(read_editor_state(directory)))
+    }
+
+    /// Loads a project, applying any patches to the vanilla data for its root format. The vanilla data may be newer
+    /// than the data the patches were made against, so anything which no longer applies cleanly is reported
+    pub fn load_rebased(directory: &str, vanilla: Arc<VanillaData>) -> Result<(Self, RebaseReport), DatapackError> {
+        let mut report = RebaseReport::default();
+        let mut project = Self::from_files(Self::read_project(directory)?, Some(&vanilla), &mut report)?
+            .with_editor_state(read_editor_state(directory));
+
+        // Projects keep storing patches once they have them
+        if !report.resources.is_empty() {
+            project.vanilla = Some(vanilla);
+        }
+
+        Ok((project, report))
+    }
+
+    fn read_project(directory: &str) -> Result<DatapackFiles, DatapackError> {
+        let root = Path::new(directory);
+
+        // Only the project's own files are read, so other files such as version control data can live alongside them
+        let mut files = DatapackFiles::new();
+        files.insert(String::from(PROJECT_FILE), fs::read(root.join(PROJECT_FILE))?);
+
+        for stored_directory in [RESOURCE_DIRECTORY, FILE_DIRECTORY] {
+            for (path, contents) in read_files(&root.join(stored_directory))? {
+                files.insert(format!("{}/{}", stored_directory, path), contents);
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Checks whether a project has been saved in a directory
+    pub fn exists(directory: &str) -> bool {
+        Path::new(directory).join(PROJECT_FILE).is_file()
+    }
+
+    /// Describes what differs in this project from an earlier version of it, one line per change
+    pub fn changes_since(&self, earlier: &Project) -> Result<Vec<String>, DatapackError> {
+        let files = self.to_files()?;
+        let earlier_files = earlier.to_files()?;
+        let mut changes = Vec::new();
+
+        // Pack info is one file, so it is compared field by field to say what actually changed
+        let info: Map<String, Value> = serde_json::from_slice(&files[PROJECT_FILE])?;
+        let earlier_info: Map<String, Value> = serde_json::from_slice(&earlier_files[PROJECT_FILE])?;
+
+        for (key, value) in &info {
+            if earlier_info.get(key) != Some(value) {
+                changes.push(format!("Changed {}", key));
+            }
+        }
+
+        let paths: BTreeSet<&String> = files.keys().chain(earlier_files.keys())
+            .filter(|path| *path != PROJECT_FILE)
+            .collect();
+
+        for path in paths {
+            let change = match (files.get(path), earlier_files.get(path)) {
+                (Some(_), None) => "Added",
+                (None, Some(_)) => "Removed",
+                (Some(contents), Some(earlier_contents)) if contents != earlier_contents => "Changed",
+                _ => continue
+            };
+
+            changes.push(format!("{} {}", change, describe_path(path)));
+        }
+
+        Ok(changes)
+    }
+
+    /// Converts the project into a datapack which can be exported or built
+    pub fn to_serializable(&self) -> SerializableDatapack {
+        SerializableDatapack::from(self.datapack.clone())
+    }
+
+    /// Gets the contents of every file in the project, keyed by path relative to the project directory
+    fn to_files(&self) -> Result<DatapackFiles, DatapackError> {
+        let mut files = DatapackFiles::new();
+
+        let datapack = &self.datapack;
+        let project_info = SerializableProjectInfo {
+            format_version: PROJECT_FORMAT_VERSION,
+            name: String::from(datapack.name()),
+            description: SerializableText::from(datapack.description().clone()),
+            root_format: datapack.root_format(),
+            min_format: datapack.min_format(),
+            max_format: datapack.max_format(),
+            overlays: datapack.overlays().clone(),
+            filters: datapack.filters().clone()
+        };
+        files.insert(String::from(PROJECT_FILE), to_stable_json(&serde_json::to_value(project_info)?)?.into_bytes());
+
+        let vanilla = self.vanilla.as_ref().and_then(|vanilla| vanilla.get(datapack.root_format()));
+
+        for (resource_location, biome) in datapack.biomes() {
+            let data = biome.data();
+            let vanilla_biome = vanilla.and_then(|vanilla| Some((vanilla.pack_format(), vanilla.biome(resource_location)?.root_data()?)));
+
+            let (root, patch) = match (data.root_data(), vanilla_biome) {
+                (Some(biome), Some((vanilla_format, vanilla_biome))) => {
+                    (None, Some(ResourcePatch::new(vanilla_format, &biome_to_value(vanilla_biome)?, &biome_to_value(biome)?)))
+                }
+                (root_data, _) => (root_data.map(biome_to_value).transpose()?, None)
+            };
+
+            let resource = SerializableProjectResource {
+                sync: biome.sync_state(),
+                root,
+                patch,
+                overlays: data.get_overlay_data().into_iter()
+                    .map(|(overlay, biome)| Ok((String::from(overlay), biome_to_value(biome)?)))
+                    .collect::<Result<_, DatapackError>>()?
+            };
+
+            let path = format!("{}/{}", RESOURCE_DIRECTORY, SerializableBiomeData::get_file_path(resource_location));
+            files.insert(path, to_stable_json(&serde_json::to_value(resource)?)?.into_bytes());
+        }
+
+        for (path, file) in datapack.passthrough() {
+            if let Some(root_file) = file.root_data() {
+                files.insert(format!("{}/root/{}", FILE_DIRECTORY, path), root_file.contents().to_vec());
+            }
+
+            for (overlay, overlay_file) in file.get_overlay_data() {
+                files.insert(format!("{}/overlay/{}/{}", FILE_DIRECTORY, overlay, path), overlay_file.contents().to_vec());
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn from_files(mut files: DatapackFiles, vanilla: Option<&VanillaData>, report: &mut RebaseReport) -> Result<Self, DatapackError> {
+        let project_info = files.remove(PROJECT_FILE)
+            .ok_or_else(|| DatapackError::File(format!("Missing {}", PROJECT_FILE).into()))?;
+        let project_info: SerializableProjectInfo = serde_json::from_slice(&project_info)
+            .map_err(|error| DatapackError::from(error).with_path(PROJECT_FILE))?;
+
+        if project_info.format_version > PROJECT_FORMAT_VERSION {
+            return Err(DatapackError::Format(format!(
+                "Project format {} is newer than this version of Gaia supports", project_info.format_version).into()))
+        }
+
+        let description = util::Text::try_from(project_info.description)
+            .map_err(|error| error.with_path(PROJECT_FILE))?
+            .into();
+
+        let root_format = project_info.root_format;
+        let vanilla = vanilla.and_then(|vanilla| vanilla.get(root_format));
+
+        let mut datapack = Datapack::new(
+            project_info.name,
+            description,
+            project_info.root_format,
+            project_info.min_format,
+            project_info.max_format,
+            project_info.overlays.clone(),
+            project_info.filters
+        );
+        let overlays = project_info.overlays;
+
+        let mut passthrough: BTreeMap<String, StoredFile> = BTreeMap::new();
+
+        for (path, contents) in files {
+            if let Some(resource_path) = path.strip_prefix(RESOURCE_DIRECTORY).and_then(|path| path.strip_prefix('/')) {
+                let Some(cap) = PROJECT_BIOME_REG.captures(resource_path) else {
+                    return Err(DatapackError::File(format!("Unknown resource type in {}", path).into()))
+                };
+
+                let resource_location = ResourceLocation::new(String::from(&cap[1]), String::from(&cap[2]));
+                let mut load_biome = || -> Result<BiomeData, DatapackError> {
+                    let resource: SerializableProjectResource = serde_json::from_slice(&contents)?;
+
+                    let root_data = match resource.patch {
+                        Some(patch) => {
+                            let vanilla = vanilla.ok_or_else(|| DatapackError::File(format!(
+                                "The biome is stored as a patch against vanilla data, but no vanilla data is loaded for format {}", root_format).into()))?;
+                            let vanilla_biome = vanilla.biome(&resource_location).and_then(|data_holder| data_holder.root_data())
+                                .ok_or_else(|| DatapackError::File(format!(
+                                    "The biome is stored as a patch, but vanilla format {} does not have it", vanilla.pack_format()).into()))?;
+
+                            let (value, patch_report) = patch.apply(&biome_to_value(vanilla_biome)?);
+                            report.resources.insert(SerializableBiomeData::get_file_path(&resource_location), patch_report);
+                            Some(biome_from_value(value)?)
+                        }
+                        None => resource.root.map(biome_from_value).transpose()?
+                    };
+                    let overlay_data = resource.overlays.into_iter()
+                        .map(|(name, value)| {
+                            let overlay = get_overlay(&overlays, &name)?;
+                            Ok((overlay, biome_from_value(value)?))
+                        })
+                        .collect::<Result<Vec<_>, DatapackError>>()?;
+
+                    let data = SerializableDataHolder::from_parts(root_data, overlay_data, Some(resource_location.clone()));
+                    Ok(BiomeData::new(data, resource.sync))
+                };
+
+                let biome = load_biome().map_err(|error| error.with_path(&path))?;
+                datapack.biomes_mut().insert(resource_location, biome);
+            }
+            else if let Some(root_path) = path.strip_prefix(FILE_DIRECTORY).and_then(|path| path.strip_prefix("/root/")) {
+                passthrough.entry(String::from(root_path)).or_default().0 = Some(PassthroughFile::new(contents));
+            }
+            else if let Some((overlay_name, overlay_path)) = path.strip_prefix(FILE_DIRECTORY)
+                .and_then(|path| path.strip_prefix("/overlay/"))
+                .and_then(|path| path.split_once('/'))
+            {
+                let overlay = get_overlay(&overlays, overlay_name).map_err(|error| error.with_path(&path))?;
+                passthrough.entry(String::from(overlay_path)).or_default().1.push((overlay, PassthroughFile::new(contents)));
+            }
+            else {
+                return Err(DatapackError::File(format!("Unexpected project file {}", path).into()))
+            }
+        }
+
+        let passthrough: Vec<(String, SerializableDataHolder<PassthroughFile>)> = passthrough.into_iter()
+            .map(|(path, (root_data, overlay_data))| (path, SerializableDataHolder::from_parts(root_data, overlay_data, None)))
+            .collect();
+        datapack.passthrough_mut().extend(passthrough);
+
+        Ok(Self::new(datapack))
+    }
+}
+
+/// Loads a datapack from a zip, a datapack folder or a project folder.
+/// Projects storing patches are applied to the given vanilla data, and anything which did not apply cleanly is diagnosed
+pub fn load_datapack(path: &str, vanilla: Option<Arc<VanillaData>>) -> Result<DatapackImport, DatapackError> {
+    if Project::exists(path) {
+        let (project, report) = match vanilla {
+            Some(vanilla) => Project::load_rebased(path, vanilla)?,
+            None => (Project::load(path)?, RebaseReport::default())
+        };
+
+        let mut diagnostics = report.diagnostics();
+        diagnostics.extend(project.datapack.validate());
+        return Ok(DatapackImport { datapack: project.to_serializable(), diagnostics })
+    }
+
+    if Path::new(path).is_dir() { SerializableDatapack::from_directory(path) }
+        else { SerializableDatapack::from_zip(path) }
+}
+
+//------------//
+
+// A passthrough file's root and overlay contents, as read from the project before being put into a data holder
+type StoredFile<'a> = (Option<PassthroughFile>, Vec<(&'a Overlay, PassthroughFile)>);
+
+fn get_overlay<'a>(overlays: &'a [Overlay], name: &str) -> Result<&'a Overlay, DatapackError> {
+    overlays.iter()
+        .find(|overlay| overlay.name == name)
+        .ok_or_else(|| DatapackError::Overlay(format!("Overlay {} is not declared in {}", name, PROJECT_FILE).into()))
+}
+
+/// Turns a project file path back into the datapack path it is built into
+fn describe_path(path: &str) -> String {
+    let Some(path) = path.strip_prefix(RESOURCE_DIRECTORY).or_else(|| path.strip_prefix(FILE_DIRECTORY)) else { return String::from(path) };
+    let path = path.trim_start_matches('/');
+
+    if let Some(path) = path.strip_prefix("root/") {
+        return String::from(path)
+    }
+
+    match path.strip_prefix("overlay/").and_then(|path| path.split_once('/')) {
+        Some((overlay, path)) => format!("{} in overlay {}", path, overlay),
+        None => String::from(path)
+    }
+}
+
+fn biome_to_value(biome: &SerializableBiomeData) -> Result<Value, DatapackError> {
+    Ok(serde_json::from_str(&biome.serialize()?)?)
+}
+
+fn biome_from_value(value: Value) -> Result<SerializableBiomeData, DatapackError> {
+    Ok(*SerializableBiomeData::deserialize(value.to_string())?)
+}
+
+/// Formats json with sorted keys and a trailing newline, so that saving unchanged data gives the same file.
+/// Objects keep their keys in insertion order, so they are sorted here rather than relying on how they were built
+fn to_stable_json(value: &Value) -> Result<String, DatapackError> {
+    Ok(format!("{}\n", serde_json::to_string_pretty(&sort_keys(value))?))
+}
+
+fn sort_keys(value: &Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<(&String, &Value)> = object.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            Value::Object(entries.into_iter().map(|(key, value)| (key.clone(), sort_keys(value))).collect())
+        }
+        Value::Array(values) => Value::Array(values.iter().map(sort_keys).collect()),
+        value => value.clone()
+    }
+}
+
+/// Reads the editor state saved with a project. It is not part of the datapack, so a missing or unreadable file is left out
+fn read_editor_state(directory: &str) -> Option<Value> {
+    let contents = fs::read(Path::new(directory).join(EDITOR_FILE)).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+fn remove_directory(path: &Path) -> Result<(), DatapackError> {
+    match fs::remove_dir_all(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(DatapackError::from(error).with_path(&path.to_string_lossy())),
+        _ => Ok(())
+    }
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), DatapackError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, contents)
+        .map_err(|error| DatapackError::from(error).with_path(&path.to_string_lossy()))
+}
+
+/// Reads every file below a directory, keyed by path relative to it and separated by '/'
+fn read_files(directory: &Path) -> Result<BTreeMap<String, Vec<u8>>, DatapackError> {
+    let mut files = BTreeMap::new();
+
+    if !directory.exists() {
+        return Ok(files)
+    }
+
+    let mut filepaths = Vec::new();
+    SerializableDatapack::collect_files(directory, &mut filepaths)?;
+
+    for filepath in filepaths {
+        let Ok(relative_path) = filepath.strip_prefix(directory) else { continue };
+        let name = relative_path.components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        files.insert(name, fs::read(&filepath)?);
+    }
+
+    Ok(files)
+}
+
+///////////////////////////////////////////////
+//------ Project Storage Serialization ------//
+///////////////////////////////////////////////
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SerializableProjectInfo {
+    format_version: u32,
+    name: String,
+    description: SerializableText,
+    root_format: DatapackFormat,
+    min_format: DatapackFormat,
+    max_format: DatapackFormat,
+    #[serde(default)]
+    overlays: Vec<Overlay>,
+    #[serde(default)]
+    filters: Vec<Filter>
+}
+
+/// A modeled resource, holding its data for the root and each overlay
+#[derive(Debug, Serialize, Deserialize)]
+struct SerializableProjectResource {
+    #[serde(default)]
+    sync: SyncState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    root: Option<Value>,
+    // Replaces the root data for biomes overriding vanilla, when the project stores patches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    patch: Option<ResourcePatch>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    overlays: BTreeMap<String, Value>
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::data::test_util;
+    use super::*;
+
+    fn datapack(biomes: &[&str]) -> Datapack {
+        let pack_info = test_util::pack_info(26, None, &[]);
+        let biome = test_util::biome(0.8);
+        let paths: Vec<String> = biomes.iter().map(|id| format!("data/test/worldgen/biome/{}.json", id)).collect();
+
+        let mut contents = vec![("pack.mcmeta", pack_info.as_str())];
+        contents.extend(paths.iter().map(|path| (path.as_str(), biome.as_str())));
+        Datapack::try_from(test_util::import(&contents).datapack).unwrap()
+    }
+
+    #[test]
+    fn stored_json_has_sorted_keys() {
+        let value = json!({"b": 1, "a": [{"d": 1, "c": 2}]});
+        assert_eq!(to_stable_json(&value).unwrap(), "{\n  \"a\": [\n    {\n      \"c\": 2,\n      \"d\": 1\n    }\n  ],\n  \"b\": 1\n}\n");
+
+        // Biomes are written with their modeled fields first, which sorting turns into the same order as any other file
+        let files = Project::new(datapack(&["a"])).to_files().unwrap();
+        let biome = String::from_utf8(files[&format!("{}/data/test/worldgen/biome/a.json", RESOURCE_DIRECTORY)].clone()).unwrap();
+        let value: Value = serde_json::from_str(&biome).unwrap();
+        assert_eq!(biome, to_stable_json(&sort_keys(&value)).unwrap());
+        assert!(biome.find("\"carvers\"").unwrap() < biome.find("\"temperature\"").unwrap());
+    }
+
+    #[test]
+    fn saving_replaces_the_earlier_save_and_keeps_other_files() {
+        let directory = std::env::temp_dir().join(format!("gaia_project_test_{}", std::process::id()));
+        let root = directory.to_str().unwrap();
+
+        Project::new(datapack(&["a", "b"])).with_editor_state(Some(json!({"collapsed": [true]}))).save(root).unwrap();
+        fs::write(directory.join("notes.txt"), "notes").unwrap();
+
+        Project::new(datapack(&["a"])).save(root).unwrap();
+
+        assert!(directory.join(RESOURCE_DIRECTORY).join("data/test/worldgen/biome/a.json").is_file());
+        assert!(!directory.join(RESOURCE_DIRECTORY).join("data/test/worldgen/biome/b.json").exists());
+        assert!(!directory.join(SAVE_DIRECTORY).exists());
+        assert!(!directory.join(EDITOR_FILE).exists());
+        assert!(directory.join("notes.txt").is_file());
+
+        let project = Project::load(root).unwrap();
+        assert_eq!(project.datapack.biomes().len(), 1);
+        assert_eq!(project.editor_state, None);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn editor_state_and_sync_state_are_kept() {
+        let directory = std::env::temp_dir().join(format!("gaia_project_state_test_{}", std::process::id()));
+        let root = directory.to_str().unwrap();
+
+        let mut datapack = datapack(&["a"]);
+        let location = ResourceLocation::new(String::from("test"), String::from("a"));
+        datapack.biomes_mut().get_mut(&location).unwrap().set_sync_state(SyncState::Manual);
+
+        let editor_state = json!({"pack_info": {"filters": {"collapsed": [true, false]}}});
+        let project = Project::new(datapack).with_editor_state(Some(editor_state.clone()));
+        project.save(root).unwrap();
+
+        let loaded = Project::load(root).unwrap();
+        assert_eq!(loaded.editor_state, Some(editor_state));
+        assert_eq!(loaded.datapack.biomes()[&location].sync_state(), SyncState::Manual);
+
+        // The editor state is not part of the datapack
+        assert!(loaded.changes_since(&Project::new(loaded.datapack.clone())).unwrap().is_empty());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,507 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use serde_json::{Map, Value};
+use crate::data::biome::SerializableBiomeData;
+use crate::data::datapack::{DirectoryLayout, FileElement, SerializableDataElement, SerializableDatapack};
+use crate::data::diff::{self, JsonKey};
+use crate::data::util::{ResourceLocation, DEFAULT_NAMESPACE};
+
+const TAG_DIRECTORY: &str = "tags";
+// Structure templates are nbt files rather than json, and vanilla's are built into the game instead of its data.
+// Registries are keyed by their plural directory, see ResourceKey::from_path
+const TEMPLATE_DIRECTORY: &str = "structures";
+// Registries whose vanilla entries are not part of the vanilla data, so references to them cannot be checked
+const BUILT_IN_REGISTRIES: &[&str] = &[TEMPLATE_DIRECTORY];
+
+// Every field known to hold references. Schemas are registries, or shapes which are only ever written inline.
+// Fields for a specific type replace the general field with the same path
+const REFERENCE_FIELDS: &[ReferenceField] = &[
+    ReferenceField::new("worldgen/biome", "features", Target::Registry("worldgen/placed_feature")),
+    ReferenceField::new("worldgen/biome", "carvers.*", Target::Registry("worldgen/configured_carver")),
+
+    ReferenceField::new("worldgen/placed_feature", "feature", Target::Registry("worldgen/configured_feature")),
+
+    ReferenceField::typed("worldgen/configured_feature", "random_selector", "config.features[]", Target::Inline("weighted_placed_feature")),
+    ReferenceField::typed("worldgen/configured_feature", "random_selector", "config.default", Target::Registry("worldgen/placed_feature")),
+    ReferenceField::typed("worldgen/configured_feature", "simple_random_selector", "config.features", Target::Registry("worldgen/placed_feature")),
+    ReferenceField::typed("worldgen/configured_feature", "random_boolean_selector", "config.feature_true", Target::Registry("worldgen/placed_feature")),
+    ReferenceField::typed("worldgen/configured_feature", "random_boolean_selector", "config.feature_false", Target::Registry("worldgen/placed_feature")),
+    ReferenceField::typed("worldgen/configured_feature", "random_patch", "config.feature", Target::Registry("worldgen/placed_feature")),
+    ReferenceField::typed("worldgen/configured_feature", "flower", "config.feature", Target::Registry("worldgen/placed_feature")),
+    ReferenceField::typed("worldgen/configured_feature", "no_bonemeal_flower", "config.feature", Target::Registry("worldgen/placed_feature")),
+    ReferenceField::typed("worldgen/configured_feature", "root_system", "config.feature", Target::Registry("worldgen/placed_feature")),
+    ReferenceField::typed("worldgen/configured_feature", "vegetation_patch", "config.vegetation_feature", Target::Registry("worldgen/placed_feature")),
+    ReferenceField::typed("worldgen/configured_feature", "waterlogged_vegetation_patch", "config.vegetation_feature", Target::Registry("worldgen/placed_feature")),
+    ReferenceField::new("weighted_placed_feature", "feature", Target::Registry("worldgen/placed_feature")),
+
+    ReferenceField::new("worldgen/structure_set", "structures[].structure", Target::Registry("worldgen/structure")),
+    ReferenceField::new("worldgen/structure_set", "placement.exclusion_zone.other_set", Target::Registry("worldgen/structure_set")),
+    ReferenceField::new("worldgen/structure", "biomes", Target::Registry("worldgen/biome")),
+    ReferenceField::new("worldgen/structure", "start_pool", Target::Registry("worldgen/template_pool")),
+    ReferenceField::new("worldgen/template_pool", "fallback", Target::Registry("worldgen/template_pool")),
+    ReferenceField::new("worldgen/template_pool", "elements[].element", Target::Inline("pool_element")),
+    ReferenceField::new("pool_element", "location", Target::Registry(TEMPLATE_DIRECTORY)),
+    ReferenceField::new("pool_element", "processors", Target::Registry("worldgen/processor_list")),
+    ReferenceField::new("pool_element", "feature", Target::Registry("worldgen/placed_feature")),
+    ReferenceField::new("pool_element", "elements[]", Target::Inline("pool_element")),
+
+    ReferenceField::new("dimension", "type", Target::Registry("dimension_type")),
+    ReferenceField::new("dimension", "generator.settings", Target::Registry("worldgen/noise_settings")),
+    ReferenceField::new("dimension", "generator.biome_source", Target::Inline("biome_source")),
+    ReferenceField::typed("biome_source", "fixed", "biome", Target::Registry("worldgen/biome")),
+    ReferenceField::typed("biome_source", "checkerboard", "biomes", Target::Registry("worldgen/biome")),
+    ReferenceField::typed("biome_source", "multi_noise", "biomes[].biome", Target::Registry("worldgen/biome")),
+    ReferenceField::typed("biome_source", "multi_noise", "preset", Target::Registry("worldgen/multi_noise_biome_source_parameter_list")),
+    ReferenceField::new("worldgen/world_preset", "dimensions.*", Target::Registry("dimension")),
+    ReferenceField::new("worldgen/flat_level_generator_preset", "settings.biome", Target::Registry("worldgen/biome")),
+    ReferenceField::new("worldgen/flat_level_generator_preset", "settings.structure_overrides", Target::Registry("worldgen/structure_set")),
+
+    ReferenceField::new("worldgen/noise_settings", "noise_router.*", Target::Registry("worldgen/density_function")),
+    ReferenceField::new("worldgen/density_function", "argument", Target::Registry("worldgen/density_function")),
+    ReferenceField::typed("worldgen/density_function", "shift_a", "argument", Target::Registry("worldgen/noise")),
+    ReferenceField::typed("worldgen/density_function", "shift_b", "argument", Target::Registry("worldgen/noise")),
+    ReferenceField::typed("worldgen/density_function", "shift", "argument", Target::Registry("worldgen/noise")),
+    ReferenceField::new("worldgen/density_function", "argument1", Target::Registry("worldgen/density_function")),
+    ReferenceField::new("worldgen/density_function", "argument2", Target::Registry("worldgen/density_function")),
+    ReferenceField::new("worldgen/density_function", "input", Target::Registry("worldgen/density_function")),
+    ReferenceField::new("worldgen/density_function", "when_in_range", Target::Registry("worldgen/density_function")),
+    ReferenceField::new("worldgen/density_function", "when_out_of_range", Target::Registry("worldgen/density_function")),
+    ReferenceField::new("worldgen/density_function", "shift_x", Target::Registry("worldgen/density_function")),
+    ReferenceField::new("worldgen/density_function", "shift_y", Target::Registry("worldgen/density_function")),
+    ReferenceField::new("worldgen/density_function", "shift_z", Target::Registry("worldgen/density_function")),
+    ReferenceField::new("worldgen/density_function", "noise", Target::Registry("worldgen/noise")),
+    ReferenceField::new("worldgen/density_function", "spline", Target::Inline("spline")),
+    ReferenceField::new("spline", "coordinate", Target::Registry("worldgen/density_function")),
+    ReferenceField::new("spline", "points[].value", Target::Inline("spline"))
+];
+
+//////////////////////////////////
+//------ Cross References ------//
+//////////////////////////////////
+
+/// A resource of any registry, such as worldgen/placed_feature or tags/worldgen/biome
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ResourceKey {
+    pub registry: String,
+    pub location: ResourceLocation
+}
+
+impl ResourceKey {
+    pub fn new(registry: &str, location: ResourceLocation) -> Self {
+        Self { registry: String::from(registry), location }
+    }
+
+    /// Works out the resource stored at a data file path, such as data/minecraft/worldgen/biome/plains.json
+    /// Files stored with the singular directory names used from 1.21, such as data/test/structure/house.nbt, get
+    /// the same key as the plural directory, so that both layouts refer to the same resources
+    pub fn from_path(path: &str) -> Option<Self> {
+        let plural_path;
+        let path = match DirectoryLayout::of_path(path) {
+            Some((DirectoryLayout::Singular, plural)) => {
+                plural_path = plural;
+                plural_path.as_str()
+            }
+            _ => path
+        };
+
+        let registry = diff::get_registry(path);
+        if registry.is_empty() {
+            return None
+        }
+
+        let (namespace, path) = path.strip_prefix("data/")?.split_once('/')?;
+        let id = path.strip_prefix(&registry)?.strip_prefix('/')?;
+        let id = id.rsplit_once('.').map_or(id, |(id, _)| id);
+
+        Some(Self::new(&registry, ResourceLocation::new(String::from(namespace), String::from(id))))
+    }
+
+    /// Reads a reference as a resource file would write it, such as minecraft:plains, or #minecraft:is_ocean for a tag
+    pub fn parse(registry: &str, reference: &str) -> Option<Self> {
+        parse_reference(registry, reference)
+    }
+
+    /// Checks whether the resource is part of vanilla, but built into the game instead of its data
+    pub fn is_built_in(&self) -> bool {
+        self.location.namespace() == DEFAULT_NAMESPACE && BUILT_IN_REGISTRIES.contains(&self.registry.as_str())
+    }
+
+    pub fn is_tag(&self) -> bool {
+        self.registry.strip_prefix(TAG_DIRECTORY).is_some_and(|registry| registry.starts_with('/'))
+    }
+}
+
+impl Display for ResourceKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}{}", self.registry, if self.is_tag() { "#" } else { "" }, self.location)
+    }
+}
+
+//------------//
+
+/// A field of one resource which refers to another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub source: ResourceKey,
+    /// The overlay holding the referring copy of the source, or None for the root data
+    pub overlay: Option<String>,
+    /// The field holding the reference, written the same way as in diffs
+    pub field: String,
+    pub target: ResourceKey,
+    /// Tag entries can be marked as optional, in which case a missing target is not an error
+    pub required: bool
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} -> {}", self.source, self.field, self.target)?;
+        if let Some(overlay) = &self.overlay {
+            write!(f, " (overlay {})", overlay)?;
+        }
+
+        Ok(())
+    }
+}
+
+//------------//
+
+/// Every reference between the resources of a datapack, looked up from either end
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceIndex {
+    // The path of each resource defined in the datapack
+    definitions: BTreeMap<ResourceKey, String>,
+    references: Vec<Reference>,
+    by_source: BTreeMap<ResourceKey, Vec<usize>>,
+    by_target: BTreeMap<ResourceKey, Vec<usize>>
+}
+
+impl ReferenceIndex {
+    /// Finds every reference in every stored copy of each resource
+    pub fn new(datapack: &SerializableDatapack) -> Self {
+        let mut index = Self::default();
+
+        for (resource_location, data_holder) in datapack.biomes() {
+            let path = SerializableBiomeData::get_file_path(resource_location);
+            let copies = data_holder.root_data().map(|biome| (None, biome)).into_iter()
+                .chain(data_holder.get_overlay_data().into_iter().map(|(overlay, biome)| (Some(overlay), biome)));
+
+            for (overlay, biome) in copies {
+                if let Some(value) = biome.serialize().ok().and_then(|json| serde_json::from_str(&json).ok()) {
+                    index.add_resource(&path, overlay, &value);
+                }
+            }
+        }
+
+        for (path, data_holder) in datapack.passthrough_files() {
+            if !path.ends_with(".json") {
+                // Files which are not json, such as structure templates, hold no references but can still be referred to
+                if let Some(key) = ResourceKey::from_path(path) {
+                    index.definitions.insert(key, path.clone());
+                }
+                continue
+            }
+
+            let copies = data_holder.root_data().map(|file| (None, file)).into_iter()
+                .chain(data_holder.get_overlay_data().into_iter().map(|(overlay, file)| (Some(overlay), file)));
+
+            for (overlay, file) in copies {
+                if let Ok(value) = serde_json::from_slice(file.contents()) {
+                    index.add_resource(path, overlay, &value);
+                }
+            }
+        }
+
+        for (position, reference) in index.references.iter().enumerate() {
+            index.by_source.entry(reference.source.clone()).or_default().push(position);
+            index.by_target.entry(reference.target.clone()).or_default().push(position);
+        }
+
+        index
+    }
+
+    pub fn references(&self) -> &Vec<Reference> { &self.references }
+
+    /// Gets the path of the file defining a resource, if the datapack defines it
+    pub fn definition(&self, key: &ResourceKey) -> Option<&String> {
+        self.definitions.get(key)
+    }
+
+    pub fn is_defined(&self, key: &ResourceKey) -> bool {
+        self.definitions.contains_key(key)
+    }
+
+    /// Gets every reference made by a resource
+    pub fn references_from(&self, key: &ResourceKey) -> Vec<&Reference> {
+        self.get_references(self.by_source.get(key))
+    }
+
+    /// Gets every reference to a resource, from anywhere in the datapack
+    pub fn usages(&self, key: &ResourceKey) -> Vec<&Reference> {
+        self.get_references(self.by_target.get(key))
+    }
+
+    /// Gets every required reference to a resource which neither the datapack nor the available resources define,
+    /// such as ones from stacked datapacks and vanilla. Vanilla resources built into the game, such as structure
+    /// templates, are assumed to exist
+    pub fn dangling(&self, available: &BTreeSet<ResourceKey>) -> Vec<&Reference> {
+        self.references.iter()
+            .filter(|reference| reference.required && !self.is_defined(&reference.target) && !available.contains(&reference.target))
+            .filter(|reference| !reference.target.is_built_in())
+            .collect()
+    }
+
+    fn get_references(&self, positions: Option<&Vec<usize>>) -> Vec<&Reference> {
+        positions.into_iter().flatten().map(|position| &self.references[*position]).collect()
+    }
+
+    fn add_resource(&mut self, path: &str, overlay: Option<&str>, value: &Value) {
+        let Some(source) = ResourceKey::from_path(path) else { return };
+        self.definitions.insert(source.clone(), String::from(path));
+
+        let mut found = Vec::new();
+
+        match source.registry.strip_prefix(TAG_DIRECTORY).and_then(|registry| registry.strip_prefix('/')) {
+            Some(registry) => find_tag_references(registry, value, &mut found),
+            None => find_references(&source.registry, value, &mut Vec::new(), &mut found)
+        }
+
+        self.references.extend(found.into_iter().map(|(field, target, required)| Reference {
+            source: source.clone(),
+            overlay: overlay.map(String::from),
+            field,
+            target,
+            required
+        }));
+    }
+}
+
+/// Gets the key of every data file in a datapack, to check references against
+pub fn resource_keys(datapack: &SerializableDatapack) -> BTreeSet<ResourceKey> {
+    datapack.resource_paths().iter().filter_map(|path| ResourceKey::from_path(path)).collect()
+}
+
+//------------//
+
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    // A string is a reference to this registry, and an object is a definition written inline
+    Registry(&'static str),
+    // Only ever written inline
+    Inline(&'static str)
+}
+
+struct ReferenceField {
+    schema: &'static str,
+    // Some schemas hold different fields depending on their type
+    object_type: Option<&'static str>,
+    path: &'static str,
+    target: Target
+}
+
+impl ReferenceField {
+    const fn new(schema: &'static str, path: &'static str, target: Target) -> Self {
+        Self { schema, object_type: None, path, target }
+    }
+
+    const fn typed(schema: &'static str, object_type: &'static str, path: &'static str, target: Target) -> Self {
+        Self { schema, object_type: Some(object_type), path, target }
+    }
+}
+
+type FoundReference = (String, ResourceKey, bool);
+
+/// Follows every reference field of a schema through an object
+fn find_references(schema: &str, value: &Value, field: &mut Vec<JsonKey>, found: &mut Vec<FoundReference>) {
+    let Value::Object(object) = value else { return };
+    let object_type = object.get("type").and_then(Value::as_str).map(|object_type| object_type.trim_start_matches("minecraft:"));
+
+    let fields: Vec<&ReferenceField> = REFERENCE_FIELDS.iter()
+        .filter(|reference_field| reference_field.schema == schema)
+        .filter(|reference_field| reference_field.object_type.is_none() || reference_field.object_type == object_type)
+        .collect();
+
+    for reference_field in &fields {
+        let is_replaced = reference_field.object_type.is_none() && fields.iter()
+            .any(|other| other.object_type.is_some() && other.path == reference_field.path);
+        if is_replaced {
+            continue
+        }
+
+        let segments: Vec<&str> = reference_field.path.split('.').collect();
+        follow_path(object, &segments, reference_field.target, field, found);
+    }
+}
+
+fn follow_path(object: &Map<String, Value>, segments: &[&str], target: Target, field: &mut Vec<JsonKey>, found: &mut Vec<FoundReference>) {
+    let Some((segment, rest)) = segments.split_first() else { return };
+    let (key, is_array) = match segment.strip_suffix("[]") {
+        Some(key) => (key, true),
+        None => (*segment, false)
+    };
+
+    let children: Vec<(String, &Value)> = if key == "*" {
+        object.iter().map(|(key, value)| (key.clone(), value)).collect()
+    }
+    else {
+        object.get(key).map(|value| (String::from(key), value)).into_iter().collect()
+    };
+
+    for (key, value) in children {
+        field.push(JsonKey::Key(key));
+
+        let elements: Vec<(Option<usize>, &Value)> = match (is_array, value) {
+            (true, Value::Array(array)) => array.iter().enumerate().map(|(index, value)| (Some(index), value)).collect(),
+            (true, _) => Vec::new(),
+            (false, value) => vec![(None, value)]
+        };
+
+        for (index, value) in elements {
+            if let Some(index) = index {
+                field.push(JsonKey::Index(index));
+            }
+
+            match (rest.is_empty(), value) {
+                (true, value) => find_target(value, target, field, found),
+                (false, Value::Object(child)) => follow_path(child, rest, target, field, found),
+                _ => {}
+            }
+
+            if index.is_some() {
+                field.pop();
+            }
+        }
+
+        field.pop();
+    }
+}
+
+/// Reads a value which refers to a target. Strings are references, lists hold several values, and objects are inline definitions
+fn find_target(value: &Value, target: Target, field: &mut Vec<JsonKey>, found: &mut Vec<FoundReference>) {
+    match (value, target) {
+        (Value::String(reference), Target::Registry(registry)) => {
+            if let Some(key) = parse_reference(registry, reference) {
+                found.push((diff::format_field(field), key, true));
+            }
+        }
+        (Value::Array(array), _) => {
+            for (index, value) in array.iter().enumerate() {
+                field.push(JsonKey::Index(index));
+                find_target(value, target, field, found);
+                field.pop();
+            }
+        }
+        (Value::Object(_), Target::Registry(schema) | Target::Inline(schema)) => find_references(schema, value, field, found),
+        _ => {}
+    }
+}
+
+/// Reads the entries of a tag, which are either strings or objects with an id and whether the entry is required
+fn find_tag_references(registry: &str, value: &Value, found: &mut Vec<FoundReference>) {
+    let Some(Value::Array(entries)) = value.get("values") else { return };
+
+    for (index, entry) in entries.iter().enumerate() {
+        let (reference, required) = match entry {
+            Value::String(reference) => (reference.as_str(), true),
+            Value::Object(object) => match object.get("id").and_then(Value::as_str) {
+                Some(reference) => (reference, object.get("required").and_then(Value::as_bool).unwrap_or(true)),
+                None => continue
+            },
+            _ => continue
+        };
+
+        if let Some(key) = parse_reference(registry, reference) {
+            found.push((format!("values[{}]", index), key, required));
+        }
+    }
+}
+
+/// Reads a reference to a resource, or to a tag when it starts with '#'
+fn parse_reference(registry: &str, reference: &str) -> Option<ResourceKey> {
+    let (registry, reference) = match reference.strip_prefix('#') {
+        Some(reference) => (format!("{}/{}", TAG_DIRECTORY, registry), reference),
+        None => (String::from(registry), reference)
+    };
+
+    Some(ResourceKey { registry, location: reference.parse().ok()? })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::test_util;
+    use super::*;
+
+    fn key(registry: &str, location: &str) -> ResourceKey {
+        ResourceKey::new(registry, location.parse().unwrap())
+    }
+
+    #[test]
+    fn indexes_templates_in_the_singular_layout() {
+        let pack_info = test_util::pack_info(48, None, &[]);
+        let template_pool = r#"{"fallback": "minecraft:empty", "elements": [{"weight": 1, "element": {
+            "element_type": "minecraft:single_pool_element", "location": "test:house", "processors": "minecraft:empty", "projection": "rigid"}}]}"#;
+
+        let import = test_util::import(&[
+            ("pack.mcmeta", &pack_info),
+            ("data/test/worldgen/template_pool/houses.json", template_pool),
+            ("data/test/structure/house.nbt", "")
+        ]);
+        let index = ReferenceIndex::new(&import.datapack);
+
+        let template = key("structures", "test:house");
+        assert_eq!(ResourceKey::from_path("data/test/structure/house.nbt"), Some(template.clone()));
+        assert_eq!(index.definition(&template).map(String::as_str), Some("data/test/structure/house.nbt"));
+        assert_eq!(index.usages(&template)[0].field, "elements[0].element.location");
+
+        let available = BTreeSet::from([key("worldgen/template_pool", "minecraft:empty"), key("worldgen/processor_list", "minecraft:empty")]);
+        assert!(index.dangling(&available).is_empty());
+    }
+
+    #[test]
+    fn indexes_templates_and_exclusion_zones() {
+        let pack_info = test_util::pack_info(26, None, &[]);
+        let structure_set = r#"{
+            "structures": [{"structure": "test:house", "weight": 1}],
+            "placement": {"type": "minecraft:random_spread", "salt": 1, "spacing": 8, "separation": 4,
+                "exclusion_zone": {"other_set": "test:towers", "chunk_count": 4}}
+        }"#;
+        let template_pool = r#"{
+            "fallback": "minecraft:empty",
+            "elements": [
+                {"weight": 1, "element": {"element_type": "minecraft:single_pool_element", "location": "test:house",
+                    "processors": "minecraft:empty", "projection": "rigid"}},
+                {"weight": 1, "element": {"element_type": "minecraft:single_pool_element", "location": "test:missing",
+                    "processors": "minecraft:empty", "projection": "rigid"}},
+                {"weight": 1, "element": {"element_type": "minecraft:single_pool_element", "location": "minecraft:village/plains/houses/plains_small_house_1",
+                    "processors": "minecraft:empty", "projection": "rigid"}}
+            ]
+        }"#;
+
+        let import = test_util::import(&[
+            ("pack.mcmeta", &pack_info),
+            ("data/test/worldgen/structure_set/houses.json", structure_set),
+            ("data/test/worldgen/template_pool/houses.json", template_pool),
+            ("data/test/structures/house.nbt", "")
+        ]);
+        let index = ReferenceIndex::new(&import.datapack);
+
+        let template = key("structures", "test:house");
+        assert!(index.is_defined(&template));
+
+        let usages = index.usages(&template);
+        assert_eq!(usages.len(), 1);
+        assert_eq!(usages[0].source, key("worldgen/template_pool", "test:houses"));
+        assert_eq!(usages[0].field, "elements[0].element.location");
+
+        let towers = key("worldgen/structure_set", "test:towers");
+        assert_eq!(index.usages(&towers)[0].field, "placement.exclusion_zone.other_set");
+
+        let available = BTreeSet::from([key("worldgen/template_pool", "minecraft:empty"), key("worldgen/processor_list", "minecraft:empty")]);
+        let dangling: BTreeSet<&ResourceKey> = index.dangling(&available).into_iter().map(|reference| &reference.target).collect();
+
+        assert_eq!(dangling, BTreeSet::from([
+            &towers,
+            &key("structures", "test:missing"),
+            &key("worldgen/structure", "test:house")
+        ]));
+    }
+}
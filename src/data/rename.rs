@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use serde_json::Value;
+use crate::data::datapack::{Datapack, DatapackError, DatapackImport, DirectoryLayout, SerializableDatapack};
+use crate::data::diff::{self, JsonKey};
+use crate::data::merge::{self, StoredPath};
+use crate::data::overlay::DatapackFiles;
+use crate::data::reference::{ReferenceIndex, ResourceKey};
+use crate::data::util::ResourceLocation;
+
+///////////////////////////////////
+//------ Renames and Moves ------//
+///////////////////////////////////
+
+/// Renames resources along with every reference to them. Each moved resource keeps its contents and changes path
+/// in the root data and in every overlay, and each reference to it, from other resources, tags, dimension biome
+/// sources and template pool elements, is rewritten to the new name. Nothing changes until the rename is finished, so it can be previewed first
+#[derive(Debug, Clone)]
+pub struct ResourceRename {
+    name: String,
+    files: BTreeMap<StoredPath, Vec<u8>>,
+    changes: Vec<RenameChange>
+}
+
+impl ResourceRename {
+    /// Renames one resource, which has to be defined by the datapack. Tags are renamed the same way as resources
+    pub fn rename(datapack: &SerializableDatapack, from: &ResourceKey, to: ResourceLocation) -> Result<Self, DatapackError> {
+        check_location(&to)?;
+
+        let index = ReferenceIndex::new(datapack);
+        let path = index.definition(from)
+            .ok_or_else(|| DatapackError::File(format!("{} is not defined by the datapack, only its own resources can be renamed", from).into()))?;
+
+        let to = ResourceKey::new(&from.registry, to);
+        if *from == to {
+            return Err(DatapackError::File(format!("{} already has that name", from).into()))
+        }
+
+        let extension = path.rsplit_once('.').map(|(_, extension)| format!(".{}", extension)).unwrap_or_default();
+        let mut new_path = format!("data/{}/{}/{}{}", to.location.namespace(), to.registry, to.location.id(), extension);
+
+        // Keys use the plural directory names, so files stored in the singular layout are moved within it
+        if let Some((DirectoryLayout::Singular, _)) = DirectoryLayout::of_path(path) {
+            if let Some((_, singular_path)) = DirectoryLayout::of_path(&new_path) {
+                new_path = singular_path;
+            }
+        }
+
+        let paths = BTreeMap::from([(path.clone(), new_path)]);
+        let keys = BTreeMap::from([(from.clone(), to)]);
+
+        Self::new(datapack, &index, paths, keys)
+    }
+
+    /// Moves every file in one namespace to another, including structure templates, whose template pool references
+    /// are rewritten along with the rest
+    pub fn move_namespace(datapack: &SerializableDatapack, from: &str, to: &str) -> Result<Self, DatapackError> {
+        check_namespace(to)?;
+        if from == to {
+            return Err(DatapackError::File(format!("The files are already in namespace {}", from).into()))
+        }
+
+        let index = ReferenceIndex::new(datapack);
+        let (from_directory, to_directory) = (format!("data/{}/", from), format!("data/{}/", to));
+
+        let paths: BTreeMap<String, String> = merge::get_stored_files(datapack)?.into_keys()
+            .filter_map(|(_, path)| {
+                let rest = path.strip_prefix(&from_directory)?;
+                Some((path.clone(), format!("{}{}", to_directory, rest)))
+            })
+            .collect();
+
+        if paths.is_empty() {
+            return Err(DatapackError::File(format!("The datapack has no files in namespace {}", from).into()))
+        }
+
+        let keys = paths.iter()
+            .filter_map(|(path, new_path)| Some((ResourceKey::from_path(path)?, ResourceKey::from_path(new_path)?)))
+            .collect();
+
+        Self::new(datapack, &index, paths, keys)
+    }
+
+    /// Works out every change, given the new path of each moved file and the new key of each moved resource
+    fn new(
+        datapack: &SerializableDatapack,
+        index: &ReferenceIndex,
+        paths: BTreeMap<String, String>,
+        keys: BTreeMap<ResourceKey, ResourceKey>
+    ) -> Result<Self, DatapackError> {
+        let stored_files = merge::get_stored_files(datapack)?;
+
+        // Moved files cannot replace files which stay where they are
+        for ((overlay, path), new_path) in stored_files.keys().filter_map(|stored_path| Some((stored_path, paths.get(&stored_path.1)?))) {
+            let stored_path = (overlay.clone(), new_path.clone());
+            if stored_files.contains_key(&stored_path) && !paths.contains_key(new_path) {
+                return Err(DatapackError::File(format!("Cannot move {} to {}, which already exists", path, new_path).into()))
+            }
+        }
+
+        // The references to rewrite in each file, found through the resource making them
+        let mut rewrites: BTreeMap<StoredPath, Vec<(&String, &ResourceKey)>> = BTreeMap::new();
+        for reference in index.references() {
+            let Some(target) = keys.get(&reference.target) else { continue };
+            if let Some(path) = index.definition(&reference.source) {
+                rewrites.entry((reference.overlay.clone(), path.clone())).or_default().push((&reference.field, target));
+            }
+        }
+
+        let mut files = BTreeMap::new();
+        let mut moves = Vec::new();
+        let mut references = Vec::new();
+
+        for ((overlay, path), mut contents) in stored_files {
+            let new_path = paths.get(&path).cloned().unwrap_or_else(|| path.clone());
+            if new_path != path {
+                moves.push(RenameChange::Move { overlay: overlay.clone(), from: path.clone(), to: new_path.clone() });
+            }
+
+            if let Some(fields) = rewrites.get(&(overlay.clone(), path.clone())) {
+                let mut value: Value = serde_json::from_slice(&contents)
+                    .map_err(|error| DatapackError::from(error).with_path(&path))?;
+
+                for (field, target) in fields {
+                    if let Some((field, from, to)) = rewrite_reference(&mut value, field, target) {
+                        references.push(RenameChange::Reference { overlay: overlay.clone(), path: new_path.clone(), field, from, to });
+                    }
+                }
+
+                contents = serde_json::to_string_pretty(&value)?.into_bytes();
+            }
+
+            files.insert((overlay, new_path), contents);
+        }
+
+        moves.extend(references);
+
+        Ok(Self {
+            name: String::from(datapack.name()),
+            files,
+            changes: moves
+        })
+    }
+
+    /// Every file moved, followed by every reference rewritten
+    pub fn changes(&self) -> &Vec<RenameChange> { &self.changes }
+
+    /// Builds the datapack with everything renamed
+    pub fn finish(&self) -> Result<DatapackImport, DatapackError> {
+        let mut datapack_files = DatapackFiles::new();
+
+        for ((overlay, path), contents) in &self.files {
+            let path = match overlay {
+                Some(overlay) => format!("{}/{}", overlay, path),
+                None => path.clone()
+            };
+
+            datapack_files.insert(path, contents.clone());
+        }
+
+        SerializableDatapack::from_files(&self.name, &datapack_files)
+    }
+}
+
+impl Datapack {
+    /// Renames one of the datapack's resources, along with every reference to it. See [`ResourceRename`]
+    pub fn rename_resource(&self, from: &ResourceKey, to: ResourceLocation) -> Result<ResourceRename, DatapackError> {
+        ResourceRename::rename(&SerializableDatapack::from(self.clone()), from, to)
+    }
+
+    /// Moves every file in one namespace to another, along with every reference to the moved resources
+    pub fn move_namespace(&self, from: &str, to: &str) -> Result<ResourceRename, DatapackError> {
+        ResourceRename::move_namespace(&SerializableDatapack::from(self.clone()), from, to)
+    }
+}
+
+//------------//
+
+/// One change a rename makes. Paths are relative to the root or overlay directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameChange {
+    Move { overlay: Option<String>, from: String, to: String },
+    // A reference rewritten in the file at the given path, after any move
+    Reference { overlay: Option<String>, path: String, field: String, from: String, to: String }
+}
+
+impl Display for RenameChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let overlay = match self {
+            RenameChange::Move { overlay, from, to } => {
+                write!(f, "Move {} to {}", from, to)?;
+                overlay
+            }
+            RenameChange::Reference { overlay, path, field, from, to } => {
+                write!(f, "Change {} {} from {} to {}", path, field, from, to)?;
+                overlay
+            }
+        };
+
+        if let Some(overlay) = overlay {
+            write!(f, " (overlay {})", overlay)?;
+        }
+
+        Ok(())
+    }
+}
+
+//------------//
+
+/// Checks that a new name can be used as a file path. The game reads locations such as minecraft:a//b, but a file
+/// needs a name for its namespace directory and for each directory along its path
+fn check_location(location: &ResourceLocation) -> Result<(), DatapackError> {
+    location.validate().map_err(|error| DatapackError::File(error.describe(&location.to_string()).into()))?;
+    check_namespace(location.namespace())?;
+
+    if location.id().split('/').any(str::is_empty) {
+        return Err(DatapackError::File(format!("\"{}\" cannot be used as a file path, as it has an empty directory or file name", location).into()))
+    }
+
+    Ok(())
+}
+
+fn check_namespace(namespace: &str) -> Result<(), DatapackError> {
+    ResourceLocation::validate_namespace(namespace)
+        .map_err(|error| DatapackError::File(format!("Invalid namespace \"{}\": {}", namespace, error).into()))?;
+
+    if namespace.is_empty() {
+        return Err(DatapackError::File("The namespace cannot be empty, as it is used as a directory name".into()))
+    }
+
+    Ok(())
+}
+
+/// Points a reference at a moved resource, returning the field along with the old and new values.
+/// Tag entries written as objects hold the reference in their id
+fn rewrite_reference(value: &mut Value, field: &str, target: &ResourceKey) -> Option<(String, String, String)> {
+    let mut keys = diff::parse_field(field)?;
+    if let Some(Value::Object(_)) = diff::get_json_field(value, &keys) {
+        keys.push(JsonKey::Key(String::from("id")));
+    }
+
+    let from = diff::get_json_field(value, &keys)?.as_str()?.to_string();
+    let to = format!("{}{}", if from.starts_with('#') { "#" } else { "" }, target.location);
+    if !diff::set_json_field(value, &keys, Some(Value::String(to.clone()))) {
+        return None
+    }
+
+    Some((diff::format_field(&keys), from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::test_util;
+    use super::*;
+
+    const STRUCTURE: &str = r#"{"type": "minecraft:jigsaw", "biomes": "test:dunes", "start_pool": "test:houses", "step": "surface_structures",
+        "spawn_overrides": {}, "size": 1, "max_distance_from_center": 80, "start_height": {"absolute": 0}, "use_expansion_hack": false}"#;
+    const TEMPLATE_POOL: &str = r#"{"fallback": "minecraft:empty", "elements": [{"weight": 1, "element": {
+        "element_type": "minecraft:single_pool_element", "location": "test:house", "processors": "minecraft:empty", "projection": "rigid"}}]}"#;
+    const TAG: &str = r#"{"values": ["test:dunes", {"id": "minecraft:plains", "required": false}]}"#;
+
+    fn import() -> SerializableDatapack {
+        let pack_info = test_util::pack_info(26, None, &[]);
+        let biome = test_util::biome(0.8);
+
+        test_util::import(&[
+            ("pack.mcmeta", &pack_info),
+            ("data/test/worldgen/biome/dunes.json", &biome),
+            ("data/test/worldgen/biome/plains.json", &biome),
+            ("data/test/worldgen/structure/houses.json", STRUCTURE),
+            ("data/test/worldgen/template_pool/houses.json", TEMPLATE_POOL),
+            ("data/test/tags/worldgen/biome/sandy.json", TAG),
+            ("data/test/structures/house.nbt", "template")
+        ]).datapack
+    }
+
+    fn location(location: &str) -> ResourceLocation {
+        location.parse().unwrap()
+    }
+
+    fn read(datapack: &SerializableDatapack, path: &str) -> Value {
+        let file = datapack.passthrough_file(path).and_then(|file| file.root_data()).expect("File should exist");
+        serde_json::from_slice(file.contents()).unwrap()
+    }
+
+    #[test]
+    fn rename_rewrites_references() {
+        let datapack = import();
+        let from = ResourceKey::new("worldgen/biome", location("test:dunes"));
+
+        let rename = ResourceRename::rename(&datapack, &from, location("test:desert_dunes")).unwrap();
+        assert_eq!(rename.changes()[0], RenameChange::Move {
+            overlay: None,
+            from: String::from("data/test/worldgen/biome/dunes.json"),
+            to: String::from("data/test/worldgen/biome/desert_dunes.json")
+        });
+        assert_eq!(rename.changes().len(), 3);
+
+        let renamed = rename.finish().unwrap().datapack;
+        assert!(renamed.biome(&location("test:dunes")).is_none());
+        assert!(renamed.biome(&location("test:desert_dunes")).is_some());
+
+        assert_eq!(read(&renamed, "data/test/worldgen/structure/houses.json")["biomes"], "test:desert_dunes");
+        assert_eq!(read(&renamed, "data/test/tags/worldgen/biome/sandy.json")["values"][0], "test:desert_dunes");
+    }
+
+    #[test]
+    fn rename_rejects_taken_and_unknown_names() {
+        let datapack = import();
+        let dunes = ResourceKey::new("worldgen/biome", location("test:dunes"));
+
+        assert!(ResourceRename::rename(&datapack, &dunes, location("test:plains")).is_err());
+        assert!(ResourceRename::rename(&datapack, &dunes, location("test:dunes")).is_err());
+        assert!(ResourceRename::rename(&datapack, &dunes, ResourceLocation::new(String::from("test"), String::from("Dunes"))).is_err());
+
+        // Locations the game reads but which are not file paths
+        assert!(ResourceRename::rename(&datapack, &dunes, location("test:a//dunes")).is_err());
+        assert!(ResourceRename::rename(&datapack, &dunes, location("test:dunes/")).is_err());
+        assert!(ResourceRename::move_namespace(&datapack, "test", "").is_err());
+
+        let missing = ResourceKey::new("worldgen/biome", location("test:missing"));
+        assert!(ResourceRename::rename(&datapack, &missing, location("test:found")).is_err());
+    }
+
+    #[test]
+    fn rename_keeps_templates_in_the_singular_layout() {
+        let pack_info = test_util::pack_info(48, None, &[]);
+        let datapack = test_util::import(&[
+            ("pack.mcmeta", &pack_info),
+            ("data/test/worldgen/template_pool/houses.json", TEMPLATE_POOL),
+            ("data/test/structure/house.nbt", "template")
+        ]).datapack;
+
+        let from = ResourceKey::new("structures", location("test:house"));
+        let renamed = ResourceRename::rename(&datapack, &from, location("test:cottage")).unwrap().finish().unwrap().datapack;
+
+        assert!(renamed.passthrough_file("data/test/structure/cottage.nbt").is_some());
+        assert!(renamed.passthrough_file("data/test/structure/house.nbt").is_none());
+        assert_eq!(read(&renamed, "data/test/worldgen/template_pool/houses.json")["elements"][0]["element"]["location"], "test:cottage");
+    }
+
+    #[test]
+    fn move_namespace_moves_templates_along_with_their_references() {
+        let datapack = import();
+
+        let moved = ResourceRename::move_namespace(&datapack, "test", "other").unwrap().finish().unwrap().datapack;
+        assert!(moved.passthrough_file("data/other/structures/house.nbt").is_some());
+        assert!(moved.passthrough_file("data/test/structures/house.nbt").is_none());
+
+        let template_pool = read(&moved, "data/other/worldgen/template_pool/houses.json");
+        assert_eq!(template_pool["elements"][0]["element"]["location"], "other:house");
+        assert_eq!(template_pool["fallback"], "minecraft:empty");
+
+        let tag = read(&moved, "data/other/tags/worldgen/biome/sandy.json");
+        assert_eq!(tag["values"][0], "other:dunes");
+        assert_eq!(tag["values"][1]["id"], "minecraft:plains");
+
+        assert!(ResourceRename::move_namespace(&datapack, "missing", "other").is_err());
+        assert!(ResourceRename::move_namespace(&datapack, "test", "Other").is_err());
+    }
+}
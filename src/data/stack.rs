@@ -0,0 +1,282 @@
+use std::collections::{BTreeMap, BTreeSet};
+use serde_json::Value;
+use crate::data::biome::SerializableBiomeData;
+use crate::data::datapack::{DatapackFormat, FileElement, Filter, PassthroughFile, SerializableDataHolder, SerializableDatapack};
+use crate::data::tag::TagMembership;
+use crate::data::util::ResourceLocation;
+
+/////////////////////////////////
+//------ Datapack Stacks ------//
+/////////////////////////////////
+
+/// Several datapacks loaded together, in the same order the game loads them.
+/// Packs pushed later have a higher priority and override packs below them, except for tags, which every pack adds to
+#[derive(Debug, Default)]
+pub struct DatapackStack {
+    layers: Vec<StackLayer>
+}
+
+#[derive(Debug)]
+struct StackLayer {
+    datapack: SerializableDatapack,
+    is_vanilla: bool
+}
+
+impl DatapackStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a datapack above every datapack already in the stack
+    pub fn push(&mut self, datapack: SerializableDatapack) {
+        self.layers.push(StackLayer { datapack, is_vanilla: false });
+    }
+
+    /// Adds the vanilla data below every datapack in the stack, as the game always loads it first.
+    /// Vanilla data is never part of a conflict
+    pub fn push_vanilla(&mut self, datapack: SerializableDatapack) {
+        self.layers.insert(0, StackLayer { datapack, is_vanilla: true });
+    }
+
+    pub fn len(&self) -> usize { self.layers.len() }
+    pub fn is_empty(&self) -> bool { self.layers.is_empty() }
+
+    pub fn get(&self, index: usize) -> Option<&SerializableDatapack> {
+        self.layers.get(index).map(|layer| &layer.datapack)
+    }
+
+    pub fn is_vanilla(&self, index: usize) -> bool {
+        self.layers.get(index).is_some_and(|layer| layer.is_vanilla)
+    }
+
+    /// Works out the members of every worldgen tag in the stack, as the given format loads them. See [`TagMembership`]
+    pub fn resolve_tags(&self, datapack_format: DatapackFormat) -> TagMembership {
+        let datapacks: Vec<&SerializableDatapack> = self.layers.iter().map(|layer| &layer.datapack).collect();
+        TagMembership::resolve(&datapacks, datapack_format)
+    }
+
+    /// Works out which datapack provides the final version of every resource in the stack. Tags are combined across
+    /// every pack instead, until a pack replaces them, so they are merged rather than overridden and never conflict
+    pub fn resolve(&self) -> StackResolution<'_> {
+        let mut resources: BTreeMap<String, ResolvedResource> = BTreeMap::new();
+        let mut filters: Vec<(usize, Filter)> = Vec::new();
+        // Tags replaced by a higher priority pack, which drop the copies of the packs below it
+        let mut replaced_tags: BTreeSet<String> = BTreeSet::new();
+
+        // Walk from the highest priority pack down, so that each pack only sees the filters of the packs above it
+        for (index, layer) in self.layers.iter().enumerate().rev() {
+            for path in layer.datapack.resource_paths() {
+                let filtered_by = filters.iter()
+                    .find(|(_, filter)| filter.matches_path(&path))
+                    .map(|(filter_index, _)| *filter_index);
+
+                if let Some(filter_index) = filtered_by {
+                    resources.entry(path)
+                        .or_default()
+                        .filtered.push(FilteredResource { datapack: index, filtered_by: filter_index });
+                    continue;
+                }
+
+                let is_merged_tag = is_tag(&path) && !replaced_tags.contains(&path);
+                if is_merged_tag && replaces_tag(&layer.datapack, &path) {
+                    replaced_tags.insert(path.clone());
+                }
+
+                let resource = resources.entry(path).or_default();
+                match resource.provider {
+                    None => resource.provider = Some(index),
+                    Some(_) if is_merged_tag => resource.merged.push(index),
+                    Some(_) => resource.overridden.push(index)
+                }
+            }
+
+            filters.extend(layer.datapack.filters().into_iter()
+                .filter(|filter| filter.validate().is_ok())
+                .map(|filter| (index, filter)));
+        }
+
+        let conflicts = resources.iter()
+            .filter(|(path, _)| !is_tag(path))
+            .filter_map(|(path, resource)| {
+                let datapacks: Vec<usize> = resource.provider.iter()
+                    .chain(resource.overridden.iter())
+                    .copied()
+                    .filter(|index| !self.is_vanilla(*index))
+                    .collect();
+
+                if datapacks.len() > 1 { Some(StackConflict { path: path.clone(), datapacks }) } else { None }
+            })
+            .collect();
+
+        StackResolution {
+            stack: self,
+            resources,
+            conflicts
+        }
+    }
+}
+
+//------------//
+
+/// The combined result of a datapack stack. Datapacks are referred to by their index in the stack
+#[derive(Debug)]
+pub struct StackResolution<'a> {
+    stack: &'a DatapackStack,
+    resources: BTreeMap<String, ResolvedResource>,
+    conflicts: Vec<StackConflict>
+}
+
+impl<'a> StackResolution<'a> {
+    /// Every resource in the stack keyed by path relative to the pack root, including ones which have been filtered out entirely
+    pub fn resources(&self) -> &BTreeMap<String, ResolvedResource> { &self.resources }
+    pub fn conflicts(&self) -> &Vec<StackConflict> { &self.conflicts }
+
+    pub fn get(&self, path: &str) -> Option<&ResolvedResource> {
+        self.resources.get(path)
+    }
+
+    /// Gets the datapack which provides the final version of a resource
+    pub fn provider(&self, path: &str) -> Option<&'a SerializableDatapack> {
+        self.resources.get(path)
+            .and_then(|resource| resource.provider)
+            .and_then(|index| self.stack.get(index))
+    }
+
+    pub fn biome(&self, resource_location: &ResourceLocation) -> Option<&'a SerializableDataHolder<SerializableBiomeData>> {
+        let path = SerializableBiomeData::get_file_path(resource_location);
+        self.provider(&path)?.biome(resource_location)
+    }
+
+    pub fn passthrough_file(&self, path: &str) -> Option<&'a SerializableDataHolder<PassthroughFile>> {
+        self.provider(path)?.passthrough_file(path)
+    }
+
+    /// Gets the name of a datapack in the stack, for displaying results
+    pub fn datapack_name(&self, index: usize) -> &'a str {
+        self.stack.get(index).map(|datapack| datapack.name()).unwrap_or("Unknown")
+    }
+
+    /// Describes the resolution in a few lines, for displaying to the user
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for index in (0..self.stack.len()).rev() {
+            let provided = self.resources.values().filter(|resource| resource.provider == Some(index)).count();
+            let overridden = self.resources.values().filter(|resource| resource.overridden.contains(&index)).count();
+            let merged = self.resources.values().filter(|resource| resource.merged.contains(&index)).count();
+            let filtered = self.resources.values()
+                .filter(|resource| resource.filtered.iter().any(|filtered| filtered.datapack == index))
+                .count();
+
+            lines.push(format!("{}: provides {}, merged {}, overridden {}, filtered {}", self.datapack_name(index), provided, merged, overridden, filtered));
+        }
+
+        for conflict in &self.conflicts {
+            let datapacks: Vec<&str> = conflict.datapacks.iter().map(|index| self.datapack_name(*index)).collect();
+            lines.push(format!("Conflict: {} is defined by {}", conflict.path, datapacks.join(", ")));
+        }
+
+        lines
+    }
+}
+
+//------------//
+
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedResource {
+    /// The datapack providing the final version of the resource, or None if every copy was filtered out
+    pub provider: Option<usize>,
+    /// Datapacks whose copy of a tag is combined with the provider's, highest priority first. See [`TagMembership`]
+    pub merged: Vec<usize>,
+    /// Datapacks whose copy was replaced by a higher priority datapack, highest priority first
+    pub overridden: Vec<usize>,
+    /// Datapacks whose copy was hidden by the filter of a higher priority datapack
+    pub filtered: Vec<FilteredResource>
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FilteredResource {
+    pub datapack: usize,
+    pub filtered_by: usize
+}
+
+//------------//
+
+/// A resource other than a tag defined by more than one non-vanilla datapack
+#[derive(Debug, Clone)]
+pub struct StackConflict {
+    pub path: String,
+    /// The datapacks defining the resource, highest priority first
+    pub datapacks: Vec<usize>
+}
+
+//------------//
+
+/// Checks whether a resource path is a tag, such as data/minecraft/tags/worldgen/biome/is_ocean.json
+fn is_tag(path: &str) -> bool {
+    path.split('/').nth(2) == Some("tags")
+}
+
+/// Checks whether a pack's copy of a tag drops the copies of the packs below it
+fn replaces_tag(datapack: &SerializableDatapack, path: &str) -> bool {
+    datapack.passthrough_file(path)
+        .and_then(|data_holder| data_holder.root_data())
+        .and_then(|file| serde_json::from_slice::<Value>(file.contents()).ok())
+        .and_then(|tag| tag.get("replace")?.as_bool())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::test_util;
+    use super::*;
+
+    const BIOME: &str = "data/test/worldgen/biome/x.json";
+    const TAG: &str = "data/test/tags/worldgen/biome/hot.json";
+
+    fn datapack(files: &[(&str, &str)]) -> SerializableDatapack {
+        let pack_info = test_util::pack_info(26, None, &[]);
+        let mut contents = vec![("pack.mcmeta", pack_info.as_str())];
+        contents.extend_from_slice(files);
+        test_util::import(&contents).datapack
+    }
+
+    #[test]
+    fn tags_are_merged_instead_of_conflicting() {
+        let biome = test_util::biome(0.8);
+        let tag = r#"{"values": ["test:x"]}"#;
+
+        let mut stack = DatapackStack::new();
+        stack.push(datapack(&[(BIOME, &biome), (TAG, tag)]));
+        stack.push(datapack(&[(BIOME, &biome), (TAG, tag)]));
+        stack.push_vanilla(datapack(&[(TAG, tag)]));
+        assert!(stack.is_vanilla(0));
+
+        let resolution = stack.resolve();
+        let tag = resolution.get(TAG).unwrap();
+        assert_eq!(tag.provider, Some(2));
+        assert_eq!(tag.merged, vec![1, 0]);
+        assert!(tag.overridden.is_empty());
+
+        let conflicts: Vec<&str> = resolution.conflicts().iter().map(|conflict| conflict.path.as_str()).collect();
+        assert_eq!(conflicts, vec![BIOME]);
+        assert_eq!(resolution.get(BIOME).unwrap().overridden, vec![1]);
+    }
+
+    #[test]
+    fn replacing_tags_override_the_packs_below() {
+        let tag = r#"{"values": ["test:x"]}"#;
+
+        let mut stack = DatapackStack::new();
+        stack.push(datapack(&[(TAG, tag)]));
+        stack.push(datapack(&[(TAG, r#"{"replace": true, "values": ["test:x"]}"#)]));
+        stack.push(datapack(&[(TAG, tag)]));
+
+        let resolution = stack.resolve();
+        let tag = resolution.get(TAG).unwrap();
+        assert_eq!(tag.provider, Some(2));
+        assert_eq!(tag.merged, vec![1]);
+        assert_eq!(tag.overridden, vec![0]);
+        assert!(resolution.conflicts().is_empty());
+    }
+}
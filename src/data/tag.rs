@@ -0,0 +1,482 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::data::datapack::{Datapack, DatapackError, DatapackFormat, Filter, PassthroughFile, SerializableDataElement, SerializableDataHolder, SerializableDatapack};
+use crate::data::reference::ResourceKey;
+use crate::data::util::{ResourceLocation, ResourceLocationError};
+
+const TAG_DIRECTORY: &str = "tags";
+
+lazy_static! {
+    static ref WORLDGEN_TAG_REG: Regex = Regex::new(r"data/[a-z0-9_.-]+/tags/worldgen/[a-z0-9_]+/[a-z0-9/_.-]+\.json$").unwrap();
+}
+
+//////////////////////////////
+//------ Worldgen Tags ------//
+//////////////////////////////
+
+/// A tag file, listing resources and other tags. Every pack defining the same tag adds to it, unless it replaces it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TagData {
+    /// Drops the entries of lower priority packs instead of adding to them
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub replace: bool,
+    pub values: Vec<TagEntry>
+}
+
+impl TagData {
+    /// Checks whether a data file path is a worldgen tag, such as data/minecraft/tags/worldgen/biome/is_ocean.json
+    pub fn is_worldgen_tag(path: &str) -> bool {
+        WORLDGEN_TAG_REG.is_match(path)
+    }
+
+    /// Gets the path of a tag, such as a key in tags/worldgen/biome
+    pub fn get_file_path(key: &ResourceKey) -> String {
+        format!("data/{}/{}/{}.json", key.location.namespace(), key.registry, key.location.id())
+    }
+
+    /// Removes every entry for a value, returning whether there were any
+    pub fn remove(&mut self, value: &TagValue) -> bool {
+        let length = self.values.len();
+        self.values.retain(|entry| entry.value != *value);
+        self.values.len() != length
+    }
+}
+
+impl SerializableDataElement for TagData {
+    fn serialize(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    fn deserialize(json: String) -> serde_json::Result<Box<Self>> {
+        Ok(Box::new(serde_json::from_str(&json)?))
+    }
+}
+
+//------------//
+
+/// A resource or nested tag listed in a tag. Optional entries are skipped when they do not exist, where a missing
+/// required entry stops the whole tag from loading
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "SerializableTagEntry", into = "SerializableTagEntry")]
+pub struct TagEntry {
+    pub value: TagValue,
+    pub required: bool
+}
+
+impl TagEntry {
+    pub fn new(value: TagValue) -> Self {
+        Self { value, required: true }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SerializableTagEntry {
+    Value(String),
+    Object {
+        id: String,
+        #[serde(default = "SerializableTagEntry::default_required")]
+        required: bool
+    }
+}
+
+impl SerializableTagEntry {
+    fn default_required() -> bool { true }
+}
+
+impl TryFrom<SerializableTagEntry> for TagEntry {
+    type Error = String;
+
+    fn try_from(entry: SerializableTagEntry) -> Result<Self, Self::Error> {
+        match entry {
+            SerializableTagEntry::Value(value) => Ok(Self { value: value.parse()?, required: true }),
+            SerializableTagEntry::Object { id, required } => Ok(Self { value: id.parse()?, required })
+        }
+    }
+}
+
+impl From<TagEntry> for SerializableTagEntry {
+    fn from(entry: TagEntry) -> Self {
+        // Required entries are written the short way, as vanilla does
+        if entry.required { SerializableTagEntry::Value(entry.value.to_string()) }
+            else { SerializableTagEntry::Object { id: entry.value.to_string(), required: false } }
+    }
+}
+
+//------------//
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TagValue {
+    Resource(ResourceLocation),
+    // Includes every member of another tag of the same registry, written with a leading '#'
+    Tag(ResourceLocation)
+}
+
+impl FromStr for TagValue {
+    type Err = String;
+
+    /// Reads a value such as minecraft:plains or #minecraft:is_ocean. Values without a namespace are in minecraft
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (reference, is_tag) = match s.strip_prefix('#') {
+            Some(reference) => (reference, true),
+            None => (s, false)
+        };
+
+        let location: ResourceLocation = reference.parse()
+            .map_err(|error: ResourceLocationError| format!("Invalid tag entry \"{}\": {}", s, error.offset(usize::from(is_tag))))?;
+
+        Ok(if is_tag { TagValue::Tag(location) } else { TagValue::Resource(location) })
+    }
+}
+
+impl Display for TagValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagValue::Resource(location) => write!(f, "{}", location),
+            TagValue::Tag(location) => write!(f, "#{}", location)
+        }
+    }
+}
+
+//------------//
+
+impl SerializableDatapack {
+    /// Reads every worldgen tag stored in the root data, keyed by tag. Use [`SerializableDatapack::for_format`]
+    /// first to get the tags a format loads, overlays included
+    pub fn worldgen_tags(&self) -> BTreeMap<ResourceKey, Result<TagData, DatapackError>> {
+        self.passthrough_files()
+            .filter(|(path, _)| TagData::is_worldgen_tag(path))
+            .filter_map(|(path, data_holder)| {
+                let key = ResourceKey::from_path(path)?;
+                let file = data_holder.root_data()?;
+                let tag = serde_json::from_slice(file.contents()).map_err(|error| DatapackError::from(error).with_path(path));
+                Some((key, tag))
+            })
+            .collect()
+    }
+}
+
+impl Datapack {
+    /// Changes every stored copy of a tag the same way, so that each format sees the change. Tags the datapack
+    /// does not have yet are created in the root data, adding to the tag of lower priority packs.
+    /// Returns the path and new contents of the tag file, leaving the datapack itself unchanged
+    pub fn edit_tag(&self, key: &ResourceKey, mut edit: impl FnMut(&mut TagData)) -> Result<(String, SerializableDataHolder<PassthroughFile>), DatapackError> {
+        let path = TagData::get_file_path(key);
+
+        let data_holder = match self.passthrough().get(&path) {
+            Some(data_holder) => data_holder.try_map(|file| {
+                let mut tag: TagData = serde_json::from_slice(file.contents())
+                    .map_err(|error| DatapackError::from(error).with_path(&path))?;
+                edit(&mut tag);
+                Ok::<_, DatapackError>(PassthroughFile::new(SerializableDataElement::serialize(&tag)?.into_bytes()))
+            })?,
+            None => {
+                let mut tag = TagData::default();
+                edit(&mut tag);
+                SerializableDataHolder::from_parts(Some(PassthroughFile::new(SerializableDataElement::serialize(&tag)?.into_bytes())), Vec::new(), None)
+            }
+        };
+
+        Ok((path, data_holder))
+    }
+}
+
+//------------//
+
+/// The members of every worldgen tag once several packs are loaded together, as one format loads them.
+/// Nested tags are expanded, so each tag lists every resource it ends up holding
+#[derive(Debug, Clone, Default)]
+pub struct TagMembership {
+    // The entries of each tag, combined across every pack
+    entries: BTreeMap<ResourceKey, Vec<TagEntry>>,
+    members: BTreeMap<ResourceKey, BTreeSet<ResourceLocation>>,
+    // Why each tag which failed to load did so
+    errors: BTreeMap<ResourceKey, String>
+}
+
+impl TagMembership {
+    /// Resolves the tags of several packs, lowest priority first, such as vanilla data followed by datapacks. Each pack's
+    /// overlays for the format are applied, and files hidden by the filters of a higher priority pack are left out.
+    /// Required entries have to exist in one of the packs
+    pub fn resolve(datapacks: &[&SerializableDatapack], datapack_format: DatapackFormat) -> Self {
+        let mut membership = Self::default();
+        let mut available: BTreeSet<ResourceKey> = BTreeSet::new();
+        let mut layers = Vec::new();
+        let mut filters: Vec<Filter> = Vec::new();
+
+        // Walk from the highest priority pack down, so that each pack only sees the filters of the packs above it
+        for datapack in datapacks.iter().rev() {
+            let datapack = datapack.for_format(datapack_format);
+            let is_visible = |path: &str| !filters.iter().any(|filter| filter.matches_path(path));
+
+            available.extend(datapack.resource_paths().iter()
+                .filter(|path| is_visible(path))
+                .filter_map(|path| ResourceKey::from_path(path)));
+
+            let tags: Vec<(ResourceKey, Result<TagData, DatapackError>)> = datapack.worldgen_tags().into_iter()
+                .filter(|(key, _)| is_visible(&TagData::get_file_path(key)))
+                .collect();
+            layers.push(tags);
+
+            filters.extend(datapack.filters().into_iter().filter(|filter| filter.validate().is_ok()));
+        }
+
+        // Then combine the tags from the lowest priority pack up
+        for (key, tag) in layers.into_iter().rev().flatten() {
+            match tag {
+                Ok(tag) => {
+                    let entries = membership.entries.entry(key).or_default();
+                    if tag.replace {
+                        entries.clear();
+                    }
+                    entries.extend(tag.values);
+                }
+                Err(error) => {
+                    membership.entries.entry(key.clone()).or_default();
+                    membership.errors.insert(key, format!("could not be read: {}", error));
+                }
+            }
+        }
+
+        let keys: Vec<ResourceKey> = membership.entries.keys().cloned().collect();
+        for key in keys {
+            let _ = membership.resolve_tag(&key, &available, &mut Vec::new());
+        }
+
+        membership
+    }
+
+    /// Every tag defined by any of the packs, including ones which failed to load
+    pub fn tags(&self) -> impl Iterator<Item = &ResourceKey> {
+        self.entries.keys()
+    }
+
+    /// Gets the entries of a tag combined across every pack, before nested tags are expanded
+    pub fn entries(&self, tag: &ResourceKey) -> &[TagEntry] {
+        self.entries.get(tag).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Gets every resource a tag holds, or None if the tag is not defined or failed to load
+    pub fn members(&self, tag: &ResourceKey) -> Option<&BTreeSet<ResourceLocation>> {
+        self.members.get(tag)
+    }
+
+    pub fn contains(&self, tag: &ResourceKey, location: &ResourceLocation) -> bool {
+        self.members(tag).is_some_and(|members| members.contains(location))
+    }
+
+    /// Checks whether a tag lists a resource itself, rather than through a nested tag
+    pub fn is_direct_member(&self, tag: &ResourceKey, location: &ResourceLocation) -> bool {
+        self.entries(tag).iter().any(|entry| entry.value == TagValue::Resource(location.clone()))
+    }
+
+    /// Gets every tag holding a resource
+    pub fn tags_containing(&self, resource: &ResourceKey) -> Vec<&ResourceKey> {
+        self.members.iter()
+            .filter(|(tag, members)| Self::member_registry(tag) == Some(resource.registry.as_str()) && members.contains(&resource.location))
+            .map(|(tag, _)| tag)
+            .collect()
+    }
+
+    pub fn error(&self, tag: &ResourceKey) -> Option<&String> {
+        self.errors.get(tag)
+    }
+
+    /// Describes why each tag which failed to load did so
+    pub fn errors(&self) -> Vec<String> {
+        self.errors.iter().map(|(tag, error)| format!("{} {}", tag, error)).collect()
+    }
+
+    /// Gets the registry a tag holds resources of, such as worldgen/biome for tags/worldgen/biome
+    fn member_registry(tag: &ResourceKey) -> Option<&str> {
+        tag.registry.strip_prefix(TAG_DIRECTORY)?.strip_prefix('/')
+    }
+
+    /// Expands the entries of a tag, remembering the result. Tags which include themselves or miss a required entry fail to load
+    fn resolve_tag(&mut self, tag: &ResourceKey, available: &BTreeSet<ResourceKey>, including: &mut Vec<ResourceKey>) -> Result<BTreeSet<ResourceLocation>, ()> {
+        if let Some(members) = self.members.get(tag) {
+            return Ok(members.clone())
+        }
+        if self.errors.contains_key(tag) {
+            return Err(())
+        }
+        if including.contains(tag) {
+            self.errors.insert(tag.clone(), String::from("includes itself through its nested tags"));
+            return Err(())
+        }
+
+        let Some(registry) = Self::member_registry(tag).map(String::from) else { return Err(()) };
+        let entries = self.entries(tag).to_vec();
+        let mut members = BTreeSet::new();
+
+        including.push(tag.clone());
+
+        for entry in entries {
+            let result = match &entry.value {
+                TagValue::Resource(location) if available.contains(&ResourceKey::new(&registry, location.clone())) => {
+                    members.insert(location.clone());
+                    Ok(())
+                }
+                TagValue::Resource(_) => Err(format!("lists {}, which does not exist", entry.value)),
+                TagValue::Tag(location) => {
+                    let nested = ResourceKey::new(&tag.registry, location.clone());
+
+                    if self.entries.contains_key(&nested) {
+                        match self.resolve_tag(&nested, available, including) {
+                            Ok(nested_members) => {
+                                members.extend(nested_members);
+                                Ok(())
+                            }
+                            Err(()) => Err(format!("includes {}, which could not be loaded", entry.value))
+                        }
+                    }
+                    else {
+                        Err(format!("includes {}, which does not exist", entry.value))
+                    }
+                }
+            };
+
+            if let Err(error) = result {
+                if entry.required {
+                    including.pop();
+                    self.errors.entry(tag.clone()).or_insert(error);
+                    return Err(())
+                }
+            }
+        }
+
+        including.pop();
+
+        // A tag including itself fails from the inside, after its own entries were already being expanded
+        if self.errors.contains_key(tag) {
+            return Err(())
+        }
+
+        self.members.insert(tag.clone(), members.clone());
+        Ok(members)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::test_util;
+    use super::*;
+
+    const PLAINS: &str = "data/test/worldgen/biome/plains.json";
+    const DESERT: &str = "data/test/worldgen/biome/desert.json";
+
+    fn tag_path(id: &str) -> String {
+        format!("data/test/tags/worldgen/biome/{}.json", id)
+    }
+
+    fn tag_key(id: &str) -> ResourceKey {
+        ResourceKey::new("tags/worldgen/biome", ResourceLocation::new(String::from("test"), String::from(id)))
+    }
+
+    fn biome_location(id: &str) -> ResourceLocation {
+        ResourceLocation::new(String::from("test"), String::from(id))
+    }
+
+    fn datapack(files: &[(&str, &str)]) -> SerializableDatapack {
+        let pack_info = test_util::pack_info(26, None, &[]);
+        let mut contents = vec![("pack.mcmeta", pack_info.as_str())];
+        contents.extend_from_slice(files);
+        test_util::import(&contents).datapack
+    }
+
+    fn members(membership: &TagMembership, id: &str) -> Option<Vec<String>> {
+        membership.members(&tag_key(id)).map(|members| members.iter().map(|location| location.id().to_string()).collect())
+    }
+
+    #[test]
+    fn tag_values_read_nested_tags_and_count_positions_past_the_hash() {
+        assert_eq!("#test:hot".parse::<TagValue>(), Ok(TagValue::Tag(biome_location("hot"))));
+        assert_eq!("test:plains".parse::<TagValue>(), Ok(TagValue::Resource(biome_location("plains"))));
+
+        let error = "#test:Hot".parse::<TagValue>().unwrap_err();
+        assert!(error.contains("position 6"), "{}", error);
+        let error = "test:Hot".parse::<TagValue>().unwrap_err();
+        assert!(error.contains("position 5"), "{}", error);
+
+        let entry: TagEntry = serde_json::from_str(r##"{"id": "#test:hot", "required": false}"##).unwrap();
+        assert_eq!(entry, TagEntry { value: TagValue::Tag(biome_location("hot")), required: false });
+        assert_eq!(serde_json::to_string(&entry).unwrap(), r##"{"id":"#test:hot","required":false}"##);
+        assert_eq!(serde_json::to_string(&TagEntry::new(TagValue::Resource(biome_location("plains")))).unwrap(), r#""test:plains""#);
+    }
+
+    #[test]
+    fn packs_add_to_tags_unless_they_replace_them() {
+        let biome = test_util::biome(0.8);
+        let (hot, all) = (tag_path("hot"), tag_path("all"));
+        let lower = datapack(&[(PLAINS, &biome), (DESERT, &biome), (&hot, r#"{"values": ["test:desert"]}"#), (&all, r#"{"values": ["test:plains"]}"#)]);
+        let upper = datapack(&[(&hot, r#"{"values": ["test:plains"]}"#), (&all, r#"{"replace": true, "values": ["test:desert"]}"#)]);
+
+        let membership = TagMembership::resolve(&[&lower, &upper], DatapackFormat::new(26));
+        assert_eq!(members(&membership, "hot"), Some(vec![String::from("desert"), String::from("plains")]));
+        assert_eq!(members(&membership, "all"), Some(vec![String::from("desert")]));
+        assert!(membership.is_direct_member(&tag_key("hot"), &biome_location("plains")));
+    }
+
+    #[test]
+    fn nested_tags_are_expanded() {
+        let biome = test_util::biome(0.8);
+        let (hot, warm, all) = (tag_path("hot"), tag_path("warm"), tag_path("all"));
+        let datapack = datapack(&[
+            (PLAINS, &biome),
+            (DESERT, &biome),
+            (&hot, r#"{"values": ["test:desert"]}"#),
+            (&warm, r##"{"values": ["#test:hot"]}"##),
+            (&all, r##"{"values": ["#test:warm", "test:plains"]}"##)
+        ]);
+
+        let membership = TagMembership::resolve(&[&datapack], DatapackFormat::new(26));
+        assert_eq!(members(&membership, "all"), Some(vec![String::from("desert"), String::from("plains")]));
+        assert!(membership.contains(&tag_key("all"), &biome_location("desert")));
+        assert!(!membership.is_direct_member(&tag_key("all"), &biome_location("desert")));
+        assert_eq!(membership.tags_containing(&ResourceKey::new("worldgen/biome", biome_location("desert"))).len(), 3);
+    }
+
+    #[test]
+    fn tags_including_themselves_fail_to_load() {
+        let biome = test_util::biome(0.8);
+        let (a, b, c) = (tag_path("a"), tag_path("b"), tag_path("c"));
+        let datapack = datapack(&[
+            (PLAINS, &biome),
+            (&a, r##"{"values": ["#test:b"]}"##),
+            (&b, r##"{"values": ["#test:a", "test:plains"]}"##),
+            (&c, r##"{"values": ["test:plains", {"id": "#test:a", "required": false}]}"##)
+        ]);
+
+        let membership = TagMembership::resolve(&[&datapack], DatapackFormat::new(26));
+        assert_eq!(members(&membership, "a"), None);
+        assert_eq!(members(&membership, "b"), None);
+        assert!(membership.error(&tag_key("a")).is_some());
+        assert!(membership.error(&tag_key("b")).is_some());
+
+        // An optional include of a tag which failed to load is skipped
+        assert_eq!(members(&membership, "c"), Some(vec![String::from("plains")]));
+    }
+
+    #[test]
+    fn missing_entries_fail_only_when_required() {
+        let biome = test_util::biome(0.8);
+        let (optional, required, nested) = (tag_path("optional"), tag_path("required"), tag_path("nested"));
+        let datapack = datapack(&[
+            (PLAINS, &biome),
+            (&optional, r#"{"values": ["test:plains", {"id": "test:missing", "required": false}]}"#),
+            (&required, r#"{"values": ["test:plains", "test:missing"]}"#),
+            (&nested, r##"{"values": ["#test:required"]}"##)
+        ]);
+
+        let membership = TagMembership::resolve(&[&datapack], DatapackFormat::new(26));
+        assert_eq!(members(&membership, "optional"), Some(vec![String::from("plains")]));
+        assert_eq!(members(&membership, "required"), None);
+        assert!(membership.error(&tag_key("required")).is_some_and(|error| error.contains("test:missing")));
+        assert_eq!(members(&membership, "nested"), None);
+        assert_eq!(membership.errors().len(), 2);
+    }
+}
@@ -0,0 +1,45 @@
+//! Small datapacks built in memory for tests
+
+use crate::data::datapack::{DatapackImport, SerializableDatapack};
+use crate::data::overlay::DatapackFiles;
+
+/// Pack info for the given format and supported range, declaring each overlay with its directory and format range
+pub fn pack_info(pack_format: u32, supported_formats: Option<(u32, u32)>, overlays: &[(&str, u32, u32)]) -> String {
+    let supported_formats = supported_formats
+        .map(|(min, max)| format!(r#", "supported_formats": [{}, {}]"#, min, max))
+        .unwrap_or_default();
+    let entries: Vec<String> = overlays.iter()
+        .map(|(directory, min, max)| format!(r#"{{"directory": "{}", "formats": [{}, {}]}}"#, directory, min, max))
+        .collect();
+
+    format!(
+        r#"{{"pack": {{"pack_format": {}, "description": "Test"{}}}, "overlays": {{"entries": [{}]}}}}"#,
+        pack_format, supported_formats, entries.join(", ")
+    )
+}
+
+/// A biome with no features or spawns, and the given temperature
+pub fn biome(temperature: f64) -> String {
+    format!(r#"{{
+        "carvers": {{}},
+        "downfall": 0.5,
+        "effects": {{"fog_color": 12638463, "sky_color": 8103167, "water_color": 4159204, "water_fog_color": 329011}},
+        "features": [],
+        "has_precipitation": false,
+        "spawn_costs": {{}},
+        "spawners": {{}},
+        "temperature": {}
+    }}"#, temperature)
+}
+
+/// The files of a datapack, keyed by path relative to the pack root
+pub fn files(files: &[(&str, &str)]) -> DatapackFiles {
+    files.iter()
+        .map(|(path, contents)| (String::from(*path), contents.as_bytes().to_vec()))
+        .collect()
+}
+
+/// Imports a datapack from the given files, which have to include pack.mcmeta
+pub fn import(contents: &[(&str, &str)]) -> DatapackImport {
+    SerializableDatapack::from_files("test", &files(contents)).expect("Test datapack should import")
+}
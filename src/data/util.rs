@@ -1,4 +1,4 @@
-use std::cell::{Cell, RefCell};
+use std::cell::Cell;
 use std::error::Error;
 use serde_with::skip_serializing_none;
 use std::fmt::{Debug, Display, Formatter};
@@ -6,9 +6,8 @@ use std::mem;
 use std::num::ParseIntError;
 use std::str::FromStr;
 use lazy_static::lazy_static;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
-use regex::Regex;
 use strum_macros::Display;
 use crate::data::datapack::DatapackError;
 
@@ -45,60 +44,155 @@ pub enum ItemNBT {
 
 //------------//
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// Namespace used by resource locations written without one
+pub const DEFAULT_NAMESPACE: &str = "minecraft";
+
+/// A namespaced id, written as "namespace:path". The path may contain directories separated by '/'
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ResourceLocation {
-    // TODO: custom serialization logic
     namespace: String,
     id: String
 }
 
 impl ResourceLocation {
-    pub(crate) fn new(namespace: String, id: String) -> Self {
+    pub fn new(namespace: String, id: String) -> Self {
         ResourceLocation { namespace, id }
     }
-}
 
-lazy_static! {
-    static ref RESOURCE_LOCATION_REG: Regex = Regex::new(r"^[a-z0-9_.-]+:[a-z0-9_.-]+$").unwrap();
+    pub fn namespace(&self) -> &str { &self.namespace }
+    pub fn id(&self) -> &str { &self.id }
+
+    /// Checks that a namespace only uses a-z, 0-9, '_', '-' and '.'
+    pub fn validate_namespace(namespace: &str) -> Result<(), ResourceLocationError> {
+        Self::validate_part(namespace, 0, false)
+    }
+
+    /// Checks that a path only uses a-z, 0-9, '_', '-', '.' and '/'. As in the game, any sequence of these is allowed,
+    /// even one which could not be a file name
+    pub fn validate_path(path: &str) -> Result<(), ResourceLocationError> {
+        Self::validate_part(path, 0, true)
+    }
+
+    /// Checks a location built with [`ResourceLocation::new`], which does not check its parts itself
+    pub fn validate(&self) -> Result<(), ResourceLocationError> {
+        Self::validate_part(&self.namespace, 0, false)?;
+        Self::validate_part(&self.id, self.namespace.chars().count() + 1, true)
+    }
+
+    /// Checks one part of a location, giving errors a position counted in characters from the start of the whole location
+    fn validate_part(part: &str, offset: usize, is_path: bool) -> Result<(), ResourceLocationError> {
+        let kind = if is_path { LocationPart::Path } else { LocationPart::Namespace };
+
+        for (index, character) in part.chars().enumerate() {
+            let is_allowed = matches!(character, 'a'..='z' | '0'..='9' | '_' | '-' | '.') || (is_path && character == '/');
+
+            if !is_allowed {
+                return Err(ResourceLocationError::Character { part: kind, character, position: offset + index })
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl FromStr for ResourceLocation {
     type Err = ResourceLocationError;
 
+    /// Reads a location such as minecraft:worldgen/plains. Locations without a namespace, or with an empty one, are in minecraft
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if RESOURCE_LOCATION_REG.find(s).is_some() {
-            let split: Vec<String> = s.split(":").map(|s| s.to_string()).collect();
+        let (namespace, id, offset) = match s.split_once(':') {
+            Some(("", id)) => (DEFAULT_NAMESPACE, id, 1),
+            Some((namespace, id)) => {
+                Self::validate_part(namespace, 0, false)?;
+                (namespace, id, namespace.chars().count() + 1)
+            }
+            None => (DEFAULT_NAMESPACE, s, 0)
+        };
 
-            Ok(ResourceLocation {
-                namespace: split.get(0).ok_or_else(|| Self::Err::Parse(format!("Missing first split on ':' in {}", s)))?.clone(),
-                id: split.get(1).ok_or_else(|| Self::Err::Parse(format!("Missing second split on ':' in {}", s)))?.clone()
-            })
-        }
-        else { Err(Self::Err::Syntax(format!("Resource location \"{}\" does not match namespace requirements! a-z, 0-9, '_', '-', '.' are allowed characters, and a single ':' must separate the namespace from the id", s))) }
+        Self::validate_part(id, offset, true)?;
+
+        Ok(ResourceLocation {
+            namespace: String::from(namespace),
+            id: String::from(id)
+        })
     }
 }
 
 impl Display for ResourceLocation {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", format!("{}:{}", &self.namespace, &self.id))
+        write!(f, "{}:{}", &self.namespace, &self.id)
+    }
+}
+
+impl Serialize for ResourceLocation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ResourceLocation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let location = String::deserialize(deserializer)?;
+        location.parse().map_err(|error: ResourceLocationError| de::Error::custom(error.describe(&location)))
     }
 }
 
 //------------//
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocationPart {
+    Namespace,
+    Path
+}
+
+impl Display for LocationPart {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LocationPart::Namespace => write!(f, "namespace"),
+            LocationPart::Path => write!(f, "path")
+        }
+    }
+}
+
+/// Why a resource location could not be read. Positions are counted in characters from the start of the location
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ResourceLocationError {
-    Regex(String),
-    Parse(String),
-    Syntax(String)
+    Character { part: LocationPart, character: char, position: usize }
 }
 
-impl From<regex::Error> for ResourceLocationError {
-    fn from(value: regex::Error) -> Self {
-        ResourceLocationError::Regex(format!("Error Creating regex: {}", value.to_string()))
+impl ResourceLocationError {
+    pub fn position(&self) -> usize {
+        match self {
+            ResourceLocationError::Character { position, .. } => *position
+        }
+    }
+
+    /// Moves the position along, for locations read from part of a longer string such as a #tag reference
+    pub fn offset(self, offset: usize) -> Self {
+        match self {
+            ResourceLocationError::Character { part, character, position } => ResourceLocationError::Character { part, character, position: position + offset }
+        }
+    }
+
+    /// Describes the error along with the location it was found in
+    pub fn describe(&self, location: &str) -> String {
+        format!("Invalid resource location \"{}\": {}", location, self)
+    }
+}
+
+impl Display for ResourceLocationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceLocationError::Character { part: LocationPart::Namespace, character, position } => write!(f,
+                "'{}' at position {} is not allowed in a namespace, only a-z, 0-9, '_', '-' and '.' are", character, position),
+            ResourceLocationError::Character { part: LocationPart::Path, character, position } => write!(f,
+                "'{}' at position {} is not allowed in a path, only a-z, 0-9, '_', '-', '.' and '/' are", character, position)
+        }
     }
 }
 
+impl Error for ResourceLocationError {}
+
 //------------//
 
 #[skip_serializing_none]
@@ -143,7 +237,7 @@ lazy_static! {
 }
 
 // TODO: better implementation of extra
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Text {
     pub text: String,
     pub should_translate: bool,
@@ -179,9 +273,24 @@ impl Text {
         }
     }
 
-    fn from_serializable_discard_formatting(serializable_text: SerializableText) -> Result<Self, DatapackError> {
+    pub(crate) fn from_serializable_discard_formatting(serializable_text: SerializableText) -> Result<Self, DatapackError> {
         use SerializableText::*;
         match serializable_text {
+            List(list) => {
+                let mut texts = list.into_iter()
+                    .map(Self::from_serializable_discard_formatting)
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if texts.is_empty() {
+                    return Err(DatapackError::Deserialize("Text as list cannot be empty".into()))
+                }
+
+                let root = texts.remove(0);
+                Ok(Self {
+                    extra: Some(texts),
+                    .. root
+                })
+            }
             Object { text, translate, .. } => {
                 let mut should_translate = false;
 
@@ -192,7 +301,7 @@ impl Text {
                 }
                 else { None };
 
-                let text = text_opt.ok_or(DatapackError::Deserialize("Text object must have either 'text' or 'translate' fields".parse().unwrap()))?;
+                let text = text_opt.ok_or(DatapackError::Deserialize("Text object must have either 'text' or 'translate' fields".into()))?;
 
                 Ok(Self {
                     text,
@@ -216,20 +325,19 @@ impl TryFrom<Vec<SerializableText>> for Text {
 
     fn try_from(mut value: Vec<SerializableText>) -> Result<Self, Self::Error> {
         match value.len() {
-            0 => Err(DatapackError::Deserialize("Text as list cannot be empty".parse().unwrap())),
+            0 => Err(DatapackError::Deserialize("Text as list cannot be empty".into())),
             1 => Ok(Self::try_from(value.remove(0))?),
             _ => {
                 let ser_root = value.remove(0);
                 let root = Self::try_from(ser_root)?;
 
                 let mut err = None;
-                let extra = value.into_iter().map(|ser_text| {
+                let extra = value.into_iter().filter_map(|ser_text| {
                     match Self::try_from(ser_text) {
                         Ok(t) => Some(t),
                         Err(e) => { err = Some(e); None }
                     }
                 })
-                .filter_map(|t| t)
                 .collect();
                 if let Some(e) = err { return Err(e) }
 
@@ -265,25 +373,24 @@ impl TryFrom<SerializableText> for Text {
                 }
                 else { None };
 
-                let text = text_opt.ok_or(DatapackError::Deserialize("Text object must have either 'text' or 'translate' fields".parse().unwrap()))?;
+                let text = text_opt.ok_or(DatapackError::Deserialize("Text object must have either 'text' or 'translate' fields".into()))?;
 
                 let mut err = None;
 
                 let extra_list: Vec<Text> = if let Some(extra) = extra {
-                    extra.into_iter().map(|ser_text| {
+                    extra.into_iter().filter_map(|ser_text| {
                         match Self::try_from(ser_text) {
                             Ok(t) => Some(t),
                             Err(e) => { err = Some(e); None }
                         }
                     })
-                    .filter_map(|t| t)
                     .collect()
                 } else { Vec::new() };
 
                 if let Some(e) = err { return Err(e) }
 
                 let color = if let Some(c) = color {
-                    match Color::from_str(&*c) {
+                    match Color::from_str(&c) {
                         Ok(c) => Some(c),
                         Err(e) => {
                             err = Some(DatapackError::from(e));
@@ -314,16 +421,49 @@ impl TryFrom<SerializableText> for Text {
     }
 }
 
-impl Into<SerializableText> for Text {
-    fn into(self) -> SerializableText {
-        todo!()
+impl From<Text> for SerializableText {
+    fn from(value: Text) -> Self {
+        let has_formatting = value.color.is_some() || value.font.is_some()
+            || value.is_bold.is_some() || value.is_italic.is_some() || value.is_underlined.is_some()
+            || value.is_strikethrough.is_some() || value.is_obfuscated.is_some();
+        let extra = value.extra.filter(|extra| !extra.is_empty());
+
+        if !value.should_translate && !has_formatting && extra.is_none() {
+            return SerializableText::String(value.text)
+        }
+
+        let (text, translate) = if value.should_translate { (None, Some(value.text)) } else { (Some(value.text), None) };
+
+        SerializableText::Object {
+            text,
+            translate,
+            color: value.color.map(|color| color.to_string()),
+            font: value.font,
+            bold: value.is_bold,
+            italic: value.is_italic,
+            underlined: value.is_underlined,
+            strikethrough: value.is_strikethrough,
+            obfuscated: value.is_obfuscated,
+            extra: extra.map(|extra| extra.into_iter().map(SerializableText::from).collect()),
+        }
     }
 }
 
-impl Into<Vec<Text>> for Text {
-    fn into(self) -> Vec<Text> {
-        let mut txt = Cell::new(self);
-        let mut txt = txt.get_mut();
+impl From<Vec<Text>> for SerializableText {
+    fn from(mut value: Vec<Text>) -> Self {
+        // Mirrors TryFrom<Vec<SerializableText>>: the first entry is the root and the rest are its extras
+        match value.len() {
+            0 => SerializableText::String(String::new()),
+            1 => SerializableText::from(value.remove(0)),
+            _ => SerializableText::List(value.into_iter().map(SerializableText::from).collect())
+        }
+    }
+}
+
+impl From<Text> for Vec<Text> {
+    fn from(value: Text) -> Self {
+        let mut txt = Cell::new(value);
+        let txt = txt.get_mut();
         let extra = mem::take(&mut txt.extra);
 
         match extra {
@@ -360,7 +500,7 @@ lazy_static! {
     static ref WHITE: Color = Color::Name(String::from("white"));
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Color {
     Hex(u32),
     Name(String)
@@ -377,7 +517,7 @@ impl Color {
     }
 
     fn get_color_from_str(name: &str) -> Option<u32> {
-        match &*name {
+        match name {
             // Values obtained from MC wiki
             "black" => Some(0x000000),
             "dark_blue" => Some(0x0000AA),
@@ -400,11 +540,20 @@ impl Color {
     }
 }
 
+impl Display for Color {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Color::Hex(val) => write!(f, "#{:06X}", val),
+            Color::Name(name) => write!(f, "{}", name)
+        }
+    }
+}
+
 impl FromStr for Color {
     type Err = ColorParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Some(_) = Self::get_color_from_str(s) {
+        if Self::get_color_from_str(s).is_some() {
             return Ok(Self::Name(String::from(s)))
         }
         else {
@@ -431,6 +580,77 @@ impl Error for ColorParseError {}
 
 impl From<ParseIntError> for ColorParseError {
     fn from(value: ParseIntError) -> Self {
-        Self::Hex(format!("Could not parse hex value {}", value.to_string()))
+        Self::Hex(format!("Could not parse hex value {}", value))
     }
-}
\ No newline at end of file
+}
+
+//------------//
+
+/// Turns a datapack name into a single file or directory name. Separators and characters which are not allowed
+/// in file names are replaced, and leading dots are dropped so the name can never point outside its directory
+pub fn to_file_name(name: &str) -> String {
+    let file_name: String = name.chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .collect();
+    let file_name = file_name.trim_start_matches('.').trim();
+
+    if file_name.is_empty() { String::from("Untitled") } else { String::from(file_name) }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_stay_inside_their_directory() {
+        assert_eq!(to_file_name("My Pack"), "My Pack");
+        assert_eq!(to_file_name("../../etc/passwd"), "_.._etc_passwd");
+        assert_eq!(to_file_name("a/b\\c:d"), "a_b_c_d");
+        assert_eq!(to_file_name(".."), "Untitled");
+        assert_eq!(to_file_name(" "), "Untitled");
+    }
+
+    #[test]
+    fn locations_without_a_namespace_are_in_minecraft() {
+        let location: ResourceLocation = "plains".parse().unwrap();
+        assert_eq!((location.namespace(), location.id()), (DEFAULT_NAMESPACE, "plains"));
+
+        let location: ResourceLocation = ":worldgen/plains".parse().unwrap();
+        assert_eq!((location.namespace(), location.id()), (DEFAULT_NAMESPACE, "worldgen/plains"));
+    }
+
+    #[test]
+    fn paths_are_any_sequence_of_allowed_characters() {
+        for location in ["test:a//b", "test:/a", "test:a/", "test:", "test:a.b-c_d/0"] {
+            assert!(location.parse::<ResourceLocation>().is_ok(), "{} should be read as the game reads it", location);
+        }
+
+        assert!("te/st:a".parse::<ResourceLocation>().is_err());
+        assert!("test:a:b".parse::<ResourceLocation>().is_err());
+    }
+
+    #[test]
+    fn errors_count_positions_from_the_start_of_the_location() {
+        let error = "teSt:a".parse::<ResourceLocation>().unwrap_err();
+        assert_eq!(error, ResourceLocationError::Character { part: LocationPart::Namespace, character: 'S', position: 2 });
+
+        assert_eq!("test:aB".parse::<ResourceLocation>().unwrap_err().position(), 6);
+        assert_eq!(":aB".parse::<ResourceLocation>().unwrap_err().position(), 2);
+        assert_eq!("aB".parse::<ResourceLocation>().unwrap_err().position(), 1);
+
+        let location = ResourceLocation::new(String::from("test"), String::from("a b"));
+        assert_eq!(location.validate().unwrap_err().position(), 6);
+    }
+
+    #[test]
+    fn locations_round_trip_through_serde() {
+        let location: ResourceLocation = serde_json::from_str(r#""test:worldgen/a.b""#).unwrap();
+        assert_eq!(location, ResourceLocation::new(String::from("test"), String::from("worldgen/a.b")));
+        assert_eq!(serde_json::to_string(&location).unwrap(), r#""test:worldgen/a.b""#);
+
+        let location: ResourceLocation = serde_json::from_str(r#""plains""#).unwrap();
+        assert_eq!(serde_json::to_string(&location).unwrap(), r#""minecraft:plains""#);
+
+        let error = serde_json::from_str::<ResourceLocation>(r#""test:Plains""#).unwrap_err();
+        assert!(error.to_string().contains("position 5"));
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::data::datapack::{Datapack, DatapackError, DatapackFormat, Diagnostic, DirectoryLayout, SerializableDatapack, Severity, DATAPACK_FORMATS};
+
+///////////////////////////////////
+//------ Format Validation ------//
+///////////////////////////////////
+
+/// The formats a datapack declares in pack.mcmeta
+#[derive(Debug, Clone)]
+pub struct FormatDeclarations {
+    pub root_format: DatapackFormat,
+    pub supported_formats: Option<DeclaredRange>,
+    pub overlays: Vec<(String, DeclaredRange)>
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DeclaredRange {
+    pub min_format: DatapackFormat,
+    pub max_format: DatapackFormat,
+    // Whether the range is written as a list or object rather than a single integer
+    pub is_range: bool
+}
+
+impl DeclaredRange {
+    fn overlaps(&self, other: &DeclaredRange) -> bool {
+        self.min_format <= other.max_format && other.min_format <= self.max_format
+    }
+
+    fn contains(&self, other: &DeclaredRange) -> bool {
+        self.min_format <= other.min_format && other.max_format <= self.max_format
+    }
+}
+
+//------------//
+
+impl SerializableDatapack {
+    /// Checks that the formats in pack.mcmeta make sense together, and that each supported format reads the data files
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        match self.get_format_declarations() {
+            Ok(declarations) => {
+                let mut diagnostics = validate_formats(&declarations, &self.get_overlay_paths());
+                diagnostics.extend(validate_directories(&declarations, &self.resource_paths()));
+                diagnostics
+            }
+            Err(error) => vec![Diagnostic::error(error.with_path("pack.mcmeta"))]
+        }
+    }
+
+    /// Validates the datapack before it is written out, failing with every error found so that a pack the game
+    /// would reject or misread is never exported. Warnings do not stop the export
+    pub(crate) fn check_export(&self) -> Result<(), DatapackError> {
+        let errors: Vec<String> = self.validate().into_iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .map(|diagnostic| diagnostic.error.to_string())
+            .collect();
+
+        if errors.is_empty() {
+            return Ok(())
+        }
+
+        Err(DatapackError::Format(format!("The datapack cannot be exported with {} error(s): {}", errors.len(), errors.join("; ")).into()))
+    }
+}
+
+impl Datapack {
+    /// Checks that the formats which would be written to pack.mcmeta make sense together, and that each supported
+    /// format reads the data files
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let declarations = self.get_format_declarations();
+        let mut diagnostics = validate_formats(&declarations, &self.get_overlay_paths());
+        diagnostics.extend(validate_directories(&declarations, &self.resource_paths()));
+        diagnostics
+    }
+}
+
+//------------//
+
+/// Warns about data files which some supported formats do not read, as registries outside of worldgen were renamed to
+/// singular directory names in 1.21. Files stored under both names are read by every format, each version reading its own
+pub fn validate_directories(declarations: &FormatDeclarations, resource_paths: &BTreeSet<String>) -> Vec<Diagnostic> {
+    let (min_format, max_format) = match &declarations.supported_formats {
+        Some(supported_formats) => (supported_formats.min_format, supported_formats.max_format),
+        None => (declarations.root_format, declarations.root_format)
+    };
+
+    // The oldest supported format reading each layout, to name in the warning
+    let mut layouts: BTreeMap<DirectoryLayout, DatapackFormat> = BTreeMap::new();
+    let formats = DATAPACK_FORMATS.iter().copied()
+        .filter(|datapack_format| min_format <= *datapack_format && *datapack_format <= max_format)
+        .chain([declarations.root_format]);
+    for datapack_format in formats {
+        let first = layouts.entry(datapack_format.get_directory_layout()).or_insert(datapack_format);
+        *first = (*first).min(datapack_format);
+    }
+
+    let mut unread: BTreeMap<DirectoryLayout, Vec<&String>> = BTreeMap::new();
+    for path in resource_paths {
+        let Some((layout, other_path)) = DirectoryLayout::of_path(path) else { continue };
+        if !layouts.contains_key(&layout) || (layouts.len() > 1 && !resource_paths.contains(&other_path)) {
+            unread.entry(layout).or_default().push(path);
+        }
+    }
+
+    unread.into_iter()
+        .flat_map(|(layout, paths)| {
+            layouts.iter()
+                .filter(move |(other_layout, _)| **other_layout != layout)
+                .map(move |(other_layout, datapack_format)| Diagnostic::warning(DatapackError::Format(format!(
+                    "{} file(s) such as {} use {} directory names, but format {} ({}) reads {} ones and will not load them",
+                    paths.len(), paths[0], layout, datapack_format.format(), datapack_format, other_layout
+                ).into())))
+        })
+        .collect()
+}
+
+pub fn validate_formats(declarations: &FormatDeclarations, overlay_paths: &BTreeMap<String, BTreeSet<String>>) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    let minimum_range_format = DatapackFormat::get_minimum_overlay_version();
+    let root_format = declarations.root_format;
+
+    // Without supported_formats, the datapack only supports its root format
+    let (min_format, max_format) = match &declarations.supported_formats {
+        Some(supported_formats) => (supported_formats.min_format, supported_formats.max_format),
+        None => (root_format, root_format)
+    };
+
+    if let Some(supported_formats) = &declarations.supported_formats {
+        if min_format > max_format {
+            errors.push(DatapackError::Format(
+                format!("Minimum supported format {} is greater than maximum supported format {}", min_format.format(), max_format.format()).into()));
+        }
+        else if root_format < min_format || root_format > max_format {
+            errors.push(DatapackError::Format(
+                format!("Root format {} is outside the supported formats {} to {}", root_format.format(), min_format.format(), max_format.format()).into()));
+        }
+
+        // supported_formats was added alongside overlays, so older versions only read pack_format
+        if supported_formats.is_range && max_format < minimum_range_format {
+            warnings.push(DatapackError::Format(
+                format!("Versions before {} only read pack_format, so the supported_formats range is ignored", minimum_range_format).into()));
+        }
+    }
+
+    for (name, overlay) in &declarations.overlays {
+        let overlay_error = |message: String| DatapackError::Overlay(message.into()).with_overlay(name);
+
+        if overlay.min_format > overlay.max_format {
+            errors.push(overlay_error(format!("Minimum format {} is greater than maximum format {}", overlay.min_format.format(), overlay.max_format.format())));
+            continue;
+        }
+
+        if overlay.is_range && overlay.min_format < minimum_range_format {
+            errors.push(overlay_error(format!("Format ranges are not supported below format {}", minimum_range_format.format())));
+        }
+
+        if declarations.supported_formats.is_none() && (overlay.min_format != root_format || overlay.max_format != root_format) {
+            errors.push(overlay_error(String::from("Overlays for formats other than the root format need supported_formats to be set")));
+        }
+        else if overlay.min_format < min_format || overlay.max_format > max_format {
+            warnings.push(overlay_error(format!("Overlay formats {} to {} go past the datapack's supported formats {} to {}",
+                overlay.min_format.format(), overlay.max_format.format(), min_format.format(), max_format.format())));
+        }
+    }
+
+    // Overlays listed later replace files from earlier ones. A later overlay which fits inside an earlier one is a
+    // deliberate override for some of its formats, but any other overlap sharing files depends on the order they are listed in
+    for (index, (name, overlay)) in declarations.overlays.iter().enumerate() {
+        for (other_name, other_overlay) in &declarations.overlays[index + 1..] {
+            if !overlay.overlaps(other_overlay) || overlay.contains(other_overlay) {
+                continue;
+            }
+
+            let (Some(paths), Some(other_paths)) = (overlay_paths.get(name), overlay_paths.get(other_name)) else { continue };
+            let shared: Vec<&String> = paths.intersection(other_paths).collect();
+
+            if let Some(first) = shared.first() {
+                warnings.push(DatapackError::Overlay(format!(
+                    "Overlays {} and {} overlap and both contain {} file(s) such as {}, so the result depends on their order",
+                    name, other_name, shared.len(), first
+                ).into()).with_overlay(name));
+            }
+        }
+    }
+
+    errors.into_iter().map(|error| Diagnostic::error(error.with_path("pack.mcmeta")))
+        .chain(warnings.into_iter().map(|warning| Diagnostic::warning(warning.with_path("pack.mcmeta"))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::test_util;
+    use super::*;
+
+    fn directory_warnings(supported_formats: Option<(u32, u32)>, paths: &[&str]) -> Vec<String> {
+        let pack_info = test_util::pack_info(26, supported_formats, &[]);
+        let mut contents = vec![("pack.mcmeta", pack_info.as_str())];
+        contents.extend(paths.iter().map(|path| (*path, "")));
+
+        test_util::import(&contents).datapack.validate().into_iter()
+            .map(|diagnostic| diagnostic.error.context().message.clone())
+            .collect()
+    }
+
+    fn range(min_format: u32, max_format: u32, is_range: bool) -> DeclaredRange {
+        DeclaredRange { min_format: DatapackFormat::new(min_format), max_format: DatapackFormat::new(max_format), is_range }
+    }
+
+    fn format_diagnostics(supported_formats: Option<DeclaredRange>, overlays: &[(&str, DeclaredRange, &[&str])]) -> Vec<(Severity, String)> {
+        let declarations = FormatDeclarations {
+            root_format: DatapackFormat::new(26),
+            supported_formats,
+            overlays: overlays.iter().map(|(name, range, _)| (String::from(*name), *range)).collect()
+        };
+        let overlay_paths = overlays.iter()
+            .map(|(name, _, paths)| (String::from(*name), paths.iter().map(|path| String::from(*path)).collect()))
+            .collect();
+
+        validate_formats(&declarations, &overlay_paths).into_iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.error.context().message.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn root_format_has_to_be_supported() {
+        assert!(format_diagnostics(Some(range(18, 26, true)), &[]).is_empty());
+
+        let diagnostics = format_diagnostics(Some(range(18, 22, true)), &[]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].0, Severity::Error);
+        assert!(diagnostics[0].1.contains("outside the supported formats"), "{}", diagnostics[0].1);
+
+        let diagnostics = format_diagnostics(Some(range(30, 18, true)), &[]);
+        assert!(diagnostics[0].1.contains("greater than"), "{}", diagnostics[0].1);
+    }
+
+    #[test]
+    fn overlays_for_other_formats_need_supported_formats() {
+        assert!(format_diagnostics(None, &[("current", range(26, 26, true), &[])]).is_empty());
+
+        let diagnostics = format_diagnostics(None, &[("older", range(18, 26, true), &[])]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].0, Severity::Error);
+        assert!(diagnostics[0].1.contains("need supported_formats"), "{}", diagnostics[0].1);
+
+        assert!(format_diagnostics(Some(range(18, 26, true)), &[("older", range(18, 26, true), &[])]).is_empty());
+    }
+
+    #[test]
+    fn overlapping_overlays_sharing_files_warn() {
+        let shared: &[&str] = &["data/test/worldgen/biome/x.json"];
+        let diagnostics = format_diagnostics(Some(range(18, 41, true)), &[("a", range(18, 26, true), shared), ("b", range(22, 41, true), shared)]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].0, Severity::Warning);
+        assert!(diagnostics[0].1.contains("Overlays a and b overlap"), "{}", diagnostics[0].1);
+
+        // Overlays which share no files, or where the later one deliberately overrides part of the earlier one
+        assert!(format_diagnostics(Some(range(18, 41, true)), &[("a", range(18, 26, true), shared), ("b", range(22, 41, true), &[])]).is_empty());
+        assert!(format_diagnostics(Some(range(18, 41, true)), &[("a", range(18, 41, true), shared), ("b", range(22, 26, true), shared)]).is_empty());
+    }
+
+    #[test]
+    fn ranges_are_not_read_by_integer_only_formats() {
+        let diagnostics = format_diagnostics(Some(range(7, 15, true)), &[]);
+        assert!(diagnostics.iter().any(|(severity, message)| *severity == Severity::Warning && message.contains("only read pack_format")), "{:?}", diagnostics);
+
+        let diagnostics = format_diagnostics(Some(range(7, 26, true)), &[("legacy", range(7, 15, true), &[])]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].0, Severity::Error);
+        assert!(diagnostics[0].1.contains("not supported below format 18"), "{}", diagnostics[0].1);
+
+        assert!(format_diagnostics(Some(range(7, 26, true)), &[("legacy", range(7, 7, false), &[])]).is_empty());
+    }
+
+    #[test]
+    fn packs_with_errors_are_not_exported() {
+        let pack_info = test_util::pack_info(26, Some((18, 22)), &[]);
+        let import = test_util::import(&[("pack.mcmeta", &pack_info)]);
+        let filepath = std::env::temp_dir().join(format!("gaia_invalid_export_{}.zip", std::process::id()));
+
+        assert!(import.datapack.to_zip(filepath.to_str().unwrap()).is_err());
+        assert!(!filepath.exists());
+        assert!(import.datapack.to_directory(filepath.to_str().unwrap()).is_err());
+        assert!(!filepath.exists());
+    }
+
+    #[test]
+    fn warns_about_directories_a_supported_format_does_not_read() {
+        assert!(directory_warnings(None, &["data/test/functions/a.mcfunction"]).is_empty());
+
+        let warnings = directory_warnings(None, &["data/test/function/a.mcfunction"]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("format 26"), "{}", warnings[0]);
+
+        let warnings = directory_warnings(Some((26, 48)), &["data/test/tags/items/a.json", "data/test/worldgen/biome/a.json"]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("format 48"), "{}", warnings[0]);
+
+        // Packs supporting both layouts can store each file under both names
+        assert!(directory_warnings(Some((26, 48)), &["data/test/tags/items/a.json", "data/test/tags/item/a.json"]).is_empty());
+    }
+
+    #[test]
+    fn paths_map_between_layouts() {
+        assert_eq!(DirectoryLayout::of_path("data/test/structures/house.nbt"), Some((DirectoryLayout::Plural, String::from("data/test/structure/house.nbt"))));
+        assert_eq!(DirectoryLayout::of_path("data/test/tags/function/tick.json"), Some((DirectoryLayout::Singular, String::from("data/test/tags/functions/tick.json"))));
+        assert_eq!(DirectoryLayout::of_path("data/test/worldgen/structure/house.json"), None);
+        assert_eq!(DatapackFormat::new(26).get_directory_layout(), DirectoryLayout::Plural);
+        assert_eq!(DatapackFormat::new(60).get_directory_layout(), DirectoryLayout::Singular);
+    }
+}
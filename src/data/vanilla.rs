@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use serde_json::Value;
+use crate::data::biome::{BiomeData, SerializableBiomeData};
+use crate::data::datapack::{Datapack, DatapackError, DatapackFormat, FileElement, SerializableDataElement, SerializableDatapack, SyncState, DATAPACK_FORMATS};
+use crate::data::diff::{self, ChangeKind, DatapackDiff};
+use crate::data::util::ResourceLocation;
+
+//////////////////////////////
+//------ Vanilla Data ------//
+//////////////////////////////
+
+/// The default game data, kept as a read only layer below the edited datapack. Each copy is keyed by the format
+/// it was made for, and covers that format along with any unknown formats after it, up to the next known format
+#[derive(Debug, Clone, Default)]
+pub struct VanillaData {
+    datapacks: BTreeMap<DatapackFormat, SerializableDatapack>
+}
+
+impl VanillaData {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a copy of the vanilla data from each zip
+    pub fn from_zips(filepaths: &[&str]) -> Result<Self, DatapackError> {
+        let mut vanilla = Self::new();
+
+        for filepath in filepaths {
+            vanilla.insert(SerializableDatapack::from_zip(filepath)?.datapack);
+        }
+
+        Ok(vanilla)
+    }
+
+    /// Adds a copy of the vanilla data, replacing any copy for the same format
+    pub fn insert(&mut self, datapack: SerializableDatapack) {
+        self.datapacks.insert(datapack.pack_format(), datapack);
+    }
+
+    pub fn is_empty(&self) -> bool { self.datapacks.is_empty() }
+
+    /// Gets every copy of the vanilla data, oldest format first
+    pub fn datapacks(&self) -> impl Iterator<Item = &SerializableDatapack> {
+        self.datapacks.values()
+    }
+
+    /// Gets the vanilla data used for a format, or None if no copy covers it. Known formats only use a copy made
+    /// for them, while unknown formats, such as snapshots, use the copy for the closest known format before them
+    pub fn get(&self, datapack_format: DatapackFormat) -> Option<&SerializableDatapack> {
+        let (vanilla_format, datapack) = self.datapacks.range(..=datapack_format).next_back()?;
+
+        let is_covered = DATAPACK_FORMATS.iter()
+            .all(|known_format| known_format <= vanilla_format || *known_format > datapack_format);
+
+        is_covered.then_some(datapack)
+    }
+
+    /// Gets the vanilla data used for a format, failing with an error naming the format if no copy covers it
+    pub fn require(&self, datapack_format: DatapackFormat) -> Result<&SerializableDatapack, DatapackError> {
+        self.get(datapack_format)
+            .ok_or_else(|| DatapackError::Format(format!("No vanilla data is loaded for format {}", datapack_format).into()))
+    }
+
+    /// Gets the format of the vanilla data used for each format a datapack supports
+    pub fn get_formats(&self, datapack: &SerializableDatapack) -> BTreeMap<DatapackFormat, DatapackFormat> {
+        datapack.get_supported_formats().into_iter()
+            .filter_map(|datapack_format| Some((datapack_format, self.get(datapack_format)?.pack_format())))
+            .collect()
+    }
+
+    /// Compares a datapack, as the given format loads it, with the vanilla data for that format
+    pub fn compare(&self, datapack: &SerializableDatapack, datapack_format: DatapackFormat) -> Result<VanillaComparison, DatapackError> {
+        let vanilla = self.require(datapack_format)?;
+
+        let resolved = datapack.for_format(datapack_format);
+        let paths = resolved.resource_paths();
+        let mut diff = vanilla.diff(&resolved)?;
+
+        // Vanilla resources the datapack leaves alone are not part of the comparison
+        diff.resources.retain(|resource| resource.change != ChangeKind::Removed && paths.contains(&resource.path));
+
+        let changes: BTreeMap<&String, ChangeKind> = diff.resources.iter().map(|resource| (&resource.path, resource.change)).collect();
+        let origins = paths.iter()
+            .map(|path| {
+                let origin = match changes.get(path) {
+                    Some(ChangeKind::Added) => ResourceOrigin::New,
+                    Some(_) => ResourceOrigin::Override,
+                    None => ResourceOrigin::Unchanged
+                };
+                (path.clone(), origin)
+            })
+            .collect();
+
+        Ok(VanillaComparison {
+            vanilla_format: vanilla.pack_format(),
+            origins,
+            diff
+        })
+    }
+
+    /// Sets one field of a biome back to its vanilla value, or removes it if vanilla does not have it. Every stored copy
+    /// of a synced biome is changed, so that each format sees the vanilla value, where a biome edited per format only
+    /// changes the copy the given format loads
+    pub fn reset_field(&self, datapack: &Datapack, path: &str, field: &str, datapack_format: DatapackFormat) -> Result<(ResourceLocation, BiomeData), DatapackError> {
+        let (resource_location, biome) = datapack.biomes().iter()
+            .find(|(resource_location, _)| SerializableBiomeData::get_file_path(resource_location) == path)
+            .ok_or_else(|| DatapackError::File(format!("{} is not a biome, only biome fields can be reset to vanilla", path).into()))?;
+
+        let vanilla_biome = self.require(datapack_format)?
+            .biome(resource_location)
+            .and_then(|data_holder| data_holder.root_data())
+            .ok_or_else(|| DatapackError::File(format!("{} is not part of the vanilla data", path).into()))?;
+
+        let keys = diff::parse_field(field)
+            .ok_or_else(|| DatapackError::File(format!("{} is not a valid field", field).into()))?;
+        let vanilla_value: Value = serde_json::from_str(&vanilla_biome.serialize()?)?;
+        let vanilla_field = diff::get_json_field(&vanilla_value, &keys).cloned();
+
+        let reset = |data: &SerializableBiomeData| {
+            let mut value: Value = serde_json::from_str(&data.serialize()?)?;
+
+            // Arrays are compared index by index, so a field inside an array may have nowhere to go in a shorter copy
+            if !diff::set_json_field(&mut value, &keys, vanilla_field.clone()) {
+                return Err(DatapackError::File(format!(
+                    "{} of {} cannot be reset to vanilla on its own, as the biome does not line up with vanilla there. Reset a field holding it instead",
+                    field, path
+                ).into()))
+            }
+
+            Ok::<_, DatapackError>(*SerializableBiomeData::deserialize(value.to_string())?)
+        };
+
+        let data = match biome.sync_state() {
+            SyncState::Synced => biome.data().try_map(reset)?,
+            SyncState::Manual => biome.data().try_map_loaded(datapack.overlays(), datapack_format, reset)?
+        };
+
+        Ok((resource_location.clone(), BiomeData::new(data, biome.sync_state())))
+    }
+}
+
+//------------//
+
+/// Where a resource of the edited datapack stands against the vanilla data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceOrigin {
+    // Not part of the vanilla data
+    New,
+    // Replaces a vanilla resource with different contents
+    Override,
+    // Replaces a vanilla resource with the same contents
+    Unchanged
+}
+
+impl Display for ResourceOrigin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceOrigin::New => write!(f, "new"),
+            ResourceOrigin::Override => write!(f, "overrides vanilla"),
+            ResourceOrigin::Unchanged => write!(f, "same as vanilla")
+        }
+    }
+}
+
+/// How a datapack differs from the vanilla data for one format
+#[derive(Debug, Clone, Default)]
+pub struct VanillaComparison {
+    /// The format of the vanilla data which was compared against
+    pub vanilla_format: DatapackFormat,
+    /// The origin of every data file in the datapack, keyed by path
+    pub origins: BTreeMap<String, ResourceOrigin>,
+    /// Resources which are new or override vanilla with different contents, with the vanilla data as the old version
+    pub diff: DatapackDiff
+}
+
+impl VanillaComparison {
+    /// Groups the data files by registry, in the same way as diffs
+    pub fn registries(&self) -> BTreeMap<String, Vec<(&String, ResourceOrigin)>> {
+        let mut registries: BTreeMap<String, Vec<(&String, ResourceOrigin)>> = BTreeMap::new();
+
+        for (path, origin) in &self.origins {
+            registries.entry(diff::get_registry(path)).or_default().push((path, *origin));
+        }
+
+        registries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::data::test_util;
+    use super::*;
+
+    fn biome_with_features(features: Value) -> String {
+        let mut biome: Value = serde_json::from_str(&test_util::biome(0.8)).unwrap();
+        biome["features"] = features;
+        biome.to_string()
+    }
+
+    fn vanilla() -> VanillaData {
+        let pack_info = test_util::pack_info(26, None, &[]);
+        let biome = biome_with_features(json!([["minecraft:a", "minecraft:b", "minecraft:c", "minecraft:d"]]));
+
+        let mut vanilla = VanillaData::new();
+        vanilla.insert(test_util::import(&[("pack.mcmeta", &pack_info), ("data/minecraft/worldgen/biome/plains.json", &biome)]).datapack);
+        vanilla
+    }
+
+    #[test]
+    fn copies_only_cover_their_own_format() {
+        let vanilla = vanilla();
+
+        assert!(vanilla.get(DatapackFormat::new(26)).is_some());
+        // Unknown formats, such as snapshots, are closest to the last known format before them
+        assert!(vanilla.get(DatapackFormat::new(30)).is_some());
+        assert!(vanilla.get(DatapackFormat::new(15)).is_none());
+        assert!(vanilla.get(DatapackFormat::new(41)).is_none());
+        assert!(vanilla.require(DatapackFormat::new(15)).is_err());
+    }
+
+    #[test]
+    fn reset_fails_when_the_field_has_nowhere_to_go() {
+        let vanilla = vanilla();
+
+        let pack_info = test_util::pack_info(26, None, &[]);
+        let biome = biome_with_features(json!([["minecraft:a"]]));
+        let import = test_util::import(&[("pack.mcmeta", &pack_info), ("data/minecraft/worldgen/biome/plains.json", &biome)]);
+        let datapack = Datapack::try_from(import.datapack).unwrap();
+
+        let path = "data/minecraft/worldgen/biome/plains.json";
+        let format = DatapackFormat::new(26);
+
+        assert!(vanilla.reset_field(&datapack, path, "features[0][3]", format).is_err());
+
+        let (_, biome) = vanilla.reset_field(&datapack, path, "features[0][1]", format).unwrap();
+        let value: Value = serde_json::from_str(&biome.data().root_data().unwrap().serialize().unwrap()).unwrap();
+        assert_eq!(value["features"], json!([["minecraft:a", "minecraft:b"]]));
+    }
+
+    #[test]
+    fn reset_changes_every_copy_only_of_synced_biomes() {
+        let vanilla = vanilla();
+
+        let pack_info = test_util::pack_info(26, Some((18, 26)), &[("old", 18, 18)]);
+        let biome = biome_with_features(json!([]));
+        let import = test_util::import(&[
+            ("pack.mcmeta", &pack_info),
+            ("data/minecraft/worldgen/biome/plains.json", &biome),
+            ("old/data/minecraft/worldgen/biome/plains.json", &biome)
+        ]);
+        let mut datapack = Datapack::try_from(import.datapack).unwrap();
+
+        let path = "data/minecraft/worldgen/biome/plains.json";
+        let location = ResourceLocation::new(String::from("minecraft"), String::from("plains"));
+        let features = |biome: &BiomeData| {
+            let root: Value = serde_json::from_str(&biome.data().root_data().unwrap().serialize().unwrap()).unwrap();
+            let overlay: Value = serde_json::from_str(&biome.data().get_overlay_data()["old"].serialize().unwrap()).unwrap();
+            (root["features"].as_array().unwrap().len(), overlay["features"].as_array().unwrap().len())
+        };
+
+        // Biomes which already differ between formats start out edited per format
+        assert_eq!(datapack.biomes()[&location].sync_state(), SyncState::Manual);
+        let (_, biome) = vanilla.reset_field(&datapack, path, "features", DatapackFormat::new(26)).unwrap();
+        assert_eq!(features(&biome), (1, 0));
+
+        datapack.biomes_mut().get_mut(&location).unwrap().set_sync_state(SyncState::Synced);
+        let (_, biome) = vanilla.reset_field(&datapack, path, "features", DatapackFormat::new(26)).unwrap();
+        assert_eq!(features(&biome), (1, 1));
+    }
+}
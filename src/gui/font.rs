@@ -1,6 +1,5 @@
-use iced::{Element, Font, widget};
+use iced::{Font, widget};
 use iced::widget::Text;
-use crate::gui::window::Message;
 
 pub const PLUS_ICON: char = '+';//'\u{F196}';
 pub const TRASH_ICON: char = '-';//'\u{E800}';
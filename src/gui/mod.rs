@@ -1,5 +1,5 @@
 pub(crate) mod window;
-mod theme;
 mod pack_info;
 mod widgets;
-mod font;
\ No newline at end of file
+mod font;
+mod recovery;
\ No newline at end of file
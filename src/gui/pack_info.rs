@@ -1,9 +1,10 @@
-use std::cmp::min;
 use iced::{Alignment, Application, Element, Font, widget};
 use iced::widget::{Column, container, Row, Rule};
+use serde::{Deserialize, Serialize};
 use strum_macros::Display;
-use crate::data::datapack::{Datapack, DatapackFormat, Overlay};
+use crate::data::datapack::{Datapack, DatapackFormat, Diagnostic, Filter, Overlay, Severity};
 use crate::data::{datapack, util};
+use crate::data::history::{DatapackEdit, EditHistory, FormatSelection};
 use crate::gui::widgets::{self, DropdownEvent, DropdownOption, DropdownState, ListEvent, ListInlineState, ListSettings, ListState, SPACING_LARGE, WidgetCallbackChannel};
 use crate::gui::widgets::MoveDirection::{Down, Up};
 use crate::gui::window;
@@ -18,21 +19,37 @@ pub enum DatapackCallbackType {
     DatapackName(String),
     Description(DescriptionEvent),
     Format(FormatEvent),
-    Overlay(ListEvent<OverlayEditEvent>)
+    Overlay(ListEvent<OverlayEditEvent>),
+    Filter(ListEvent<FilterEditEvent>)
 }
 
 //------------//
 
 pub fn handle_datapack_update(
     datapack: &mut Datapack,
+    history: &mut EditHistory,
     callback_type: DatapackCallbackType,
     mut pack_info_state: PackInfoState
 ) -> PackInfoState {
     use DatapackCallbackType::*;
     match callback_type {
-        DatapackName(name) => datapack.set_name(&*name),
+        DatapackName(name) => {
+            let before = String::from(datapack.name());
+            datapack.set_name(&name);
+
+            if before != name {
+                history.record(DatapackEdit::Name { before, after: name }, Some(String::from("name")));
+            }
+        }
         Description(event) => match event {
             DescriptionEvent::Content(list_event) => {
+                let before = datapack.description().clone();
+                // Typing into a text field is undone as a whole rather than one character at a time
+                let group = match &list_event {
+                    ListEvent::Edit(TextEditEvent::Text(_), index) => Some(format!("description_{}", index)),
+                    _ => None
+                };
+
                 let list_event = widgets::handle_list_event(list_event, datapack.description_mut(), &mut pack_info_state.description_state.collapsed_state);
 
                 use ListEvent::*;
@@ -51,7 +68,8 @@ pub fn handle_datapack_update(
                         }
                     }
                     Edit(edit_event, index) => {
-                        let mut text = datapack.description_mut().get_mut(index).expect("List edit event should not return values out of range");
+                        // Edit events come from the list itself, so this only fails if the list changed in between
+                        let Some(text) = datapack.description_mut().get_mut(index) else { return pack_info_state };
 
                         use TextEditEvent::*;
                         match edit_event {
@@ -67,12 +85,18 @@ pub fn handle_datapack_update(
                     }
                     _ => {}
                 }
+
+                if before != *datapack.description() {
+                    history.record(DatapackEdit::Description { before, after: datapack.description().clone() }, group);
+                }
             },
             DescriptionEvent::Type(index, type_event) => {
                 widgets::handle_dropdown_event(type_event, &mut pack_info_state.description_state.text_type_state[index]);
             }
         },
         Format(format_event) => {
+            let before = FormatSelection::from_datapack(datapack);
+
             use FormatEvent::*;
             match format_event {
                 Type(event) => widgets::handle_dropdown_event(event, &mut pack_info_state.format_state.format_type),
@@ -85,37 +109,128 @@ pub fn handle_datapack_update(
                     widgets::handle_dropdown_event(event, state);
                 }
             }
+
+            let format_state = &pack_info_state.format_state;
+            let root = format_state.root.selected;
+            datapack.set_root_format(root);
+
+            if is_single_format(&pack_info_state) {
+                datapack.set_min_format(root);
+                datapack.set_max_format(root);
+            }
+            else {
+                datapack.set_min_format(format_state.min.selected);
+                datapack.set_max_format(format_state.max.selected);
+            }
+
+            let after = FormatSelection::from_datapack(datapack);
+            if before != after {
+                history.record(DatapackEdit::Formats { before, after }, None);
+            }
         }
         Overlay(list_event) => {
+            let before = datapack.overlays().clone();
+            // Renames are recorded on their own, as undoing them has to move the overlay's data back
+            let is_rename = matches!(&list_event, ListEvent::Edit(OverlayEditEvent::Name(_) | OverlayEditEvent::SubmitName, _));
             let list_event = widgets::handle_list_event(list_event, datapack.overlays_mut(), &mut pack_info_state.overlay_state.list_state);
 
+            let overlay_format_states = &mut pack_info_state.overlay_state.overlay_format_states;
+
             use ListEvent::*;
             match list_event {
+                Add(index) => {
+                    // New overlays get a name of their own, so that their data is never mixed with another overlay's
+                    let name = (1..)
+                        .map(|number| format!("overlay_{}", number))
+                        .find(|name| datapack.overlays().iter().all(|overlay| overlay.name != *name))
+                        .expect("There should be a free overlay name");
+                    datapack.overlays_mut()[index].name = name;
+
+                    overlay_format_states.insert(index, OverlayFormatState::new(&datapack.overlays()[index]));
+                }
+                Remove(index) => { overlay_format_states.remove(index); },
+                Move(direction, index) => match direction {
+                    Up => if index > 0 {
+                        overlay_format_states.swap(index, index - 1);
+                    },
+                    Down => if index < overlay_format_states.len() - 1 {
+                        overlay_format_states.swap(index, index + 1);
+                    }
+                }
                 Edit(edit_event, index) => {
-                    let mut overlay = datapack.overlays_mut().get_mut(index).expect("List edit event should not return values out of range");
-
                     use OverlayEditEvent::*;
                     match edit_event {
-                        Name(name) => overlay.name = name,
+                        // The name is only applied once it is submitted, so that typing never passes through another overlay's name
+                        Name(name) => {
+                            overlay_format_states[index].name = name;
+                            overlay_format_states[index].name_error = None;
+                        }
+                        SubmitName => {
+                            let previous = datapack.overlays()[index].name.clone();
+                            let name = overlay_format_states[index].name.clone();
+
+                            match datapack.rename_overlay(index, name.clone()) {
+                                Ok(()) => if previous != name {
+                                    history.record(DatapackEdit::RenameOverlay { index, before: previous, after: name }, None);
+                                }
+                                Err(error) => overlay_format_states[index].name_error = Some(error.context().message.to_string())
+                            }
+                        }
                         Format(format_event) => {
                             use FormatEvent::*;
                             match format_event {
-                                Type(event) => widgets::handle_dropdown_event(event, &mut pack_info_state.overlay_state.overlay_format_states[index].format_type),
+                                Type(event) => widgets::handle_dropdown_event(event, &mut overlay_format_states[index].format_type),
                                 Format(format_number, event) => {
                                     let state = match format_number {
-                                        0 => &mut pack_info_state.overlay_state.overlay_format_states[index].min,
-                                        _ => &mut pack_info_state.overlay_state.overlay_format_states[index].max,
+                                        0 => &mut overlay_format_states[index].min,
+                                        _ => &mut overlay_format_states[index].max,
                                     };
                                     widgets::handle_dropdown_event(event, state);
                                 }
                             }
+
+                            let (Some(format_state), Some(overlay)) = (overlay_format_states.get(index), datapack.overlays_mut().get_mut(index))
+                                else { return pack_info_state };
+
+                            overlay.min_format = format_state.min.selected;
+                            overlay.max_format = if format_state.format_type.selected == FormatType::Single { overlay.min_format }
+                                else { format_state.max.selected };
                         }
                     }
                 }
                 _ => {}
             }
+
+            if !is_rename && before != *datapack.overlays() {
+                history.record(DatapackEdit::Overlays { before, after: datapack.overlays().clone() }, None);
+            }
         },
+        Filter(list_event) => {
+            let before = datapack.filters().clone();
+            let group = match &list_event {
+                ListEvent::Edit(FilterEditEvent::Namespace(_), index) => Some(format!("filter_namespace_{}", index)),
+                ListEvent::Edit(FilterEditEvent::Path(_), index) => Some(format!("filter_path_{}", index)),
+                _ => None
+            };
+
+            let list_event = widgets::handle_list_event(list_event, datapack.filters_mut(), &mut pack_info_state.filter_state);
+
+            if let ListEvent::Edit(edit_event, index) = list_event {
+                let Some(filter) = datapack.filters_mut().get_mut(index) else { return pack_info_state };
+
+                match edit_event {
+                    FilterEditEvent::Namespace(namespace) => filter.set_namespace(namespace),
+                    FilterEditEvent::Path(path) => filter.set_path(path),
+                }
+            }
+
+            if before != *datapack.filters() {
+                history.record(DatapackEdit::Filters { before, after: datapack.filters().clone() }, group);
+            }
+        }
     }
+
+    pack_info_state.validation = datapack.validate();
     pack_info_state
 }
 
@@ -128,6 +243,8 @@ pub struct PackInfoState {
     description_state: DescriptionState,
     format_state: DatapackFormatState,
     overlay_state: OverlayState,
+    filter_state: ListState,
+    validation: Vec<Diagnostic>,
 }
 
 impl PackInfoState {
@@ -142,15 +259,47 @@ impl PackInfoState {
             },
             format_state: DatapackFormatState::new(datapack),
             overlay_state: OverlayState::new(datapack),
+            filter_state: ListState::new(datapack.filters().len()),
+            validation: datapack.validate(),
+        }
+    }
+
+    /// Restores a saved layout. Lists which no longer have the same number of entries are left expanded
+    pub fn with_layout(mut self, layout: &PackInfoLayout) -> Self {
+        let restore = |list_state: &mut ListState, saved: &ListState| {
+            if list_state.len() == saved.len() {
+                *list_state = saved.clone();
+            }
+        };
+
+        restore(&mut self.description_state.collapsed_state, &layout.description);
+        restore(&mut self.overlay_state.list_state, &layout.overlays);
+        restore(&mut self.filter_state, &layout.filters);
+        self
+    }
+
+    pub fn layout(&self) -> PackInfoLayout {
+        PackInfoLayout {
+            description: self.description_state.collapsed_state.clone(),
+            overlays: self.overlay_state.list_state.clone(),
+            filters: self.filter_state.clone()
         }
     }
 }
 
+/// Which lists of the pack info view are collapsed, saved with a project so that it opens the way it was left
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackInfoLayout {
+    description: ListState,
+    overlays: ListState,
+    filters: ListState
+}
+
 pub fn pack_info_gui<'a>(
     datapack: &Datapack,
     pack_info_state: &PackInfoState
 ) -> Element<'a, Message, <ApplicationWindow as Application>::Theme> {
-    let name = widgets::text_editor("Name", "Name", &datapack.name(),
+    let name = widgets::text_editor("Name", "Name", datapack.name(),
         |s| WidgetCallbackChannel::PackInfo(DatapackCallbackType::DatapackName(s)));
 
     let description = widgets::list("Description", datapack.description(), &pack_info_state.description_state.collapsed_state, &pack_info_state,
@@ -173,19 +322,34 @@ pub fn pack_info_gui<'a>(
         },
         |list_event| WidgetCallbackChannel::PackInfo(DatapackCallbackType::Overlay(list_event)));
 
+    let filters = widgets::list("Filters", datapack.filters(), &pack_info_state.filter_state, &pack_info_state,
+        ListSettings {
+            required: false,
+            inline_state: ListInlineState::Extended(Box::new(|f, i, _| filter_gui(f, i)))
+        },
+        |filter: &Filter, _, collapsed, _: &&PackInfoState| {
+            if collapsed { Some(widget::text(filter_summary(filter)).into()) } else { None }
+        },
+        |list_event| WidgetCallbackChannel::PackInfo(DatapackCallbackType::Filter(list_event)));
+
     let mut widget = Column::new()
         .push(widget::container(name).max_width(widgets::MAX_CONTENT_WIDTH))
         .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
         .push(widget::container(description).max_width(widgets::MAX_CONTENT_WIDTH))
         .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
         .push(widget::container(format).max_width(widgets::MAX_CONTENT_WIDTH))
+        .push(validation_gui(pack_info_state))
         .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
         .spacing(SPACING_LARGE);
 
     if !is_single_format(pack_info_state) {
-        widget = widget.push(widget::container(overlays).max_width(widgets::MAX_CONTENT_WIDTH));
+        widget = widget
+            .push(widget::container(overlays).max_width(widgets::MAX_CONTENT_WIDTH))
+            .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH));
     }
 
+    widget = widget.push(widget::container(filters).max_width(widgets::MAX_CONTENT_WIDTH));
+
     widget::container(widget)
         .into()
 }
@@ -203,7 +367,7 @@ fn text_gui_extended<'a>(
 
     let text_editor = Row::new()
         .push(widget::text("Text:"))
-        .push(widget::text_input("Text", &*text.text.replace("\n", "\\n"))
+        .push(widget::text_input("Text", &text.text.replace("\n", "\\n"))
             .on_input(move |s| {
                 Message::Input(WidgetCallbackChannel::PackInfo(DatapackCallbackType::Description(DescriptionEvent::Content(
                     ListEvent::Edit(TextEditEvent::Text(s.replace("\\n", "\n")), index)))))
@@ -271,7 +435,7 @@ fn text_header_widget<'a>(
             .push(dropdown);
 
         if collapsed {
-            header = header.push(widget::text(&text.text.replace("\n", "\\n")))
+            header = header.push(widget::text(text.text.replace("\n", "\\n")))
         }
 
         widget::container(header
@@ -292,7 +456,7 @@ pub enum DescriptionEvent {
 //------------//
 
 #[derive(Debug, Clone)]
-enum TextEditEvent {
+pub enum TextEditEvent {
     Text(String),
 
     Bold(Option<bool>),
@@ -305,7 +469,7 @@ enum TextEditEvent {
 //------------//
 
 #[derive(Clone, Copy, Debug, Default, Display)]
-enum TextType {
+pub enum TextType {
     #[default]
     String,
     Object
@@ -388,6 +552,25 @@ fn format_selector<'a>(
     widget.into()
 }
 
+fn validation_gui<'a>(
+    pack_info_state: &PackInfoState
+) -> Element<'a, Message, <ApplicationWindow as Application>::Theme> {
+    let messages = pack_info_state.validation.iter().map(|diagnostic| {
+        let color = match diagnostic.severity {
+            Severity::Warning => window::WARNING_COLOR,
+            Severity::Error => window::ERROR_COLOR,
+        };
+
+        widget::text(diagnostic.to_string())
+            .style(color)
+            .into()
+    });
+
+    Column::with_children(messages)
+        .spacing(widgets::SPACING_SMALL)
+        .into()
+}
+
 fn is_single_format(pack_info_state: &PackInfoState) -> bool {
     pack_info_state.format_state.format_type.selected == FormatType::Single
 }
@@ -395,7 +578,7 @@ fn is_single_format(pack_info_state: &PackInfoState) -> bool {
 //------------//
 
 #[derive(Copy, Clone, Debug, Display, Default, Eq, PartialEq)]
-enum FormatType {
+pub enum FormatType {
     #[default]
     Single,
     Range
@@ -462,9 +645,14 @@ fn overlay_gui<'a>(
     index: usize,
     pack_info_state: &PackInfoState
 ) -> Option<Element<'a, Message, <ApplicationWindow as Application>::Theme>> {
-    let text_editor = widgets::text_editor("Overlay Directory", "Directory", &overlay.name,
+    let overlay_format_state = &pack_info_state.overlay_state.overlay_format_states[index];
+
+    let text_editor = widgets::submit_text_editor("Overlay Directory", "Directory", &overlay_format_state.name,
         move |s| WidgetCallbackChannel::PackInfo(DatapackCallbackType::Overlay(
             ListEvent::Edit(OverlayEditEvent::Name(s), index)
+        )),
+        WidgetCallbackChannel::PackInfo(DatapackCallbackType::Overlay(
+            ListEvent::Edit(OverlayEditEvent::SubmitName, index)
         )));
 
     let format_type = widgets::dropdown(Some("Format: "), &pack_info_state.overlay_state.overlay_format_states[index].format_type,
@@ -486,8 +674,17 @@ fn overlay_gui<'a>(
         .push(max_format)
         .spacing(SPACING_LARGE);
 
-    Some(container(Column::new()
-        .push(text_editor)
+    let mut widget = Column::new()
+        .push(text_editor);
+
+    if let Some(error) = &overlay_format_state.name_error {
+        widget = widget.push(widget::text(error).style(window::ERROR_COLOR));
+    }
+    else if overlay_format_state.name != overlay.name {
+        widget = widget.push(widget::text("Press enter to rename the overlay"));
+    }
+
+    Some(container(widget
         .push(formats)
         .spacing(5)
     ).into())
@@ -498,6 +695,7 @@ fn overlay_gui<'a>(
 #[derive(Clone, Debug)]
 pub enum OverlayEditEvent {
     Name(String),
+    SubmitName,
     Format(FormatEvent)
 }
 
@@ -512,7 +710,7 @@ struct OverlayState {
 impl OverlayState {
     fn new(datapack: &Datapack) -> Self {
         let overlays = datapack.overlays();
-        let overlay_format_states = overlays.iter().map(|overlay| OverlayFormatState::new(overlay)).collect();
+        let overlay_format_states = overlays.iter().map(OverlayFormatState::new).collect();
 
         Self {
             list_state: ListState::new(overlays.len()),
@@ -525,6 +723,9 @@ impl OverlayState {
 
 #[derive(Clone, Debug)]
 struct OverlayFormatState {
+    // The name being typed, which is applied to the overlay once submitted
+    name: String,
+    name_error: Option<String>,
     format_type: DropdownState<FormatType>,
     min: DropdownState<DatapackFormat>,
     max: DropdownState<DatapackFormat>,
@@ -538,9 +739,56 @@ impl OverlayFormatState {
         let format_type = if min == max { FormatType::Single } else { FormatType::Range };
 
         Self {
+            name: overlay.name.clone(),
+            name_error: None,
             format_type: DropdownState::new(format_type),
             min: DropdownState::new(min),
             max: DropdownState::new(max),
         }
     }
-}
\ No newline at end of file
+}
+
+//------------//
+
+//------ Filters ------//
+
+fn filter_gui<'a>(
+    filter: &Filter,
+    index: usize
+) -> Option<Element<'a, Message, <ApplicationWindow as Application>::Theme>> {
+    let namespace = widgets::text_editor("Namespace Pattern", "Any namespace", filter.namespace(),
+        move |s| WidgetCallbackChannel::PackInfo(DatapackCallbackType::Filter(
+            ListEvent::Edit(FilterEditEvent::Namespace(s), index)
+        )));
+    let path = widgets::text_editor("Path Pattern", "Any path", filter.path(),
+        move |s| WidgetCallbackChannel::PackInfo(DatapackCallbackType::Filter(
+            ListEvent::Edit(FilterEditEvent::Path(s), index)
+        )));
+
+    let mut widget = Column::new()
+        .push(namespace)
+        .push(path)
+        .spacing(5);
+
+    if let Err(error) = filter.validate() {
+        widget = widget.push(widget::text(error.context().message.clone())
+            .style(window::ERROR_COLOR));
+    }
+
+    Some(container(widget).into())
+}
+
+fn filter_summary(filter: &Filter) -> String {
+    let namespace = if filter.namespace().is_empty() { "*" } else { filter.namespace() };
+    let path = if filter.path().is_empty() { "*" } else { filter.path() };
+
+    format!("{}:{}", namespace, path)
+}
+
+//------------//
+
+#[derive(Clone, Debug)]
+pub enum FilterEditEvent {
+    Namespace(String),
+    Path(String)
+}
@@ -0,0 +1,200 @@
+use std::fs;
+use std::io;
+use std::sync::Arc;
+use crate::data::datapack::DatapackError;
+use crate::data::project::Project;
+use crate::data::vanilla::VanillaData;
+use crate::gui::window;
+
+//////////////////////////////
+//------ Unsaved Work ------//
+//////////////////////////////
+
+/// Unsaved work left in the recovery directory, and what changed in it since the project was last saved
+#[derive(Debug, Clone)]
+pub(crate) struct Recovery {
+    pub project: Project,
+    pub changes: Vec<String>
+}
+
+impl Recovery {
+    /// Looks for unsaved work in the recovery directory, comparing it with the project saved in the project directory
+    pub fn find(recovery_directory: &str, project_directory: &str, vanilla: &Arc<VanillaData>) -> Result<Option<Self>, DatapackError> {
+        if !Project::exists(recovery_directory) {
+            return Ok(None)
+        }
+
+        let (project, _) = Project::load_rebased(recovery_directory, vanilla.clone())?;
+        let saved_directory = window::pack_path(project_directory, &project.datapack);
+
+        let changes = if Project::exists(&saved_directory) {
+            project.changes_since(&Project::load_rebased(&saved_directory, vanilla.clone())?.0)?
+        }
+        else {
+            vec![format!("{} has never been saved as a project", project.datapack.name())]
+        };
+
+        // An autosave identical to the last save has nothing worth restoring
+        if changes.is_empty() {
+            remove(recovery_directory)?;
+            return Ok(None)
+        }
+
+        Ok(Some(Self { project, changes }))
+    }
+}
+
+pub(crate) fn remove(recovery_directory: &str) -> io::Result<()> {
+    match fs::remove_dir_all(recovery_directory) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(())
+    }
+}
+
+//------------//
+
+/// How an autosave went, once it is done
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AutosaveResult {
+    Saved,
+    Failed,
+    // The project was saved while the autosave was running, so the copy it left behind is older than the save
+    Stale
+}
+
+/// Keeps track of unsaved changes and of the autosaves writing them to the recovery directory. Autosaves run in the
+/// background and cannot be stopped, so each one is tagged with the number of explicit saves made before it started.
+/// One which finishes after a later save is recognised as stale, rather than leaving a copy which looks unsaved
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AutosaveState {
+    // Whether the datapack changed since it was last saved or autosaved
+    has_unsaved_changes: bool,
+    // Whether the datapack changed since the project was last saved, which autosaves do not count as
+    has_changes_since_save: bool,
+    is_scheduled: bool,
+    saves: u64
+}
+
+impl AutosaveState {
+    /// Marks the datapack as changed, returning whether an autosave has to be scheduled for it
+    pub fn changed(&mut self) -> bool {
+        self.has_unsaved_changes = true;
+        self.has_changes_since_save = true;
+        !std::mem::replace(&mut self.is_scheduled, true)
+    }
+
+    /// Marks everything as saved, such as after saving the project or replacing the datapack.
+    /// Autosaves started before this are stale once they finish
+    pub fn saved(&mut self) {
+        self.has_unsaved_changes = false;
+        self.has_changes_since_save = false;
+        self.saves += 1;
+    }
+
+    /// Starts a scheduled autosave, returning the tag to finish it with, or None if there is nothing to save
+    pub fn start(&mut self) -> Option<u64> {
+        self.is_scheduled = false;
+        std::mem::replace(&mut self.has_unsaved_changes, false).then_some(self.saves)
+    }
+
+    /// Drops the scheduled autosave without saving, keeping the changes for the next one
+    pub fn postpone(&mut self) {
+        self.is_scheduled = false;
+    }
+
+    /// Finishes the autosave with the given tag. A stale copy should be removed, and if the datapack changed since
+    /// the save, [`AutosaveState::has_changes_since_save`] tells that another autosave is needed to replace it
+    pub fn finish(&mut self, tag: u64, succeeded: bool) -> AutosaveResult {
+        if tag != self.saves {
+            return AutosaveResult::Stale
+        }
+
+        if !succeeded {
+            self.has_unsaved_changes = true;
+            return AutosaveResult::Failed
+        }
+
+        AutosaveResult::Saved
+    }
+
+    pub fn has_changes_since_save(&self) -> bool { self.has_changes_since_save }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::datapack::{Datapack, SerializableDatapack};
+    use super::*;
+
+    #[test]
+    fn autosaves_finishing_after_a_save_are_stale() {
+        let mut autosave = AutosaveState::default();
+        assert!(autosave.changed());
+        assert!(!autosave.changed());
+
+        let tag = autosave.start().unwrap();
+        autosave.saved();
+        assert_eq!(autosave.finish(tag, true), AutosaveResult::Stale);
+        assert!(!autosave.has_changes_since_save());
+        assert_eq!(autosave.start(), None);
+
+        // Changes made after the save still need an autosave of their own
+        assert!(autosave.changed());
+        let tag = autosave.start().unwrap();
+        assert_eq!(autosave.finish(tag, true), AutosaveResult::Saved);
+    }
+
+    #[test]
+    fn stale_autosaves_are_redone_for_changes_after_the_save() {
+        let mut autosave = AutosaveState::default();
+        autosave.changed();
+        let tag = autosave.start().unwrap();
+        autosave.saved();
+        autosave.changed();
+
+        assert_eq!(autosave.finish(tag, true), AutosaveResult::Stale);
+        assert!(autosave.has_changes_since_save());
+    }
+
+    #[test]
+    fn failed_autosaves_are_retried() {
+        let mut autosave = AutosaveState::default();
+        autosave.changed();
+        let tag = autosave.start().unwrap();
+
+        assert_eq!(autosave.finish(tag, false), AutosaveResult::Failed);
+        assert!(autosave.changed());
+        assert!(autosave.start().is_some());
+    }
+
+    #[test]
+    fn recovery_holds_changes_since_the_last_save() {
+        let directory = std::env::temp_dir().join(format!("gaia_recovery_test_{}", std::process::id()));
+        let (recovery_directory, project_directory) = (directory.join("recovery"), directory.join("projects"));
+        let (recovery_directory, project_directory) = (recovery_directory.to_str().unwrap(), project_directory.to_str().unwrap());
+        let vanilla = Arc::new(VanillaData::new());
+
+        let files = crate::data::overlay::DatapackFiles::from([
+            (String::from("pack.mcmeta"), br#"{"pack": {"pack_format": 26, "description": "Test"}}"#.to_vec())
+        ]);
+        let datapack = Datapack::try_from(SerializableDatapack::from_files("test", &files).unwrap().datapack).unwrap();
+
+        assert!(Recovery::find(recovery_directory, project_directory, &vanilla).unwrap().is_none());
+
+        // Never saved as a project
+        Project::new(datapack.clone()).save(recovery_directory).unwrap();
+        let recovery = Recovery::find(recovery_directory, project_directory, &vanilla).unwrap().unwrap();
+        assert_eq!(recovery.changes.len(), 1);
+
+        // Identical to the saved project, so there is nothing to restore and the copy is removed
+        Project::new(datapack.clone()).save(&window::pack_path(project_directory, &datapack)).unwrap();
+        assert!(Recovery::find(recovery_directory, project_directory, &vanilla).unwrap().is_none());
+        assert!(!Project::exists(recovery_directory));
+
+        let mut changed = datapack.clone();
+        changed.filters_mut().push(crate::data::datapack::Filter::new("test", ""));
+        Project::new(changed).save(recovery_directory).unwrap();
+        assert!(!Recovery::find(recovery_directory, project_directory, &vanilla).unwrap().unwrap().changes.is_empty());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
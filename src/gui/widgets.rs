@@ -1,13 +1,14 @@
 use std::fmt::Display;
-use iced::{Alignment, Application, Element, Length, settings};
+use iced::{Alignment, Application, Element, Length};
 use iced::alignment::{Horizontal, Vertical};
 use iced::theme;
 use iced::widget::{self, Column, Row, Rule};
 use iced_aw::DropDown;
+use serde::{Deserialize, Serialize};
 use strum_macros::Display;
-use crate::gui::{font, widgets};
+use crate::gui::font;
 use crate::gui::pack_info::DatapackCallbackType;
-use crate::gui::window::{ApplicationWindow, Message};
+use crate::gui::window::{ApplicationWindow, Message, PackView};
 
 pub(crate) static MAX_CONTENT_WIDTH: f32 = 750.;
 
@@ -20,23 +21,14 @@ pub(crate) static STANDARD_RULE_WIDTH: f32 = 4.;
 
 #[derive(Debug, Clone)]
 pub enum WidgetCallbackChannel {
-    PackInfo(DatapackCallbackType)
+    PackInfo(DatapackCallbackType),
+    View(DropdownEvent<PackView>)
 }
 
 ///////////////////////////////
 //------ Basic Editors ------//
 ///////////////////////////////
 
-pub fn create_standard(element: Element<Message>) -> Element<Message> {
-    todo!()
-}
-
-pub fn create_collapsible(element: Element<Message>) -> Element<Message> {
-    todo!()
-}
-
-//------------//
-
 pub fn text_editor<'a, F>(
     label: &str,
     default: &str,
@@ -48,7 +40,7 @@ where F: Fn(String) -> WidgetCallbackChannel + 'a {
 
     Row::new()
         .push(widget::text(format!("{label}:")))
-        .push(widget::text_input(default, &*text)
+        .push(widget::text_input(default, &text)
             .on_input(move |s| {
                 Message::Input(callback_channel(s.replace("\\n", "\n")))
             }))
@@ -56,45 +48,28 @@ where F: Fn(String) -> WidgetCallbackChannel + 'a {
         .spacing(SPACING_LARGE)
 }
 
-/////////////////////////////////
-//------ Boolean Toggles ------//
-/////////////////////////////////
-
-pub fn boolean_toggle<'a, F>(
+/// A text editor which only reports its text once it is submitted with enter, along with every change to it
+pub fn submit_text_editor<'a, F>(
     label: &str,
-    state: bool,
-    callback_channel: F
+    default: &str,
+    text: &str,
+    callback_channel: F,
+    submit_channel: WidgetCallbackChannel
 ) -> Row<'a, Message, <ApplicationWindow as Application>::Theme>
-where F: Fn(bool) -> WidgetCallbackChannel + 'a {
-    let mut button_true = widget::button("True");
-    let mut button_false = widget::button("False");
-
-    if state {
-        button_true = button_true
-            .on_press(Message::Input(callback_channel(true)))
-            .style(theme::Button::Positive);
-        button_false = button_false
-            .on_press(Message::Input(callback_channel(false)))
-            .style(theme::Button::Secondary);
-    }
-    else {
-        button_true = button_true
-            .on_press(Message::Input(callback_channel(true)))
-            .style(theme::Button::Secondary);
-        button_false = button_false
-            .on_press(Message::Input(callback_channel(false)))
-            .style(theme::Button::Destructive);
-    }
-
-    // TODO: Style
-
+where F: Fn(String) -> WidgetCallbackChannel + 'a {
     Row::new()
-        .push(widget::text(format!("{label}: ")))
-        .push(button_true)
-        .push(button_false)
+        .push(widget::text(format!("{label}:")))
+        .push(widget::text_input(default, text)
+            .on_input(move |s| Message::Input(callback_channel(s)))
+            .on_submit(Message::Input(submit_channel)))
         .align_items(Alignment::Center)
+        .spacing(SPACING_LARGE)
 }
 
+/////////////////////////////////
+//------ Boolean Toggles ------//
+/////////////////////////////////
+
 pub fn boolean_toggle_optional<'a, F>(
     label: &str,
     state: Option<bool>,
@@ -243,7 +218,7 @@ where T: for<'a> DropdownOption<'a> {
 
 pub fn list<'a, T, EditEventType, InlineWidgetCreator, MessageCallback, ContentState>(
     label: &str,
-    data: &Vec<T>,
+    data: &[T],
     list_state: &ListState,
     content_state: &ContentState,
     settings: ListSettings<'a, T, ContentState>,
@@ -445,7 +420,7 @@ where
 
 //------------//
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ListState {
     collapsed: Vec<bool>
 }
@@ -457,6 +432,10 @@ impl ListState {
         }
     }
 
+    pub(crate) fn len(&self) -> usize {
+        self.collapsed.len()
+    }
+
     pub fn swap(&mut self, i: usize, j: usize) {
         self.collapsed.swap(i, j);
     }
@@ -466,7 +445,7 @@ impl ListState {
     }
 
     fn set_collapsed(&mut self, index: usize, collapsed: bool) {
-        if let Some(mut val) = self.collapsed.get_mut(index) {
+        if let Some(val) = self.collapsed.get_mut(index) {
             *val = collapsed;
         }
     }
@@ -500,10 +479,12 @@ where T: Default {
 
 //------------//
 
+pub type ExtendedWidgetCreator<'a, T, S> = Box<dyn Fn(&T, usize, &S) -> Option<Element<'a, Message, <ApplicationWindow as Application>::Theme>>>;
+
 pub enum ListInlineState<'a, T, S>
 where T: Default {
     Inline,
-    Extended(Box<dyn Fn(&T, usize, &S) -> Option<Element<'a, Message, <ApplicationWindow as Application>::Theme>>>)
+    Extended(ExtendedWidgetCreator<'a, T, S>)
 }
 
 //------------//
@@ -1,17 +1,57 @@
-use std::fmt::Debug;
+use std::collections::BTreeSet;
+use std::fmt::{Debug, Display, Formatter};
 use std::fs;
-use iced::{Application, Command, Element, executor, Font, Length, Renderer, Sandbox, Theme};
+use std::sync::Arc;
+use std::time::Duration;
+use iced::{Application, Command, Element, executor, Font, keyboard, Length, mouse, Renderer, Subscription, Theme};
 use iced::alignment::Vertical;
 use iced::theme::Button;
 use iced::widget::{self, Container, Row, Column, PaneGrid, Rule};
 use iced::widget::pane_grid::{self, Axis, TitleBar};
+use iced::futures::channel::oneshot;
 use lazy_static::lazy_static;
-use crate::data::datapack::{Datapack, SerializableDatapack};
-use crate::gui::{pack_info, widgets};
-use crate::gui::pack_info::PackInfoState;
-use crate::gui::widgets::WidgetCallbackChannel;
+use serde::{Deserialize, Serialize};
+use crate::data::datapack::{self as datapack, Datapack, DatapackError, DatapackFormat, Diagnostic, SerializableDatapack, Severity};
+use crate::data::diff::{ChangeKind, DatapackDiff};
+use crate::data::history::{DatapackEdit, EditHistory, FormatSelection};
+use crate::data::merge::{DatapackMerge, MergeSide};
+use crate::data::project::{self, Project};
+use crate::data::reference::{self, ReferenceIndex, ResourceKey};
+use crate::data::rename::ResourceRename;
+use crate::data::stack::DatapackStack;
+use crate::data::tag::{TagEntry, TagMembership, TagValue};
+use crate::data::util::{self, ResourceLocation};
+use crate::data::vanilla::{ResourceOrigin, VanillaComparison, VanillaData};
+use crate::gui::{pack_info, recovery, widgets};
+use crate::gui::recovery::{AutosaveResult, AutosaveState, Recovery};
+use crate::gui::pack_info::{PackInfoLayout, PackInfoState};
+use crate::gui::widgets::{DropdownOption, DropdownState, WidgetCallbackChannel};
 
 pub const MINECRAFT_FONT: &str = "MinecraftRegular";
+const EXPORT_DIRECTORY: &str = "export";
+const PROJECT_DIRECTORY: &str = "projects";
+// The open datapack is saved here in the background, so that unsaved work can be restored after a crash
+const RECOVERY_DIRECTORY: &str = "recovery";
+// Someone else's copy of the datapack, as a project, folder or zip, to merge into the open datapack
+const MERGE_DIRECTORY: &str = "merge";
+// The datapack as it was when a copy was handed off, the common base both copies are merged from
+const MERGE_BASE_DIRECTORY: &str = "merge_base";
+const AUTOSAVE_DELAY: Duration = Duration::from_secs(30);
+
+const VANILLA_DATAPACK: &str = "resources/data/1-20-4.zip";
+const TERRALITH_DATAPACK: &str = "resources/data/Terralith_1.20_v2.4.11.zip";
+// Vanilla data for each format it is available for, kept as a read only layer below the edited datapack
+const VANILLA_DATAPACKS: [&str; 1] = [VANILLA_DATAPACK];
+// Datapacks loaded below the edited datapack when stacking, lowest priority first
+const STACKED_DATAPACKS: [&str; 1] = [TERRALITH_DATAPACK];
+
+// Structures are placed in the biomes of their has_structure tag
+const DEFAULT_TAG_FILTER: &str = "has_structure/";
+const BIOME_TAG_REGISTRY: &str = "tags/worldgen/biome";
+
+pub(crate) const WARNING_COLOR: iced::Color = iced::Color::from_rgb(0.9, 0.75, 0.2);
+pub(crate) const ERROR_COLOR: iced::Color = iced::Color::from_rgb(0.9, 0.3, 0.3);
+pub(crate) const ADDED_COLOR: iced::Color = iced::Color::from_rgb(0.4, 0.8, 0.4);
 
 //------------//
 
@@ -19,25 +59,163 @@ pub const MINECRAFT_FONT: &str = "MinecraftRegular";
 pub enum Message {
     // Program functionality
     SwitchPacks,
+    SaveProject,
+    OpenProject,
+    ExportPack(ExportTarget),
+    StackPacks,
+    Undo,
+    Redo,
+    // Focus moved away from the field being typed into, so its edits are no longer undone together
+    EndEditGroup,
+    Compare(CompareTarget),
+    CloseComparison,
+    ResetToVanilla { path: String, field: String },
+    ToggleRegistry(String),
+    StorePatches(bool),
+    SelectResource(Option<ResourceKey>),
+    RenameInput(String),
+    NamespaceInput(String),
+    PreviewRename,
+    PreviewNamespaceMove,
+    ApplyRename,
+    CancelRename,
+    ShowPackInfo,
+    ShowTagMatrix,
+    TagFilter(String),
+    ToggleTagMember { tag: ResourceKey, biome: ResourceLocation },
+    HandOffCopy,
+    StartMerge,
+    ResolveConflict(usize, MergeSide),
+    ApplyMerge,
+    CancelMerge,
+    Autosave,
+    // The tag the autosave was started with, and whether it succeeded
+    Autosaved(u64, Result<(), String>),
+    RestoreRecovery,
+    DiscardRecovery,
     Input(WidgetCallbackChannel),
     // Pane grid functionality
     ResizedPane(pane_grid::ResizeEvent),
     ClickedPane(pane_grid::Pane),
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum ExportTarget {
+    Zip,
+    Folder,
+    // One zip per group of formats, for datapacks supporting formats from before overlays
+    Build
+}
+
+/// The datapack the edited datapack is compared against
+#[derive(Debug, Clone, Copy)]
+pub enum CompareTarget {
+    // The last explicit save of the project
+    SavedProject,
+    Terralith,
+    // The vanilla data for the format being viewed
+    Vanilla
+}
+
+//------------//
+
+/// How the editor was left, saved alongside a project
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct EditorState {
+    #[serde(default)]
+    pack_info: PackInfoLayout
+}
+
+//------------//
+
+/// Which version of the datapack is shown in the editor. Format views are read only
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PackView {
+    #[default]
+    Source,
+    Format(DatapackFormat)
+}
+
+impl Display for PackView {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackView::Source => write!(f, "Source"),
+            PackView::Format(datapack_format) => write!(f, "{}", datapack_format)
+        }
+    }
+}
+
+impl<'a> DropdownOption<'a> for PackView {
+    fn variants() -> &'a [Self] {
+        &PACK_VIEWS[..]
+    }
+}
+
+lazy_static! {
+    static ref PACK_VIEWS: Vec<PackView> = std::iter::once(PackView::Source)
+        .chain(datapack::DATAPACK_FORMATS.iter().map(|datapack_format| PackView::Format(*datapack_format)))
+        .collect();
+}
+
 //------------//
 
 #[derive(Debug, Clone)]
 pub enum MainContentState {
     PackInfo(PackInfoState),
-    Biome
+    // Which biomes each biome tag holds, such as the biomes each structure can spawn in
+    TagMatrix
 }
 
 //------------//
 
 pub struct ApplicationWindow {
     datapack: Datapack,
+    // Kept on the window rather than the editor page, so that switching pages keeps it
+    history: EditHistory,
+    // The datapack as the selected format loads it, when not viewing the source
+    view: Option<Datapack>,
+    view_state: DropdownState<PackView>,
     state: MainContentState,
+    status: Option<String>,
+    diagnostics: Vec<Diagnostic>,
+    stack_summary: Vec<String>,
+    // What the edited datapack is compared against, a name for it, and how the edited datapack differs from it
+    comparison: Option<(CompareTarget, String, DatapackDiff)>,
+    // Shared with projects, which store biomes overriding it as patches
+    vanilla: Arc<VanillaData>,
+    store_patches: bool,
+    // Patches which no longer applied cleanly to the vanilla data when the project was opened
+    rebase_issues: Vec<String>,
+    // Which data files are new or override vanilla, for the format being viewed
+    vanilla_comparison: Option<VanillaComparison>,
+    // Registries opened in the file tree
+    expanded_registries: BTreeSet<String>,
+    // Every reference between the resources of the edited datapack
+    references: ReferenceIndex,
+    // Resources defined outside the edited datapack, by vanilla or the stacked datapacks
+    available_resources: BTreeSet<ResourceKey>,
+    // The resource whose references are shown
+    selected_resource: Option<ResourceKey>,
+    // The new name and namespace typed in for the selected resource
+    rename_input: String,
+    namespace_input: String,
+    // A rename or namespace move shown before it is applied
+    rename: Option<ResourceRename>,
+    // The datapacks loaded between vanilla and the edited datapack by the last stack
+    stacked_datapacks: Vec<SerializableDatapack>,
+    // The members of every worldgen tag across vanilla, the stacked datapacks and the edited datapack, for the format being viewed
+    tag_membership: TagMembership,
+    // Only biome tags containing this are shown in the tag matrix
+    tag_filter: String,
+    // How the pack info view was left, saved with the project
+    pack_info_layout: PackInfoLayout,
+    // A merge with conflicts left to resolve before it can be applied
+    merge: Option<DatapackMerge>,
+    // Their copy being merged, which becomes the new common base once the merge is applied
+    merge_theirs: Option<Datapack>,
+    autosave: AutosaveState,
+    // Work found in the recovery directory on launch, until the user restores or discards it
+    recovery: Option<Recovery>,
 
     panes: pane_grid::State<PaneState>,
     focus: Option<pane_grid::Pane>,
@@ -45,15 +223,26 @@ pub struct ApplicationWindow {
 
 impl Default for ApplicationWindow {
     fn default() -> Self {
-        let filepath_default = "resources/data/1-20-4.zip";
+        let (datapack, diagnostics, status) = match load_datapack(TERRALITH_DATAPACK).or_else(|_| load_datapack(VANILLA_DATAPACK)) {
+            Ok((datapack, diagnostics)) => (datapack, diagnostics, None),
+            Err(error) => {
+                let datapack = Datapack::new(String::from("Untitled"), Vec::new(), DatapackFormat::default(),
+                    DatapackFormat::default(), DatapackFormat::default(), Vec::new(), Vec::new());
+                (datapack, Vec::new(), Some(format!("Could not open the default datapack, starting with an empty one: {}", error)))
+            }
+        };
 
-        let serialized_datapack = SerializableDatapack::from_zip(filepath_default).unwrap();
-        let datapack = Datapack::try_from(serialized_datapack).unwrap();
+        let (vanilla, status) = match VanillaData::from_zips(&VANILLA_DATAPACKS) {
+            Ok(vanilla) => (Arc::new(vanilla), status),
+            Err(error) => (Arc::new(VanillaData::new()), Some(format!("Could not load the vanilla data: {}", error)))
+        };
 
-        let filepath_terralith = "resources/data/Terralith_1.20_v2.4.11.zip";
+        let (recovery, status) = match Recovery::find(RECOVERY_DIRECTORY, PROJECT_DIRECTORY, &vanilla) {
+            Ok(recovery) => (recovery, status),
+            Err(error) => (None, Some(format!("Could not read unsaved work from {}: {}", RECOVERY_DIRECTORY, error)))
+        };
 
-        let datapack = SerializableDatapack::from_zip(filepath_terralith).unwrap();
-        let datapack = Datapack::try_from(datapack).unwrap();
+        let available_resources = vanilla.datapacks().flat_map(reference::resource_keys).collect();
 
         let file_tree_pane = PaneState::new(PaneType::FileTree);
         let main_content_pain = PaneState::new(PaneType::MainContent);
@@ -74,13 +263,42 @@ impl Default for ApplicationWindow {
 
         let state = MainContentState::PackInfo(PackInfoState::new(&datapack));
 
-        Self {
+        let mut window = Self {
             datapack,
+            history: EditHistory::new(),
+            view: None,
+            view_state: DropdownState::default(),
             state,
+            status,
+            diagnostics,
+            stack_summary: Vec::new(),
+            comparison: None,
+            vanilla,
+            store_patches: false,
+            rebase_issues: Vec::new(),
+            vanilla_comparison: None,
+            expanded_registries: BTreeSet::new(),
+            references: ReferenceIndex::default(),
+            available_resources,
+            selected_resource: None,
+            rename_input: String::new(),
+            namespace_input: String::new(),
+            rename: None,
+            stacked_datapacks: Vec::new(),
+            tag_membership: TagMembership::default(),
+            tag_filter: String::from(DEFAULT_TAG_FILTER),
+            pack_info_layout: PackInfoLayout::default(),
+            merge: None,
+            merge_theirs: None,
+            autosave: AutosaveState::default(),
+            recovery,
 
             panes,
             focus: None
-        }
+        };
+
+        window.refresh_resources();
+        window
     }
 }
 
@@ -106,29 +324,413 @@ impl Application for ApplicationWindow {
             //------ Program Functionality ------//
             ///////////////////////////////////////
             SwitchPacks => {
-                let filepath_default = "resources/data/1-20-4.zip";
-                let filepath_terralith = "resources/data/Terralith_1.20_v2.4.11.zip";
+                let filepath = if self.datapack.name() == "1-20-4" { TERRALITH_DATAPACK } else { VANILLA_DATAPACK };
+
+                match load_datapack(filepath) {
+                    Ok((datapack, diagnostics)) => {
+                        self.open_datapack(datapack, diagnostics);
+                        self.status = None;
+                    }
+                    Err(error) => self.status = Some(format!("Could not open {}: {}", filepath, error))
+                }
+            }
+            SaveProject => {
+                let directory = pack_path(PROJECT_DIRECTORY, &self.datapack);
+
+                self.status = Some(match self.project().save(&directory) {
+                    Ok(_) => {
+                        // Everything is saved, so there is nothing left to recover, unless it is still waiting to be restored.
+                        // Autosaves still running are stale from now on, and clear the copy they leave once they finish
+                        self.autosave.saved();
+                        match if self.recovery.is_none() { recovery::remove(RECOVERY_DIRECTORY) } else { Ok(()) } {
+                            Ok(_) => format!("Saved project to {}", directory),
+                            Err(error) => format!("Saved project to {}, but could not clear {}: {}", directory, RECOVERY_DIRECTORY, error)
+                        }
+                    }
+                    Err(error) => format!("Saving project failed: {}", error)
+                });
+            }
+            OpenProject => {
+                let directory = pack_path(PROJECT_DIRECTORY, &self.datapack);
+
+                match Project::load_rebased(&directory, self.vanilla.clone()) {
+                    Ok((project, report)) => {
+                        self.open_project(project);
+                        self.rebase_issues = report.lines();
+                        self.status = Some(match self.rebase_issues.len() {
+                            0 => format!("Opened project {}", directory),
+                            issues => format!("Opened project {}, {} patched fields need another look", directory, issues)
+                        });
+                    }
+                    Err(error) => self.status = Some(format!("Opening project failed: {}", error))
+                }
+            }
+            ExportPack(target) => {
+                let errors: Vec<String> = self.datapack.validate().into_iter()
+                    .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                    .map(|diagnostic| diagnostic.to_string())
+                    .collect();
+
+                if !errors.is_empty() {
+                    self.status = Some(format!("Export cancelled, the pack info has problems:\n{}", errors.join("\n")));
+                    return Command::none()
+                }
+
+                let datapack = SerializableDatapack::from(self.datapack.clone());
 
-                if self.datapack.name() == "1-20-4" {
-                    let datapack = SerializableDatapack::from_zip(filepath_terralith).unwrap();
-                    self.datapack = Datapack::try_from(datapack).unwrap();
+                let export_result = match target {
+                    ExportTarget::Zip => {
+                        let filepath = format!("{}.zip", pack_path(EXPORT_DIRECTORY, &self.datapack));
+                        fs::create_dir_all(EXPORT_DIRECTORY)
+                            .map_err(DatapackError::from)
+                            .and_then(|_| datapack.to_zip(&filepath))
+                            .map(|_| format!("Exported to {}", filepath))
+                    }
+                    ExportTarget::Folder => {
+                        let filepath = pack_path(EXPORT_DIRECTORY, &self.datapack);
+                        datapack.to_directory(&filepath)
+                            .map(|_| format!("Exported to {}", filepath))
+                    }
+                    ExportTarget::Build => datapack.build(EXPORT_DIRECTORY)
+                        .map(|report| report.to_string())
+                };
+
+                self.status = Some(match export_result {
+                    Ok(message) => message,
+                    Err(error) => format!("Export failed: {}", error)
+                });
+            }
+            StackPacks => {
+                let mut stack = DatapackStack::new();
+                let mut failed = Vec::new();
+
+                match self.vanilla.require(self.datapack.root_format()) {
+                    Ok(vanilla) => stack.push_vanilla(vanilla.clone()),
+                    Err(error) => failed.push(error.to_string())
+                }
+
+                self.stacked_datapacks.clear();
+                for filepath in STACKED_DATAPACKS {
+                    match SerializableDatapack::from_zip(filepath) {
+                        Ok(import) => if import.datapack.name() != self.datapack.name() {
+                            self.available_resources.extend(reference::resource_keys(&import.datapack));
+                            self.stacked_datapacks.push(import.datapack.clone());
+                            stack.push(import.datapack)
+                        },
+                        Err(error) => failed.push(format!("{}: {}", filepath, error))
+                    }
+                }
+
+                stack.push(SerializableDatapack::from(self.datapack.clone()));
+
+                self.stack_summary = stack.resolve().summary();
+                self.refresh_resources();
+                self.status = if failed.is_empty() { None } else { Some(format!("Some datapacks could not be stacked: {}", failed.join("; "))) };
+            }
+            Undo | Redo => {
+                if self.view.is_some() {
+                    self.status = Some(String::from("Format views are read only, switch back to the source to make changes"));
+                    return Command::none()
+                }
+
+                let changed = if matches!(message, Undo) { self.history.undo(&mut self.datapack) }
+                    else { self.history.redo(&mut self.datapack) };
+
+                if changed {
+                    if let PackInfo(_) = self.state {
+                        self.state = PackInfo(self.pack_info_state(&self.datapack));
+                    }
+                    self.refresh_resources();
+                    return self.schedule_autosave()
+                }
+            }
+            Compare(target) => {
+                match self.compare(target) {
+                    Ok((label, diff)) => {
+                        self.status = diff.is_empty().then(|| format!("No differences from {}", label));
+                        self.comparison = Some((target, label, diff));
+                    }
+                    Err(error) => self.status = Some(error)
+                }
+            }
+            CloseComparison => self.comparison = None,
+            ResetToVanilla { path, field } => {
+                if self.view.is_some() {
+                    self.status = Some(String::from("Format views are read only, switch back to the source to make changes"));
+                    return Command::none()
+                }
+
+                match self.vanilla.reset_field(&self.datapack, &path, &field, self.displayed_format()) {
+                    Ok((resource_location, biome)) => {
+                        let before = self.datapack.biomes_mut().insert(resource_location.clone(), biome.clone());
+                        self.history.record(DatapackEdit::Biome {
+                            resource_location,
+                            before: before.map(Box::new),
+                            after: Some(Box::new(biome))
+                        }, None);
+
+                        self.refresh_resources();
+                        self.status = Some(format!("Reset {} of {} to vanilla", field, path));
+                        return self.schedule_autosave()
+                    }
+                    Err(error) => self.status = Some(format!("Could not reset {} to vanilla: {}", field, error))
+                }
+            }
+            StorePatches(store_patches) => self.store_patches = store_patches,
+            SelectResource(key) => {
+                self.rename_input = key.as_ref().map(|key| key.location.to_string()).unwrap_or_default();
+                self.selected_resource = key;
+            }
+            RenameInput(text) => self.rename_input = text,
+            NamespaceInput(text) => self.namespace_input = text,
+            PreviewRename | PreviewNamespaceMove => {
+                if self.view.is_some() {
+                    self.status = Some(String::from("Format views are read only, switch back to the source to make changes"));
+                    return Command::none()
+                }
+                let Some(key) = &self.selected_resource else { return Command::none() };
+
+                let rename = match message {
+                    PreviewRename => match ResourceKey::parse(&key.registry, &self.rename_input) {
+                        Some(to) => self.datapack.rename_resource(key, to.location),
+                        None => Err(DatapackError::File(format!("{} is not a valid name", self.rename_input).into()))
+                    },
+                    _ => self.datapack.move_namespace(key.location.namespace(), &self.namespace_input)
+                };
+
+                match rename {
+                    Ok(rename) => self.rename = Some(rename),
+                    Err(error) => self.status = Some(format!("Could not rename {}: {}", key, error))
+                }
+            }
+            ApplyRename => {
+                if let Some(rename) = self.rename.take() {
+                    let renamed = rename.finish().and_then(|import| Ok((Datapack::try_from(import.datapack)?, import.diagnostics)));
+
+                    match renamed {
+                        Ok((datapack, diagnostics)) => {
+                            self.selected_resource = None;
+                            self.status = Some(format!("Made {} changes", rename.changes().len()));
+                            return self.replace_datapack(datapack, diagnostics)
+                        }
+                        Err(error) => {
+                            self.status = Some(format!("Rename failed: {}", error));
+                            self.rename = Some(rename);
+                        }
+                    }
+                }
+            }
+            CancelRename => self.rename = None,
+            ShowPackInfo => self.state = PackInfo(self.pack_info_state(self.displayed_datapack())),
+            ShowTagMatrix => self.state = TagMatrix,
+            TagFilter(text) => self.tag_filter = text,
+            ToggleTagMember { tag, biome } => {
+                if self.view.is_some() {
+                    self.status = Some(String::from("Format views are read only, switch back to the source to make changes"));
+                    return Command::none()
+                }
+
+                let value = TagValue::Resource(biome.clone());
+                let is_member = self.tag_membership.contains(&tag, &biome);
+
+                if is_member && !self.tag_membership.is_direct_member(&tag, &biome) {
+                    self.status = Some(format!("{} is part of {} through a nested tag, remove it from that tag instead", biome, tag));
+                    return Command::none()
+                }
+
+                let mut replaces_tag = false;
+                let edit = if is_member {
+                    // Entries from lower priority packs can only be taken out by replacing the whole tag. The edited datapack
+                    // is loaded last, so its own entries come after theirs
+                    let entries = self.tag_membership.entries(&tag);
+
+                    self.datapack.edit_tag(&tag, |tag_data| {
+                        let lower = &entries[..entries.len().saturating_sub(tag_data.values.len())];
+
+                        if lower.iter().any(|entry| entry.value == value) {
+                            // The copied entries are optional, as the packs defining them may not be loaded alongside this one
+                            let copied = lower.iter()
+                                .filter(|entry| entry.value != value)
+                                .map(|entry| TagEntry { value: entry.value.clone(), required: false });
+                            let own: Vec<TagEntry> = tag_data.values.iter().filter(|entry| entry.value != value).cloned().collect();
+
+                            tag_data.replace = true;
+                            tag_data.values = copied.chain(own).collect();
+                            replaces_tag = true;
+                        }
+                        else {
+                            tag_data.remove(&value);
+                        }
+                    })
                 }
                 else {
-                    let datapack = SerializableDatapack::from_zip(filepath_default).unwrap();
-                    self.datapack = Datapack::try_from(datapack).unwrap();
+                    self.datapack.edit_tag(&tag, |tag_data| tag_data.values.push(TagEntry::new(value.clone())))
+                };
+
+                match edit {
+                    Ok((path, data_holder)) => {
+                        let before = self.datapack.passthrough_mut().insert(path.clone(), data_holder.clone());
+                        self.history.record(DatapackEdit::Passthrough {
+                            path,
+                            before: before.map(Box::new),
+                            after: Some(Box::new(data_holder))
+                        }, None);
+
+                        self.refresh_resources();
+                        self.status = Some(match (is_member, replaces_tag) {
+                            (true, true) => format!("Removed {} {}, which now replaces the tag of lower priority packs with optional copies of their entries", biome, tag),
+                            (true, false) => format!("Removed {} {}", biome, tag),
+                            (false, _) => format!("Added {} {}", biome, tag)
+                        });
+                        return self.schedule_autosave()
+                    }
+                    Err(error) => self.status = Some(format!("Could not change {}: {}", tag, error))
+                }
+            }
+            ToggleRegistry(registry) => {
+                if !self.expanded_registries.remove(&registry) {
+                    self.expanded_registries.insert(registry);
                 }
+            }
+            HandOffCopy => {
+                let their_directory = pack_path(MERGE_DIRECTORY, &self.datapack);
+                let base_directory = pack_path(MERGE_BASE_DIRECTORY, &self.datapack);
 
-                self.state = PackInfo(PackInfoState::new(&self.datapack));
+                // The copy and the base are the same project, so that a merge later only sees what changed since
+                let copy = self.project();
+                self.status = Some(match copy.save(&their_directory).and_then(|_| copy.save(&base_directory)) {
+                    Ok(_) => format!("Handed off a copy to {}, its changes can be merged back from there", their_directory),
+                    Err(error) => format!("Handing off a copy failed: {}", error)
+                });
+            }
+            StartMerge => {
+                if self.view.is_some() {
+                    self.status = Some(String::from("Format views are read only, switch back to the source to make changes"));
+                    return Command::none()
+                }
+
+                // Only the datapack as it was when the copy was handed off is a common base of both copies,
+                // merging from anything newer would revert the changes we made since
+                let base_directory = pack_path(MERGE_BASE_DIRECTORY, &self.datapack);
+                let their_path = pack_path(MERGE_DIRECTORY, &self.datapack);
+
+                if !Project::exists(&base_directory) {
+                    self.status = Some(format!("No common base is recorded in {}, hand off a copy before merging it back", base_directory));
+                    return Command::none()
+                }
+
+                let merge = Project::load_rebased(&base_directory, self.vanilla.clone()).and_then(|(base, base_report)| {
+                    let (theirs, their_issues) = load_their_copy(&their_path, self.vanilla.clone())?;
+
+                    // Patches which no longer apply cleanly are shown alongside the merge, as they may change what it merges
+                    self.rebase_issues = base_report.lines().into_iter().chain(their_issues).collect();
+                    let merge = base.datapack.merge(&self.datapack, &theirs)?;
+                    self.merge_theirs = Some(theirs);
+                    Ok(merge)
+                });
+
+                match merge {
+                    Ok(merge) if merge.conflicts().is_empty() => return self.apply_merge(merge),
+                    Ok(merge) => {
+                        self.status = Some(format!("{} conflicts to resolve before merging {}", merge.conflicts().len(), their_path));
+                        self.merge = Some(merge);
+                    }
+                    Err(error) => self.status = Some(format!("Could not merge {} into {}: {}", their_path, self.datapack.name(), error))
+                }
+            }
+            ResolveConflict(index, side) => {
+                if let Some(merge) = &mut self.merge {
+                    merge.resolve(index, side);
+                }
+            }
+            ApplyMerge => {
+                if let Some(merge) = self.merge.take() {
+                    return self.apply_merge(merge)
+                }
+            }
+            CancelMerge => {
+                self.merge = None;
+                self.merge_theirs = None;
+            }
+            EndEditGroup => self.history.end_group(),
+            Autosave => {
+                // Unsaved work waiting to be restored must not be replaced before the user decides what to do with it
+                if self.recovery.is_some() {
+                    self.autosave.postpone();
+                    return Command::none()
+                }
+
+                let Some(tag) = self.autosave.start() else { return Command::none() };
+                let project = self.project();
+
+                return Command::perform(async move {
+                    project.save(RECOVERY_DIRECTORY).map_err(|error| error.to_string())
+                }, move |result| Autosaved(tag, result))
+            }
+            Autosaved(tag, result) => match self.autosave.finish(tag, result.is_ok()) {
+                AutosaveResult::Saved => {}
+                AutosaveResult::Failed => {
+                    self.status = Some(format!("Autosave failed: {}", result.err().unwrap_or_default()));
+                }
+                AutosaveResult::Stale => {
+                    if let Err(error) = recovery::remove(RECOVERY_DIRECTORY) {
+                        self.status = Some(format!("Could not clear {}: {}", RECOVERY_DIRECTORY, error));
+                    }
+
+                    // Changes made since the save were only in the stale copy, so they are autosaved again
+                    if self.autosave.has_changes_since_save() {
+                        return self.schedule_autosave()
+                    }
+                }
+            },
+            RestoreRecovery => {
+                if let Some(recovery) = self.recovery.take() {
+                    self.open_project(recovery.project);
+                    self.status = Some(String::from("Restored unsaved work"));
+                    return self.schedule_autosave()
+                }
+            }
+            DiscardRecovery => {
+                self.recovery = None;
+                self.status = recovery::remove(RECOVERY_DIRECTORY).err()
+                    .map(|error| format!("Could not clear {}: {}", RECOVERY_DIRECTORY, error));
             }
             Input(callback_channel) => {
                 match callback_channel {
+                    WidgetCallbackChannel::View(event) => {
+                        widgets::handle_dropdown_event(event, &mut self.view_state);
+
+                        self.view = match self.view_state.selected {
+                            PackView::Source => None,
+                            PackView::Format(datapack_format) => match self.datapack.for_format(datapack_format) {
+                                Ok(datapack) => Some(datapack),
+                                Err(error) => {
+                                    self.status = Some(format!("Could not view datapack as {}: {}", datapack_format, error));
+                                    self.view_state = DropdownState::default();
+                                    None
+                                }
+                            }
+                        };
+
+                        self.state = PackInfo(self.pack_info_state(self.displayed_datapack()));
+                        self.refresh_resources();
+                    }
+                    WidgetCallbackChannel::PackInfo(_) if self.view.is_some() => {
+                        self.status = Some(String::from("Format views are read only, switch back to the source to make changes"));
+                    }
                     WidgetCallbackChannel::PackInfo(callback_type) => {
                         if let PackInfo(pack_info_state) = self.state.clone() {
+                            let layout = (FormatSelection::from_datapack(&self.datapack), self.datapack.overlays().clone());
                             let datapack = &mut self.datapack;
-                            self.state = PackInfo(pack_info::handle_datapack_update(datapack, callback_type, pack_info_state));
-                        }
-                        else {
-                            panic!("Illegal state - pack info callback requested while not in pack info state!")
+                            let pack_info_state = pack_info::handle_datapack_update(datapack, &mut self.history, callback_type, pack_info_state);
+                            self.pack_info_layout = pack_info_state.layout();
+                            self.state = PackInfo(pack_info_state);
+
+                            // Only the formats and overlays change which data each format sees
+                            if layout != (FormatSelection::from_datapack(&self.datapack), self.datapack.overlays().clone()) {
+                                self.refresh_resources();
+                            }
+                            return self.schedule_autosave()
                         }
                     }
                 }
@@ -147,22 +749,48 @@ impl Application for ApplicationWindow {
         Command::none()
     }
 
+    fn subscription(&self) -> Subscription<Self::Message> {
+        // Focused text inputs capture every key press, so shortcuts are read from all events instead of only ignored ones
+        iced::event::listen_with(|event, _status| {
+            let (key, modifiers) = match event {
+                iced::Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) => (key, modifiers),
+                // Text inputs do not report losing focus, so anything which can move it ends the current edit group
+                iced::Event::Mouse(mouse::Event::ButtonPressed(_)) | iced::Event::Window(_, iced::window::Event::Unfocused) => {
+                    return Some(Message::EndEditGroup)
+                }
+                _ => return None
+            };
+
+            match key.as_ref() {
+                keyboard::Key::Named(keyboard::key::Named::Tab) => Some(Message::EndEditGroup),
+                keyboard::Key::Character("z") if modifiers.command() && modifiers.shift() => Some(Message::Redo),
+                keyboard::Key::Character("z") if modifiers.command() => Some(Message::Undo),
+                keyboard::Key::Character("y") if modifiers.command() => Some(Message::Redo),
+                _ => None
+            }
+        })
+    }
+
     fn view(&self) -> Element<'_, Self::Message> {
         let header_menu = self.get_header();
 
-        let main_view = PaneGrid::new(&self.panes, |pane, state, is_maximized| {
+        let main_view = PaneGrid::new(&self.panes, |_, state, _| {
             let mut title: Row<'_, Message, Theme, Renderer> = Row::new();
 
             title = match state.pane_type {
                 PaneType::FileTree => {
-                    let title_text = &self.datapack.name();
+                    let title_text = &self.displayed_datapack().name();
                     title.push(widget::text(title_text))
                 }
                 PaneType::MainContent => {
                     title.push(widget::text("Pack Info"))
                 }
-                PaneType::Preview => {
-                    title.push(widget::text("Json Preview"))
+                PaneType::Preview => match (&self.merge, &self.rename, &self.comparison, &self.selected_resource) {
+                    (Some(_), _, _, _) => title.push(widget::text("Merge Conflicts")),
+                    (None, Some(_), _, _) => title.push(widget::text("Rename Preview")),
+                    (None, None, Some((_, label, _)), _) => title.push(widget::text(format!("Changes since {}", label))),
+                    (None, None, None, Some(key)) => title.push(widget::text(format!("References of {}", key))),
+                    (None, None, None, None) => title.push(widget::text("Json Preview"))
                 }
             };
 
@@ -200,6 +828,170 @@ impl Application for ApplicationWindow {
 }
 
 impl<'a> ApplicationWindow {
+    fn displayed_datapack(&self) -> &Datapack {
+        self.view.as_ref().unwrap_or(&self.datapack)
+    }
+
+    /// Replaces the edited datapack, resetting everything tied to the previous one
+    fn open_datapack(&mut self, datapack: Datapack, diagnostics: Vec<Diagnostic>) {
+        self.datapack = datapack;
+        self.diagnostics = diagnostics;
+        self.history = EditHistory::new();
+        self.merge = None;
+        self.merge_theirs = None;
+        self.rename = None;
+        self.store_patches = false;
+        self.rebase_issues.clear();
+        self.autosave.saved();
+        self.stack_summary.clear();
+        self.view = None;
+        self.view_state = DropdownState::default();
+        self.pack_info_layout = PackInfoLayout::default();
+
+        self.state = MainContentState::PackInfo(self.pack_info_state(&self.datapack));
+        self.refresh_resources();
+    }
+
+    /// Opens a saved project, along with how its editor was left
+    fn open_project(&mut self, project: Project) {
+        let diagnostics = project.datapack.validate();
+        let stores_patches = project.stores_patches();
+        let editor_state = project.editor_state.and_then(|editor_state| serde_json::from_value::<EditorState>(editor_state).ok());
+
+        self.open_datapack(project.datapack, diagnostics);
+        self.store_patches = stores_patches;
+
+        if let Some(editor_state) = editor_state {
+            self.pack_info_layout = editor_state.pack_info;
+            self.state = MainContentState::PackInfo(self.pack_info_state(&self.datapack));
+        }
+    }
+
+    fn pack_info_state(&self, datapack: &Datapack) -> PackInfoState {
+        PackInfoState::new(datapack).with_layout(&self.pack_info_layout)
+    }
+
+    /// The edited datapack as a project, ready to be saved
+    fn project(&self) -> Project {
+        self.project_of(self.datapack.clone())
+    }
+
+    /// A project for a datapack, stored the same way as the edited datapack
+    fn project_of(&self, datapack: Datapack) -> Project {
+        let editor_state = EditorState { pack_info: self.pack_info_layout.clone() };
+        let project = Project::new(datapack).with_editor_state(serde_json::to_value(editor_state).ok());
+        if self.store_patches { project.with_vanilla(self.vanilla.clone()) } else { project }
+    }
+
+    /// The format whose data is shown, which is the root format when viewing the source
+    fn displayed_format(&self) -> DatapackFormat {
+        match self.view_state.selected {
+            PackView::Source => self.datapack.root_format(),
+            PackView::Format(datapack_format) => datapack_format
+        }
+    }
+
+    /// Compares the edited datapack with another version of it, returning a name for the other version and the differences
+    fn compare(&self, target: CompareTarget) -> Result<(String, DatapackDiff), String> {
+        let compare_with = |label: String, other: Result<Datapack, DatapackError>| {
+            match other.and_then(|other| other.diff(&self.datapack)) {
+                Ok(diff) => Ok((label, diff)),
+                Err(error) => Err(format!("Could not compare with {}: {}", label, error))
+            }
+        };
+
+        match target {
+            CompareTarget::SavedProject => {
+                let directory = pack_path(PROJECT_DIRECTORY, &self.datapack);
+                let saved = Project::load_rebased(&directory, self.vanilla.clone()).map(|(project, _)| project.datapack);
+                compare_with(directory, saved)
+            }
+            CompareTarget::Terralith => compare_with(String::from(TERRALITH_DATAPACK), load_datapack(TERRALITH_DATAPACK).map(|(datapack, _)| datapack)),
+            CompareTarget::Vanilla => self.vanilla.compare(&SerializableDatapack::from(self.datapack.clone()), self.displayed_format())
+                .map(|comparison| (format!("vanilla (format {})", comparison.vanilla_format), comparison.diff))
+                .map_err(|error| format!("Could not compare with vanilla: {}", error))
+        }
+    }
+
+    /// Works out again what each data file refers to, which files are new or override vanilla,
+    /// and what each tag holds, after the data or the viewed format changed
+    fn refresh_resources(&mut self) {
+        let datapack = SerializableDatapack::from(self.datapack.clone());
+        self.references = ReferenceIndex::new(&datapack);
+
+        let datapack_format = self.displayed_format();
+        let layers: Vec<&SerializableDatapack> = self.vanilla.get(datapack_format).into_iter()
+            .chain(self.stacked_datapacks.iter())
+            .chain(std::iter::once(&datapack))
+            .collect();
+        self.tag_membership = TagMembership::resolve(&layers, datapack_format);
+
+        if self.vanilla.is_empty() {
+            return
+        }
+
+        match self.vanilla.compare(&datapack, self.displayed_format()) {
+            Ok(comparison) => {
+                if let Some((CompareTarget::Vanilla, label, diff)) = &mut self.comparison {
+                    *label = format!("vanilla (format {})", comparison.vanilla_format);
+                    *diff = comparison.diff.clone();
+                }
+                self.vanilla_comparison = Some(comparison);
+            }
+            Err(error) => {
+                self.vanilla_comparison = None;
+                self.status = Some(format!("Could not compare with the vanilla data: {}", error));
+            }
+        }
+    }
+
+    /// Replaces the edited datapack with a finished merge, as one edit which can be undone
+    fn apply_merge(&mut self, merge: DatapackMerge) -> Command<Message> {
+        let merged = merge.finish().and_then(|import| Ok((Datapack::try_from(import.datapack)?, import.diagnostics)));
+
+        match merged {
+            Ok((datapack, diagnostics)) => {
+                // Their copy now has nothing left to merge, so it is the base for their next changes
+                let base_directory = pack_path(MERGE_BASE_DIRECTORY, &self.datapack);
+                let saved_base = match self.merge_theirs.take() {
+                    Some(theirs) => self.project_of(theirs).save(&base_directory),
+                    None => Ok(())
+                };
+
+                self.status = Some(match saved_base {
+                    Ok(_) => String::from("Merged changes"),
+                    Err(error) => format!("Merged changes, but could not record their copy as the next common base: {}", error)
+                });
+                self.replace_datapack(datapack, diagnostics)
+            }
+            Err(error) => {
+                self.status = Some(format!("Merge failed: {}", error));
+                self.merge = Some(merge);
+                Command::none()
+            }
+        }
+    }
+
+    /// Replaces the whole edited datapack, as one edit which can be undone
+    fn replace_datapack(&mut self, datapack: Datapack, diagnostics: Vec<Diagnostic>) -> Command<Message> {
+        let before = std::mem::replace(&mut self.datapack, datapack);
+        self.history.record(DatapackEdit::Datapack { before: Box::new(before), after: Box::new(self.datapack.clone()) }, None);
+        self.diagnostics = diagnostics;
+        self.state = MainContentState::PackInfo(self.pack_info_state(&self.datapack));
+        self.refresh_resources();
+        self.schedule_autosave()
+    }
+
+    /// Marks the datapack as changed, and saves it to the recovery directory after a short delay.
+    /// Further changes before then are included in the same autosave
+    fn schedule_autosave(&mut self) -> Command<Message> {
+        if !self.autosave.changed() {
+            return Command::none()
+        }
+
+        Command::perform(wait(AUTOSAVE_DELAY), |_| Message::Autosave)
+    }
+
     fn get_header(&self) -> Container<'a, <ApplicationWindow as Application>::Message> {
         widget::container(
             Row::new()
@@ -220,9 +1012,72 @@ impl<'a> ApplicationWindow {
         widget::container(
             Column::new()
                 .push(widget::text("Pack Info"))
+                .push(widgets::dropdown(Some("View as:"), &self.view_state, WidgetCallbackChannel::View))
                 .push(widget::button(widget::text("Switch pack"))
                     .on_press(Message::SwitchPacks)
                     .style(Button::Primary))
+                .push(widget::button(widget::text("Save project"))
+                    .on_press(Message::SaveProject)
+                    .style(Button::Primary))
+                .push(widget::button(widget::text("Open project"))
+                    .on_press(Message::OpenProject)
+                    .style(Button::Primary))
+                .push(widget::button(widget::text("Export zip"))
+                    .on_press(Message::ExportPack(ExportTarget::Zip))
+                    .style(Button::Primary))
+                .push(widget::button(widget::text("Export folder"))
+                    .on_press(Message::ExportPack(ExportTarget::Folder))
+                    .style(Button::Primary))
+                .push(widget::button(widget::text("Build"))
+                    .on_press(Message::ExportPack(ExportTarget::Build))
+                    .style(Button::Primary))
+                .push(Row::new()
+                    .push(widget::button(widget::text("Undo"))
+                        .on_press_maybe(self.history.can_undo().then_some(Message::Undo))
+                        .style(Button::Secondary))
+                    .push(widget::button(widget::text("Redo"))
+                        .on_press_maybe(self.history.can_redo().then_some(Message::Redo))
+                        .style(Button::Secondary))
+                    .spacing(widgets::SPACING_SMALL))
+                .push(Row::new()
+                    .push(widget::button(widget::text("Compare with saved"))
+                        .on_press(Message::Compare(CompareTarget::SavedProject))
+                        .style(Button::Secondary))
+                    .push(widget::button(widget::text("Compare with Terralith"))
+                        .on_press(Message::Compare(CompareTarget::Terralith))
+                        .style(Button::Secondary))
+                    .push(widget::button(widget::text("Compare with vanilla"))
+                        .on_press_maybe((!self.vanilla.is_empty()).then_some(Message::Compare(CompareTarget::Vanilla)))
+                        .style(Button::Secondary))
+                    .spacing(widgets::SPACING_SMALL))
+                .push(Row::new()
+                    .push(widget::button(widget::text("Pack info"))
+                        .on_press(Message::ShowPackInfo)
+                        .style(Button::Secondary))
+                    .push(widget::button(widget::text("Biome tags"))
+                        .on_press(Message::ShowTagMatrix)
+                        .style(Button::Secondary))
+                    .spacing(widgets::SPACING_SMALL))
+                .push(Row::new()
+                    .push(widget::button(widget::text("Hand off copy"))
+                        .on_press(Message::HandOffCopy)
+                        .style(Button::Primary))
+                    .push(widget::button(widget::text("Merge"))
+                        .on_press(Message::StartMerge)
+                        .style(Button::Primary))
+                    .spacing(widgets::SPACING_SMALL))
+                .push(widget::button(widget::text("Stack packs"))
+                    .on_press(Message::StackPacks)
+                    .style(Button::Primary))
+                .push(widget::checkbox("Store vanilla overrides as patches", self.store_patches)
+                    .on_toggle(Message::StorePatches))
+                .push(self.get_recovery())
+                .push(widget::text(self.status.clone().unwrap_or_default()))
+                .push(self.get_rebase_issues())
+                .push(self.get_resource_tree())
+                .push(self.get_stack_summary())
+                .push(self.get_diagnostics())
+                .push(self.get_dangling_references())
                 .align_items(iced::Alignment::Start)
                 .spacing(10)
                 .width(Length::Fill)
@@ -232,16 +1087,386 @@ impl<'a> ApplicationWindow {
             .padding(5)
     }
 
-    fn get_content_view(&'a self) -> Container<'a, <ApplicationWindow as Application>::Message> {
-        let datapack = &self.datapack;
-        let pack_info_state = if let MainContentState::PackInfo(pack_info_state) = &self.state {
-            pack_info_state
+    fn get_diagnostics(&self) -> Element<'a, <ApplicationWindow as Application>::Message> {
+        if self.diagnostics.is_empty() {
+            return Column::new().into()
+        }
+
+        let entries = self.diagnostics.iter().map(|diagnostic| {
+            let color = match diagnostic.severity {
+                Severity::Warning => WARNING_COLOR,
+                Severity::Error => ERROR_COLOR,
+            };
+
+            widget::text(diagnostic.to_string())
+                .style(color)
+                .into()
+        });
+
+        Column::new()
+            .push(widget::text(format!("Diagnostics ({})", self.diagnostics.len())))
+            .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
+            .push(widget::scrollable(Column::with_children(entries).spacing(widgets::SPACING_SMALL)))
+            .spacing(widgets::SPACING_SMALL)
+            .into()
+    }
+
+    /// Shows each changed resource with its old values on the left and new values on the right.
+    /// Biome fields compared with vanilla can be reset to their vanilla value
+    fn get_comparison(&self, target: CompareTarget, diff: &DatapackDiff) -> Container<'a, <ApplicationWindow as Application>::Message> {
+        let summary = diff.summary().into_iter().map(|((registry, overlay), counts)| {
+            let registry = if registry.is_empty() { String::from("Other files") } else { registry };
+            let text = match overlay {
+                Some(overlay) => format!("{} in overlay {}: {}", registry, overlay, counts),
+                None => format!("{}: {}", registry, counts)
+            };
+            widget::text(text).into()
+        });
+
+        let resources = diff.resources.iter().map(|resource| {
+            let color = match resource.change {
+                ChangeKind::Added => ADDED_COLOR,
+                ChangeKind::Removed => ERROR_COLOR,
+                ChangeKind::Changed => WARNING_COLOR
+            };
+            let title = match &resource.overlay {
+                Some(overlay) => format!("{} {} (overlay {})", resource.change, resource.path, overlay),
+                None => format!("{} {}", resource.change, resource.path)
+            };
+
+            // Added and removed resources would show their entire contents, so only changed resources list fields
+            let fields = resource.fields.iter().filter(|_| resource.change == ChangeKind::Changed).map(|field| {
+                let value_text = |value: &Option<serde_json::Value>| widget::text(value.as_ref().map(|value| value.to_string()).unwrap_or_default())
+                    .font(Font::MONOSPACE)
+                    .width(Length::FillPortion(2));
+
+                let is_biome = resource.registry == "worldgen/biome";
+                let reset = matches!(target, CompareTarget::Vanilla).then(|| widget::button(widget::text("Reset"))
+                    .on_press_maybe(is_biome.then(|| Message::ResetToVanilla { path: resource.path.clone(), field: field.field.clone() }))
+                    .style(Button::Secondary));
+
+                Row::new()
+                    .push(widget::text(&field.field).width(Length::FillPortion(1)))
+                    .push(value_text(&field.old))
+                    .push(value_text(&field.new))
+                    .push_maybe(reset)
+                    .spacing(widgets::SPACING_SMALL)
+                    .into()
+            });
+
+            Column::new()
+                .push(widget::text(title).style(color))
+                .push(Column::with_children(fields).spacing(widgets::SPACING_SMALL))
+                .spacing(widgets::SPACING_SMALL)
+                .into()
+        });
+
+        widget::container(
+            Column::new()
+                .push(widget::button(widget::text("Close comparison"))
+                    .on_press(Message::CloseComparison)
+                    .style(Button::Secondary))
+                .push(Column::with_children(summary).spacing(widgets::SPACING_SMALL))
+                .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
+                .push(Row::new()
+                    .push(widget::text("Field").width(Length::FillPortion(1)))
+                    .push(widget::text("Old").width(Length::FillPortion(2)))
+                    .push(widget::text("New").width(Length::FillPortion(2)))
+                    .spacing(widgets::SPACING_SMALL))
+                .push(widget::scrollable(Column::with_children(resources).spacing(widgets::SPACING_LARGE)))
+                .spacing(10)
+                .width(Length::Fill)
+                .height(Length::Fill))
+            .width(Length::FillPortion(1))
+            .height(Length::Fill)
+            .padding(5)
+    }
+
+    /// Shows each conflict with the base, our and their values side by side, and which one has been picked
+    fn get_merge(&self, merge: &DatapackMerge) -> Container<'a, <ApplicationWindow as Application>::Message> {
+        let conflicts = merge.conflicts().iter().enumerate().map(|(index, conflict)| {
+            let color = if conflict.resolution.is_some() { ADDED_COLOR } else { WARNING_COLOR };
+
+            let sides = [MergeSide::Base, MergeSide::Ours, MergeSide::Theirs].into_iter().map(|side| {
+                let label = match side {
+                    MergeSide::Base => "Base",
+                    MergeSide::Ours => "Ours",
+                    MergeSide::Theirs => "Theirs"
+                };
+                let value = conflict.get(side).map(|value| value.to_string()).unwrap_or_else(|| String::from("(missing)"));
+                let style = if conflict.resolution == Some(side) { Button::Primary } else { Button::Secondary };
+
+                Column::new()
+                    .push(widget::button(widget::text(label))
+                        .on_press(Message::ResolveConflict(index, side))
+                        .style(style))
+                    .push(widget::text(value).font(Font::MONOSPACE))
+                    .spacing(widgets::SPACING_SMALL)
+                    .width(Length::FillPortion(1))
+                    .into()
+            });
+
+            Column::new()
+                .push(widget::text(conflict.to_string()).style(color))
+                .push(Row::with_children(sides).spacing(widgets::SPACING_SMALL))
+                .spacing(widgets::SPACING_SMALL)
+                .into()
+        });
+
+        let resolved = merge.conflicts().iter().filter(|conflict| conflict.resolution.is_some()).count();
+
+        widget::container(
+            Column::new()
+                .push(Row::new()
+                    .push(widget::button(widget::text("Apply merge"))
+                        .on_press_maybe(merge.is_resolved().then_some(Message::ApplyMerge))
+                        .style(Button::Primary))
+                    .push(widget::button(widget::text("Cancel merge"))
+                        .on_press(Message::CancelMerge)
+                        .style(Button::Secondary))
+                    .spacing(widgets::SPACING_SMALL))
+                .push(widget::text(format!("{} of {} conflicts resolved", resolved, merge.conflicts().len())))
+                .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
+                .push(widget::scrollable(Column::with_children(conflicts).spacing(widgets::SPACING_LARGE)))
+                .spacing(10)
+                .width(Length::Fill)
+                .height(Length::Fill))
+            .width(Length::FillPortion(1))
+            .height(Length::Fill)
+            .padding(5)
+    }
+
+    /// Lists every file a rename moves and every reference it rewrites
+    fn get_rename(&self, rename: &ResourceRename) -> Container<'a, <ApplicationWindow as Application>::Message> {
+        let changes = rename.changes().iter().map(|change| widget::text(change.to_string()).into());
+
+        widget::container(
+            Column::new()
+                .push(Row::new()
+                    .push(widget::button(widget::text("Apply rename"))
+                        .on_press(Message::ApplyRename)
+                        .style(Button::Primary))
+                    .push(widget::button(widget::text("Cancel rename"))
+                        .on_press(Message::CancelRename)
+                        .style(Button::Secondary))
+                    .spacing(widgets::SPACING_SMALL))
+                .push(widget::text(format!("{} changes", rename.changes().len())))
+                .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
+                .push(widget::scrollable(Column::with_children(changes).spacing(widgets::SPACING_SMALL)))
+                .spacing(10)
+                .width(Length::Fill)
+                .height(Length::Fill))
+            .width(Length::FillPortion(1))
+            .height(Length::Fill)
+            .padding(5)
+    }
+
+    fn get_recovery(&self) -> Element<'a, <ApplicationWindow as Application>::Message> {
+        let Some(recovery) = &self.recovery else {
+            return Column::new().into()
+        };
+
+        let entries = recovery.changes.iter().map(|change| widget::text(change).into());
+
+        Column::new()
+            .push(widget::text(format!("Unsaved work found for {}", recovery.project.datapack.name())).style(WARNING_COLOR))
+            .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
+            .push(widget::scrollable(Column::with_children(entries).spacing(widgets::SPACING_SMALL)))
+            .push(Row::new()
+                .push(widget::button(widget::text("Restore"))
+                    .on_press(Message::RestoreRecovery)
+                    .style(Button::Primary))
+                .push(widget::button(widget::text("Discard"))
+                    .on_press(Message::DiscardRecovery)
+                    .style(Button::Secondary))
+                .spacing(widgets::SPACING_SMALL))
+            .spacing(widgets::SPACING_SMALL)
+            .into()
+    }
+
+    /// Lists the data files of each registry, marking which are new and which override vanilla
+    fn get_resource_tree(&self) -> Element<'a, <ApplicationWindow as Application>::Message> {
+        let Some(comparison) = &self.vanilla_comparison else {
+            return Column::new().into()
+        };
+
+        let registries = comparison.registries().into_iter().map(|(registry, resources)| {
+            let count = |origin: ResourceOrigin| resources.iter().filter(|(_, resource_origin)| *resource_origin == origin).count();
+            let is_expanded = self.expanded_registries.contains(&registry);
+            let name = if registry.is_empty() { String::from("other files") } else { registry.clone() };
+
+            let header = widget::button(widget::text(format!("{} {} ({} new, {} overrides)",
+                if is_expanded { "-" } else { "+" }, name, count(ResourceOrigin::New), count(ResourceOrigin::Override))))
+                .on_press(Message::ToggleRegistry(registry))
+                .style(Button::Text);
+
+            let entries = resources.iter().filter(|_| is_expanded).map(|(path, origin)| {
+                let (marker, color) = match origin {
+                    ResourceOrigin::New => ("+", ADDED_COLOR),
+                    ResourceOrigin::Override => ("*", WARNING_COLOR),
+                    ResourceOrigin::Unchanged => ("=", iced::Color::WHITE)
+                };
+
+                widget::button(widget::text(format!("{} {}", marker, path)).style(color))
+                    .on_press_maybe(ResourceKey::from_path(path).map(|key| Message::SelectResource(Some(key))))
+                    .style(Button::Text)
+                    .padding(0)
+                    .into()
+            });
+
+            Column::new()
+                .push(header)
+                .push(Column::with_children(entries).padding([0, 0, 0, 20]))
+                .into()
+        });
+
+        Column::new()
+            .push(widget::text(format!("Data Files (vanilla format {})", comparison.vanilla_format)))
+            .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
+            .push(widget::scrollable(Column::with_children(registries).spacing(widgets::SPACING_SMALL)))
+            .spacing(widgets::SPACING_SMALL)
+            .into()
+    }
+
+    /// Shows where a resource is defined, what it refers to and what refers to it. Each resource listed can be opened in turn
+    fn get_references(&self, key: &ResourceKey) -> Container<'a, <ApplicationWindow as Application>::Message> {
+        // Only the datapack's own resources can be renamed
+        let rename = self.references.is_defined(key).then(|| Column::new()
+            .push(Row::new()
+                .push(widget::text_input("New name", &self.rename_input)
+                    .on_input(Message::RenameInput))
+                .push(widget::button(widget::text("Preview rename"))
+                    .on_press(Message::PreviewRename)
+                    .style(Button::Secondary))
+                .spacing(widgets::SPACING_SMALL))
+            .push(Row::new()
+                .push(widget::text_input("New namespace", &self.namespace_input)
+                    .on_input(Message::NamespaceInput))
+                .push(widget::button(widget::text(format!("Move namespace {}", key.location.namespace())))
+                    .on_press(Message::PreviewNamespaceMove)
+                    .style(Button::Secondary))
+                .spacing(widgets::SPACING_SMALL))
+            .spacing(widgets::SPACING_SMALL));
+
+        let definition = match self.references.definition(key) {
+            Some(path) => widget::text(format!("Defined in {}", path)),
+            None if self.available_resources.contains(key) => widget::text("Defined by vanilla or a stacked datapack"),
+            None => widget::text("Not defined anywhere").style(ERROR_COLOR)
+        };
+
+        let resource_button = |key: &ResourceKey, is_missing: bool| {
+            let text = widget::text(key.to_string()).style(if is_missing { ERROR_COLOR } else { iced::Color::WHITE });
+            widget::button(text)
+                .on_press(Message::SelectResource(Some(key.clone())))
+                .style(Button::Text)
+                .padding(0)
+                .width(Length::FillPortion(2))
+        };
+
+        let references_from = self.references.references_from(key);
+        let outgoing = references_from.iter().map(|reference| {
+            let is_missing = !self.references.is_defined(&reference.target) && !self.available_resources.contains(&reference.target);
+            Row::new()
+                .push(widget::text(&reference.field).font(Font::MONOSPACE).width(Length::FillPortion(1)))
+                .push(resource_button(&reference.target, is_missing && reference.required))
+                .spacing(widgets::SPACING_SMALL)
+                .into()
+        });
+
+        let usages = self.references.usages(key);
+        let incoming = usages.iter().map(|reference| {
+            Row::new()
+                .push(resource_button(&reference.source, false))
+                .push(widget::text(&reference.field).font(Font::MONOSPACE).width(Length::FillPortion(1)))
+                .spacing(widgets::SPACING_SMALL)
+                .into()
+        });
+
+        widget::container(
+            Column::new()
+                .push(widget::button(widget::text("Close references"))
+                    .on_press(Message::SelectResource(None))
+                    .style(Button::Secondary))
+                .push(definition)
+                .push_maybe(rename)
+                .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
+                .push(widget::scrollable(Column::new()
+                    .push(widget::text(format!("Refers to ({})", references_from.len())))
+                    .push(Column::with_children(outgoing).spacing(widgets::SPACING_SMALL))
+                    .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
+                    .push(widget::text(format!("Used by ({})", usages.len())))
+                    .push(Column::with_children(incoming).spacing(widgets::SPACING_SMALL))
+                    .spacing(widgets::SPACING_SMALL)))
+                .spacing(10)
+                .width(Length::Fill)
+                .height(Length::Fill))
+            .width(Length::FillPortion(1))
+            .height(Length::Fill)
+            .padding(5)
+    }
+
+    fn get_dangling_references(&self) -> Element<'a, <ApplicationWindow as Application>::Message> {
+        let dangling = self.references.dangling(&self.available_resources);
+        if dangling.is_empty() {
+            return Column::new().into()
+        }
+
+        let entries = dangling.iter().map(|reference| {
+            widget::button(widget::text(reference.to_string()).style(ERROR_COLOR))
+                .on_press(Message::SelectResource(Some(reference.source.clone())))
+                .style(Button::Text)
+                .padding(0)
+                .into()
+        });
+
+        Column::new()
+            .push(widget::text(format!("Missing References ({})", dangling.len())))
+            .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
+            .push(widget::scrollable(Column::with_children(entries).spacing(widgets::SPACING_SMALL)))
+            .spacing(widgets::SPACING_SMALL)
+            .into()
+    }
+
+    fn get_rebase_issues(&self) -> Element<'a, <ApplicationWindow as Application>::Message> {
+        if self.rebase_issues.is_empty() {
+            return Column::new().into()
         }
-        else {panic!("Main content hardcoded for pack info")};
+
+        let entries = self.rebase_issues.iter().map(|line| widget::text(line).style(WARNING_COLOR).into());
+
+        Column::new()
+            .push(widget::text(format!("Patches to Review ({})", self.rebase_issues.len())))
+            .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
+            .push(widget::scrollable(Column::with_children(entries).spacing(widgets::SPACING_SMALL)))
+            .spacing(widgets::SPACING_SMALL)
+            .into()
+    }
+
+    fn get_stack_summary(&self) -> Element<'a, <ApplicationWindow as Application>::Message> {
+        if self.stack_summary.is_empty() {
+            return Column::new().into()
+        }
+
+        let entries = self.stack_summary.iter().map(|line| widget::text(line).into());
+
+        Column::new()
+            .push(widget::text("Stacked Datapacks"))
+            .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
+            .push(widget::scrollable(Column::with_children(entries).spacing(widgets::SPACING_SMALL)))
+            .spacing(widgets::SPACING_SMALL)
+            .into()
+    }
+
+    fn get_content_view(&'a self) -> Container<'a, <ApplicationWindow as Application>::Message> {
+        let datapack = self.displayed_datapack();
+        let content: Element<'a, Message> = match &self.state {
+            MainContentState::PackInfo(pack_info_state) => pack_info::pack_info_gui(datapack, pack_info_state),
+            MainContentState::TagMatrix => self.get_tag_matrix()
+        };
 
         widget::container(
             Column::new()
-                .push(pack_info::pack_info_gui(datapack, pack_info_state))
+                .push(content)
                 .align_items(iced::Alignment::Start)
                 .spacing(10)
                 .width(Length::Fill)
@@ -252,8 +1477,94 @@ impl<'a> ApplicationWindow {
             .padding(5)
     }
 
+    /// Shows a grid of biomes against biome tags, where each cell adds the biome to or removes it from the tag.
+    /// Membership includes vanilla, the stacked datapacks and nested tags
+    fn get_tag_matrix(&self) -> Element<'a, <ApplicationWindow as Application>::Message> {
+        const BIOME_WIDTH: f32 = 220.;
+        const TAG_WIDTH: f32 = 110.;
+
+        let tags: Vec<&ResourceKey> = self.tag_membership.tags()
+            .filter(|tag| tag.registry == BIOME_TAG_REGISTRY && tag.location.id().contains(self.tag_filter.as_str()))
+            .collect();
+
+        let biomes: BTreeSet<ResourceLocation> = self.available_resources.iter()
+            .filter(|key| key.registry == "worldgen/biome")
+            .map(|key| key.location.clone())
+            .chain(self.displayed_datapack().biomes().keys().cloned())
+            .collect();
+
+        let header = tags.iter().map(|tag| {
+            let name = tag.location.id().strip_prefix(self.tag_filter.as_str()).unwrap_or(tag.location.id());
+            let color = if self.tag_membership.error(tag).is_some() { ERROR_COLOR } else { iced::Color::WHITE };
+
+            widget::text(name)
+                .size(12)
+                .style(color)
+                .width(Length::Fixed(TAG_WIDTH))
+                .into()
+        });
+
+        let rows = biomes.iter().map(|biome| {
+            let cells = tags.iter().map(|tag| {
+                let (tag, biome) = ((*tag).clone(), biome.clone());
+                let is_member = self.tag_membership.contains(&tag, &biome);
+
+                widget::container(widget::checkbox("", is_member)
+                    .on_toggle(move |_| Message::ToggleTagMember { tag: tag.clone(), biome: biome.clone() }))
+                    .width(Length::Fixed(TAG_WIDTH))
+                    .into()
+            });
+
+            Row::new()
+                .push(widget::text(biome.to_string()).width(Length::Fixed(BIOME_WIDTH)))
+                .push(Row::with_children(cells))
+                .into()
+        });
+
+        let errors = tags.iter()
+            .filter_map(|tag| Some(widget::text(format!("{} {}", tag, self.tag_membership.error(tag)?)).style(ERROR_COLOR).into()));
+
+        let direction = widget::scrollable::Direction::Both {
+            vertical: widget::scrollable::Properties::default(),
+            horizontal: widget::scrollable::Properties::default()
+        };
+
+        Column::new()
+            .push(Row::new()
+                .push(widget::text("Biome tags containing:"))
+                .push(widget::text_input(DEFAULT_TAG_FILTER, &self.tag_filter)
+                    .on_input(Message::TagFilter))
+                .align_items(iced::Alignment::Center)
+                .spacing(widgets::SPACING_LARGE))
+            .push(widget::text(format!("{} biomes, {} tags", biomes.len(), tags.len())))
+            .push(Column::with_children(errors).spacing(widgets::SPACING_SMALL))
+            .push(Rule::horizontal(widgets::STANDARD_RULE_WIDTH))
+            .push(widget::scrollable(Column::new()
+                    .push(Row::new()
+                        .push(widget::text("Biome").width(Length::Fixed(BIOME_WIDTH)))
+                        .push(Row::with_children(header)))
+                    .push(Column::with_children(rows).spacing(widgets::SPACING_SMALL))
+                    .spacing(widgets::SPACING_SMALL))
+                .direction(direction))
+            .spacing(10)
+            .into()
+    }
+
     fn get_preview(&self) -> Container<'a, <ApplicationWindow as Application>::Message> {
-        let datapack = &self.datapack;
+        if let Some(merge) = &self.merge {
+            return self.get_merge(merge)
+        }
+        if let Some(rename) = &self.rename {
+            return self.get_rename(rename)
+        }
+        if let Some((target, _, diff)) = &self.comparison {
+            return self.get_comparison(*target, diff)
+        }
+        if let Some(key) = &self.selected_resource {
+            return self.get_references(key)
+        }
+
+        let datapack = self.displayed_datapack();
 
         widget::container(
             Column::new()
@@ -270,6 +1581,42 @@ impl<'a> ApplicationWindow {
     }
 }
 
+/// The path a datapack is stored at in one of the editor's directories. The datapack name can be anything,
+/// so it is made into a file name which cannot point outside the directory
+pub(crate) fn pack_path(directory: &str, datapack: &Datapack) -> String {
+    format!("{}/{}", directory, util::to_file_name(datapack.name()))
+}
+
+fn load_datapack(filepath: &str) -> Result<(Datapack, Vec<Diagnostic>), DatapackError> {
+    let import = SerializableDatapack::from_zip(filepath)?;
+    let datapack = Datapack::try_from(import.datapack)?;
+
+    Ok((datapack, import.diagnostics))
+}
+
+/// Loads someone else's copy of the datapack, as a project, folder or zip, with the patches of a project which need another look
+fn load_their_copy(path: &str, vanilla: Arc<VanillaData>) -> Result<(Datapack, Vec<String>), DatapackError> {
+    if Project::exists(path) {
+        let (project, report) = Project::load_rebased(path, vanilla)?;
+        return Ok((project.datapack, report.lines()))
+    }
+
+    let import = project::load_datapack(path, None)?;
+    Ok((Datapack::try_from(import.datapack)?, Vec::new()))
+}
+
+/// Waits without blocking the executor, as the default executor has no timers
+async fn wait(duration: Duration) {
+    let (sender, receiver) = oneshot::channel();
+
+    std::thread::spawn(move || {
+        std::thread::sleep(duration);
+        let _ = sender.send(());
+    });
+
+    let _ = receiver.await;
+}
+
 //------------//
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,3 @@
+//! The datapack model behind Gaia. Datapacks can be loaded, edited, validated and built through it without the editor
+
+pub mod data;
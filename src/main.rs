@@ -1,10 +1,9 @@
-#![allow(unused)]
-
 use std::borrow::Cow;
-use iced::{Application, Font, Settings};
+use iced::{Application, Settings};
 use crate::gui::window::ApplicationWindow;
 
-mod data;
+use worlddatagen::data;
+
 mod gui;
 
 fn main() -> iced::Result {